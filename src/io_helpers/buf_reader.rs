@@ -21,3 +21,49 @@ pub fn get_bufreader(
         Ok(Box::new(io::BufReader::new(file)))
     }
 }
+
+/// A line read along with the byte offset it starts at
+pub type OffsetLine = (u64, io::Result<String>);
+
+/// Iterator over the lines of a reader like [`BufRead::lines`], also giving the byte offset
+/// each line starts at. Lines that are not valid UTF-8 are still counted towards the offsets
+pub struct OffsetLines<B> {
+    reader: B,
+    offset: u64,
+}
+
+impl<B: BufRead> Iterator for OffsetLines<B> {
+    type Item = OffsetLine;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.offset;
+        let mut buf = Vec::new();
+        match self.reader.read_until(b'\n', &mut buf) {
+            Ok(0) => None,
+            Ok(n) => {
+                self.offset += n as u64;
+                if buf.ends_with(b"\n") {
+                    buf.pop();
+                    if buf.ends_with(b"\r") {
+                        buf.pop();
+                    }
+                }
+                let line = String::from_utf8(buf)
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error));
+                Some((offset, line))
+            }
+            Err(error) => Some((offset, Err(error))),
+        }
+    }
+}
+
+pub trait ToOffsetLines: BufRead + Sized {
+    fn offset_lines(self) -> OffsetLines<Self> {
+        OffsetLines {
+            reader: self,
+            offset: 0,
+        }
+    }
+}
+
+impl<B: BufRead> ToOffsetLines for B {}
@@ -1,20 +1,22 @@
 use std::io;
-use std::io::BufRead;
 use std::sync::mpsc;
 use std::sync::mpsc::Receiver;
 use std::thread;
 
+use super::buf_reader::{OffsetLine, ToOffsetLines};
+
 pub trait BackgroundRead {
-    fn background_read_lines(self, bound: usize) -> Receiver<io::Result<String>>;
+    fn background_read_lines(self, bound: usize) -> Receiver<OffsetLine>;
 }
 
 impl BackgroundRead for io::Stdin {
     // https://stackoverflow.com/questions/30012995/how-can-i-read-non-blocking-from-stdin
-    /// Setup background thread to read input from stdin into a channel
-    fn background_read_lines(self, bound: usize) -> Receiver<io::Result<String>> {
-        let (tx, rx) = mpsc::sync_channel::<io::Result<String>>(bound);
+    /// Setup background thread to read input from stdin into a channel, along with the byte
+    /// offset of each line
+    fn background_read_lines(self, bound: usize) -> Receiver<OffsetLine> {
+        let (tx, rx) = mpsc::sync_channel::<OffsetLine>(bound);
         thread::spawn(move || {
-            for line in self.lock().lines() {
+            for line in self.lock().offset_lines() {
                 if tx.send(line).is_err() {
                     break;
                 };
@@ -1,20 +1,45 @@
+pub mod budget;
+pub mod clean;
+pub mod duplicate_keys;
+pub mod duplicates;
 pub mod errors;
+pub mod group;
+pub mod location;
+pub mod predicate;
+pub mod quarantine;
+pub mod query;
+pub mod repair;
+pub mod sample;
 pub mod stats;
+pub mod window;
 
 use std::fmt::Write;
 
-use crate::io_helpers::buf_reader::get_bufreader;
+use crate::io_helpers::buf_reader::{OffsetLine, ToOffsetLines, get_bufreader};
 use crate::io_helpers::stdin::BackgroundRead;
-use crate::json::paths::ValuePaths;
-use crate::json::{Value, ValueType};
+use crate::json::paths::{JSONDepth, ValuePaths};
+use crate::json::{IndexMap, Value, ValueType};
 use crate::{Cli, Settings};
 
-use self::errors::NDJSONError;
+pub use self::budget::{Budget, BudgetExceeded, Threshold};
+pub use self::clean::CleanCopy;
+pub use self::duplicate_keys::DuplicateKeys;
+use self::duplicates::{CandidateKeyCounter, DuplicateRecordCounter};
+pub use self::duplicates::{CandidateKeys, DuplicateRecords};
 use self::errors::collection::{
-    Errors, ErrorsPar, IndexedNDJSONError, IntoEnumeratedErrFiltered, IntoErrFiltered,
-    NDJSONProcessingErrors,
+    ErrorSink, IndexedNDJSONError, IntoEnumeratedErrFiltered, IntoErrFiltered,
 };
-pub use self::stats::{FileStats, Stats};
+use self::errors::{NDJSONError, ParseErrorKind};
+pub use self::group::{Group, GroupBy};
+pub use self::location::RecordLocation;
+pub use self::predicate::Predicate;
+pub use self::quarantine::Quarantine;
+pub use self::query::NamedQuery;
+use self::repair::{JoinSplitRecords, LineCandidate, RepairedLine, repair};
+pub use self::repair::{Repair, RepairCounts};
+pub use self::sample::{Sample, Sampling};
+pub use self::stats::{FileStats, LineIdLimits, LineIds, ParseErrorSummary, Section, Stats};
+pub use self::window::{LineRange, LineWindow, Window};
 
 use dashmap::DashMap;
 use indexmap::map::RawEntryApiV1;
@@ -34,10 +59,8 @@ use std::sync::mpsc::Receiver;
 
 // Reusable types for function signatures
 type IJSONCandidate = (usize, String);
-type IdJSON = (String, Value);
+type IdJSON = (RecordLocation, Value);
 type IdJSONIter<'a> = Box<dyn Iterator<Item = IdJSON> + 'a>;
-type NDJSONErrors = Errors<IndexedNDJSONError>;
-type NDJSONErrorsPar = ErrorsPar<IndexedNDJSONError>;
 
 trait Indexed: Iterator {
     fn indexed(self) -> Zip<RangeFrom<usize>, Self>
@@ -50,15 +73,124 @@ trait Indexed: Iterator {
 
 impl<T> Indexed for T where T: Iterator {}
 
-type IdJSONResult = (String, Result<Value, NDJSONError>);
-type IdJSONResultIter<'a> = Box<dyn Iterator<Item = IdJSONResult> + 'a>;
+type IdJSONResult = (RecordLocation, Result<Value, NDJSONError>);
+/// [`IdJSONResult`] tagged with the index of the `--jsonpath` query it was selected by,
+/// `None` when it applies to every query, and the `--group-by` group of its record if any
+type QueryIdJSONResult = (Option<usize>, Option<Group>, IdJSONResult);
+type QueryIdJSONResultIter<'a> = Box<dyn Iterator<Item = QueryIdJSONResult> + 'a>;
+
+/// What is found about the text of the lines of an input while parsing them, before their
+/// records are filtered by the `--where` predicates, selected by the queries and grouped
+#[derive(Debug, Default)]
+struct LineChecks {
+    /// Lines fixed by `--repair`
+    repairs: Option<RepairCounts>,
+    /// Lines with duplicate keys, from `--duplicate-keys`
+    duplicate_keys: Option<DuplicateKeys>,
+}
+
+impl LineChecks {
+    fn new(settings: &Settings) -> Self {
+        let args = &settings.args;
+        Self {
+            repairs: args.repair.then(RepairCounts::default),
+            duplicate_keys: args
+                .duplicate_keys
+                .then(|| DuplicateKeys::new(settings.line_id_limits, args.duplicate_keys_bad)),
+        }
+    }
+
+    /// Checks the `text` of the line `id`, failing for duplicate keys when they make bad
+    /// lines
+    fn check(&self, id: &RecordLocation, text: &str) -> Result<(), NDJSONError> {
+        if let Some(duplicate_keys) = &self.duplicate_keys {
+            duplicate_keys.check(id, text)?;
+        }
+        Ok(())
+    }
+
+    /// Adds what was found to the overall stats of each query, as the checks are of all the
+    /// lines read whatever the predicates, queries and groups
+    fn add_to(self, mut sections: IndexMap<Section, Stats>) -> IndexMap<Section, Stats> {
+        let repairs = self.repairs.map(RepairCounts::into_inner);
+        let duplicate_keys = self.duplicate_keys.map(DuplicateKeys::into_inner);
+        for (_, fs) in sections.iter_mut().filter(|(s, _)| s.group.is_none()) {
+            if let Some(repairs) = &repairs {
+                fs.repairs = repairs.clone();
+            }
+            if let Some(duplicate_keys) = &duplicate_keys {
+                fs.duplicate_keys = duplicate_keys.clone();
+            }
+        }
+        sections
+    }
+}
+
+/// Comparisons of the records selected by a `--jsonpath` query, once filtered by the
+/// `--where` predicates, for its overall stats
+#[derive(Debug, Default)]
+struct RecordChecks {
+    /// Records the same as an earlier one, from `--duplicates`
+    duplicate_records: Option<DuplicateRecordCounter>,
+    /// Distinct values of the scalar paths, from `--candidate-keys`
+    candidate_keys: Option<CandidateKeyCounter>,
+}
+
+impl RecordChecks {
+    fn new(settings: &Settings) -> Self {
+        let args = &settings.args;
+        Self {
+            duplicate_records: args.duplicates.then(|| {
+                DuplicateRecordCounter::new(
+                    settings.duplicates_on.clone(),
+                    args.max_exact_hashes(),
+                    settings.line_id_limits,
+                    !args.parallel,
+                )
+            }),
+            candidate_keys: args
+                .candidate_keys
+                .then(|| CandidateKeyCounter::new(args.max_exact_hashes())),
+        }
+    }
+
+    /// Compares the record `json` of the line `id` with those before it
+    fn check(&self, settings: &Settings, id: &RecordLocation, json: &Value) {
+        if let Some(duplicate_records) = &self.duplicate_records {
+            duplicate_records.add(id, json);
+        }
+        if let Some(candidate_keys) = &self.candidate_keys {
+            candidate_keys.add(json, &settings.walk_options);
+        }
+    }
 
+    /// Adds what was found to the overall stats `fs` of the query
+    fn add_to(self, settings: &Settings, fs: &mut Stats) {
+        fs.duplicate_records = self
+            .duplicate_records
+            .map(DuplicateRecordCounter::into_inner);
+        fs.candidate_keys = self
+            .candidate_keys
+            .map(|keys| keys.into_inner(&settings.walk_options.maps));
+    }
+}
+
+/// Parsing of the lines of NDJSON read along with their byte offsets, attempting `--repair` on
+/// those that fail to parse and checking the records as set up by the `checks`
 trait ToNDJSON<'a> {
-    fn parse_ndjson(self, args: &Cli) -> impl Iterator<Item = IdJSONResult> + 'a;
+    fn parse_ndjson(
+        self,
+        settings: &'a Settings,
+        checks: &'a LineChecks,
+    ) -> impl Iterator<Item = IdJSONResult> + 'a;
 }
 
 trait ToNDJSONPar<'a>: ToNDJSON<'a> {
-    fn parse_ndjson_par(self, args: &Cli) -> impl ParallelIterator<Item = IdJSONResult> + 'a;
+    fn parse_ndjson_par(
+        self,
+        settings: &'a Settings,
+        checks: &'a LineChecks,
+    ) -> impl ParallelIterator<Item = IdJSONResult> + 'a;
 }
 
 // trait ProcessesNDJSON<'a> {
@@ -70,36 +202,134 @@ trait ToNDJSONPar<'a>: ToNDJSON<'a> {
 // }
 
 // TODO: IntoIterator or Iterator?
-impl<'a, T: Iterator<Item = io::Result<String>> + 'a> ToNDJSON<'a> for T {
-    fn parse_ndjson(self, _args: &Cli) -> impl Iterator<Item = IdJSONResult> + 'a {
-        self.map(|result| result.map_err(|e| e.into()))
-            .indexed()
-            .map(|(i, json_candidate)| {
-                (
-                    i.to_string(),
-                    json_candidate
-                        .and_then(|jc| serde_json::from_str::<Value>(&jc).map_err(|e| e.into())),
-                )
-            })
+impl<'a, T: Iterator<Item = OffsetLine> + 'a> ToNDJSON<'a> for T {
+    fn parse_ndjson(
+        self,
+        settings: &'a Settings,
+        checks: &'a LineChecks,
+    ) -> impl Iterator<Item = IdJSONResult> + 'a {
+        let args = &settings.args;
+        let iter = self.indexed().map(located);
+        // limit the lines before parsing, so only those inspected are copied by `--clean-out`
+        let iter = sample(args, limit(args, iter));
+
+        let clean_copy = settings.clean_copy.as_deref();
+        JoinSplitRecords::new(iter, checks.repairs.is_some())
+            .flat_map(move |candidate| parse_candidate(candidate, clean_copy, checks))
     }
 }
 
-impl<'a, T: Iterator<Item = io::Result<String>> + Send + 'a> ToNDJSONPar<'a> for T {
-    fn parse_ndjson_par(self, args: &Cli) -> impl ParallelIterator<Item = IdJSONResult> + 'a {
-        let iter = self
-            .map(|result| result.map_err(|e| e.into()))
-            .indexed()
-            // limit the lines before moving to the parallel processing where the lines would become non-deterministic
-            .take(args.lines.unwrap_or(usize::MAX));
+impl<'a, T: Iterator<Item = OffsetLine> + Send + 'a> ToNDJSONPar<'a> for T {
+    fn parse_ndjson_par(
+        self,
+        settings: &'a Settings,
+        checks: &'a LineChecks,
+    ) -> impl ParallelIterator<Item = IdJSONResult> + 'a {
+        let args = &settings.args;
+        let iter = self.indexed().map(located);
+        // limit the lines before moving to the parallel processing where the lines would become non-deterministic
+        let iter = sample(args, limit(args, iter));
+
+        let clean_copy = settings.clean_copy.as_deref();
+        // split records are joined beforehand, as they need the lines in order
+        JoinSplitRecords::new(iter, checks.repairs.is_some())
+            .par_bridge()
+            .flat_map_iter(move |candidate| parse_candidate(candidate, clean_copy, checks))
+    }
+}
 
-        iter.par_bridge().map(|(i, json_candidate)| {
-            (
-                i.to_string(),
-                json_candidate
-                    .and_then(|jc| serde_json::from_str::<Value>(&jc).map_err(|e| e.into())),
-            )
+/// Locates the `i`th line read at byte `offset`
+fn located(
+    (i, (offset, line)): (usize, OffsetLine),
+) -> (RecordLocation, Result<String, NDJSONError>) {
+    (
+        RecordLocation::at(i, offset),
+        line.map_err(NDJSONError::from),
+    )
+}
+
+/// Parses a line as JSON, [`checked`] once parsed, repairing it if it fails to parse and
+/// the `checks` have repairs to count the fix in. A repaired line gives each of its records,
+/// the glued records being numbered after the id of their line, as in `3#2`.
+/// Lines that could not be repaired give their original error
+fn parse_candidate(
+    (id, json_candidate, joined): LineCandidate<NDJSONError>,
+    clean_copy: Option<&CleanCopy>,
+    checks: &LineChecks,
+) -> Vec<IdJSONResult> {
+    let Some(repairs) = &checks.repairs else {
+        let json = json_candidate.and_then(|text| match serde_json::from_str(&text) {
+            Ok(json) => checked(checks, clean_copy, &id, &text, json),
+            Err(error) => Err(NDJSONError::invalid_json(&error, text)),
+        });
+        return vec![(id, json)];
+    };
+    let text = match json_candidate {
+        Ok(text) => text,
+        Err(error) => return vec![(id, Err(error))],
+    };
+    let already = if joined {
+        vec![Repair::SplitRecord]
+    } else {
+        vec![]
+    };
+    let repaired = match serde_json::from_str(&text) {
+        Ok(json) if joined => RepairedLine {
+            repairs: already,
+            records: vec![(text, json)],
+        },
+        Ok(json) => {
+            let json = checked(checks, clean_copy, &id, &text, json);
+            return vec![(id, json)];
+        }
+        Err(error) => match repair(&text, already) {
+            Some(repaired) => repaired,
+            None => return vec![(id, Err(NDJSONError::invalid_json(&error, text)))],
+        },
+    };
+    repairs.add(&repaired.repairs);
+    let glued = repaired.records.len() > 1;
+    repaired
+        .records
+        .into_iter()
+        .enumerate()
+        .map(|(i, (text, json))| {
+            let id = if glued {
+                id.clone().with_record(i + 1)
+            } else {
+                id.clone()
+            };
+            let json = checked(checks, clean_copy, &id, &text, json);
+            (id, json)
         })
+        .collect()
+}
+
+/// The `json` parsed from the `text` of the record `id`, once through the `checks` and
+/// copied to the `clean_copy` if still valid
+fn checked(
+    checks: &LineChecks,
+    clean_copy: Option<&CleanCopy>,
+    id: &RecordLocation,
+    text: &str,
+    json: Value,
+) -> Result<Value, NDJSONError> {
+    checks.check(id, text)?;
+    Ok(copied(clean_copy, text, json))
+}
+
+/// Parses a line as JSON, diagnosing why it is not valid JSON if so
+fn parse_json(json_candidate: String) -> Result<Value, NDJSONError> {
+    serde_json::from_str(&json_candidate)
+        .map_err(|error| NDJSONError::invalid_json(&error, json_candidate))
+}
+
+/// Copies the `line` that parsed as the `json` to the `clean_copy`, if any
+fn copied(clean_copy: Option<&CleanCopy>, line: &str, json: Value) -> Value {
+    if let Some(clean_copy) = clean_copy {
+        clean_copy.write(line, &json);
     }
+    json
 }
 
 /// Progress spinner for the processing loops, hidden when `--quiet` is set
@@ -113,67 +343,63 @@ fn progress_spinner(args: &Cli) -> ProgressBar {
     )
 }
 
+/// Fans a JSON out to each of the `--jsonpath` queries, the JSON itself when there are none,
+/// tagged with its `--group-by` group.
+/// Results that are not JSONs, or JSONs filtered out by the `--where` predicates, are tagged
+/// `None` as they apply to every query, and are only counted towards the overall stats
+fn select_queries(settings: &Settings, (id, json_result): IdJSONResult) -> Vec<QueryIdJSONResult> {
+    let Ok(json) = json_result else {
+        return vec![(None, None, (id, json_result))];
+    };
+    if !settings.matches_predicates(&json) {
+        return vec![(None, None, (id, Err(NDJSONError::FilteredOut)))];
+    }
+    let group = settings.group_of(&json);
+    if settings.queries.is_empty() {
+        return vec![(Some(0), group, (id, Ok(json)))];
+    }
+    let mut selections = Vec::new();
+    for (query_index, query) in settings.queries.iter().enumerate() {
+        let selected = query.selector.query(&json);
+        if selected.is_empty() {
+            selections.push((
+                Some(query_index),
+                group.to_owned(),
+                (id.to_owned(), Err(settings.empty_query(&json))),
+            ));
+        }
+        selections.extend(selected.into_iter().enumerate().map(|(i, json)| {
+            (
+                Some(query_index),
+                group.to_owned(),
+                (id.clone().with_match(i), Ok(json.to_owned())),
+            )
+        }));
+    }
+    selections
+}
+
 // TODO: Consider switching to match _par implementation without Box<_> (needs benchmarking)
-/// Handles the jsonpath query expansion of the Iterators values. Single threaded
+/// Handles the jsonpath query expansion of the Iterators values, fanning each value out to
+/// every `--jsonpath` query so they can all be analysed from a single read. Single threaded
 ///
 /// See also [`expand_jsonpath_query_result_par`]
 pub fn expand_jsonpath_query_result<'a>(
     settings: &'a Settings,
     json_iter: impl Iterator<Item = IdJSONResult> + 'a,
-) -> IdJSONResultIter<'a> {
-    let json_iter_out: IdJSONResultIter<'a>;
-    if let Some(ref selector) = settings.jsonpath_selector {
-        let path = settings.args.jsonpath.to_owned();
-        let path = path.expect("must exist for jsonpath_selector to exist");
-        let expanded = json_iter.flat_map(move |(id, json_result)| {
-            let Ok(json) = json_result else {
-                return vec![(id.to_owned(), json_result)];
-            };
-            let selected = selector.query(&json);
-            if selected.is_empty() {
-                return vec![(id, Err(NDJSONError::EmptyQuery))];
-            }
-            selected
-                .into_iter()
-                .enumerate()
-                .map(|(i, json)| (format!("{id}:{path}[{i}]"), Ok(json.to_owned())))
-                .collect::<Vec<_>>()
-        });
-        json_iter_out = Box::new(expanded);
-    } else {
-        json_iter_out = Box::new(json_iter);
-    }
-    json_iter_out
+) -> QueryIdJSONResultIter<'a> {
+    Box::new(json_iter.flat_map(move |id_json_result| select_queries(settings, id_json_result)))
 }
 
-/// Handles the jsonpath query expansion of the Iterators values. Multi-threaded.
+/// Handles the jsonpath query expansion of the Iterators values, fanning each value out to
+/// every `--jsonpath` query. Multi-threaded.
 ///
 /// See also [`expand_jsonpath_query_result`]
 pub fn expand_jsonpath_query_result_par<'a>(
     settings: &'a Settings,
     json_iter: impl ParallelIterator<Item = IdJSONResult> + 'a,
-) -> impl ParallelIterator<Item = IdJSONResult> + 'a {
-    json_iter.flat_map(move |(id, json_result)| {
-        let Some(ref selector) = settings.jsonpath_selector else {
-            return vec![(id, json_result)];
-        };
-        let Ok(json_input) = json_result else {
-            return vec![(id, json_result)];
-        };
-
-        let path = settings.args.jsonpath.to_owned();
-        let path = path.expect("must exist for jsonpath_selector to exist");
-
-        let selected = selector.query(&json_input);
-        if selected.is_empty() {
-            return vec![(id, Err(NDJSONError::EmptyQuery))];
-        }
-        selected
-            .into_iter()
-            .enumerate()
-            .map(|(i, json)| (format!("{id}:{path}[{i}]"), Ok(json.to_owned())))
-            .collect::<Vec<_>>()
-    })
+) -> impl ParallelIterator<Item = QueryIdJSONResult> + 'a {
+    json_iter.flat_map(move |id_json_result| select_queries(settings, id_json_result))
 }
 
 // impl<'a, T: Iterator<Item=String> + 'a> ToNDJSON<'a> for T  {
@@ -240,18 +466,13 @@ pub fn expand_jsonpath_query_result_par<'a>(
 pub fn parse_ndjson_receiver<'a>(
     _args: &Cli,
     receiver: Receiver<String>,
-    errors: &NDJSONErrors,
+    errors: &'a dyn ErrorSink,
 ) -> Result<IdJSONIter<'a>, Box<dyn Error>> {
     let json_iter = receiver
         .into_iter()
         .indexed()
-        .map(|(i, json_candidate)| {
-            (
-                i.to_string(),
-                serde_json::from_str::<Value>(&json_candidate),
-            )
-        })
-        .to_err_filtered(errors.new_ref());
+        .map(|(i, json_candidate)| (RecordLocation::from(i), parse_json(json_candidate)))
+        .to_err_filtered(errors);
 
     Ok(Box::new(json_iter))
 }
@@ -264,7 +485,7 @@ pub fn parse_ndjson_receiver<'a>(
 pub fn parse_ndjson_receiver_par<'a>(
     args: &Cli,
     receiver: Receiver<String>,
-    errors: &'a NDJSONErrorsPar,
+    errors: &'a dyn ErrorSink,
 ) -> impl ParallelIterator<Item = IdJSON> + use<'a> {
     let receiver = receiver.into_iter().indexed();
     parse_ndjson_iter_par(args, receiver, errors)
@@ -280,20 +501,19 @@ pub fn parse_ndjson_receiver_par<'a>(
 pub fn parse_ndjson_bufreader_par<'a>(
     args: &Cli,
     file_path: &PathBuf,
-    errors: &'a NDJSONErrorsPar,
+    errors: &'a dyn ErrorSink,
 ) -> Result<impl ParallelIterator<Item = IdJSON> + use<'a>, NDJSONError> {
     let reader = get_bufreader(args, file_path)?;
 
     let iter = reader.lines().enumerate();
     let iter = iter.filter_map(|(i, line)| {
         let i = i + 1; // count lines from 1
-        let io_errors = errors.new_ref();
         match line {
             Err(e) => {
-                io_errors.push(IndexedNDJSONError {
-                    location: i.to_string(),
-                    error: NDJSONError::IOError(e),
-                });
+                errors.record(IndexedNDJSONError::new(
+                    RecordLocation::from(i),
+                    NDJSONError::IOError(e),
+                ));
                 None
             }
             Ok(json) => Some((i, json)),
@@ -312,32 +532,23 @@ pub fn parse_ndjson_bufreader_par<'a>(
 pub fn parse_ndjson_iter_par<'a, I>(
     args: &Cli,
     iter: I,
-    errors: &'a NDJSONErrorsPar,
+    errors: &'a dyn ErrorSink,
 ) -> impl ParallelIterator<Item = IdJSON> + use<'a, I>
 where
     I: Iterator<Item = IJSONCandidate> + Send,
 {
-    let iter = iter.take(args.lines.unwrap_or(usize::MAX));
+    let iter = sample(args, limit(args, iter));
 
-    let json_iter = iter.par_bridge().map(|(i, json_candidate)| {
-        (
-            i.to_string(),
-            serde_json::from_str::<Value>(&json_candidate),
-        )
-    });
+    let json_iter = iter
+        .par_bridge()
+        .map(|(i, json_candidate)| (RecordLocation::from(i), parse_json(json_candidate)));
 
-    json_iter.filter_map(|(id, json)| {
-        let json_parse_errors = errors.new_ref();
-        match json {
-            Err(e) => {
-                json_parse_errors.push(IndexedNDJSONError {
-                    location: id,
-                    error: NDJSONError::JSONParsingError(e),
-                });
-                None
-            }
-            Ok(json) => Some((id, json)),
+    json_iter.filter_map(|(id, json)| match json {
+        Err(e) => {
+            errors.record(IndexedNDJSONError::new(id, e));
+            None
         }
+        Ok(json) => Some((id, json)),
     })
 }
 
@@ -349,19 +560,15 @@ where
 pub fn parse_ndjson_bufreader<'a>(
     _args: &Cli,
     reader: impl BufRead + 'a,
-    errors: &NDJSONErrors,
+    errors: &'a dyn ErrorSink,
 ) -> IdJSONIter<'a> {
     let json_iter = reader.lines();
 
-    let json_iter = json_iter.to_enumerated_err_filtered(errors.new_ref());
+    let json_iter = json_iter.to_enumerated_err_filtered(errors);
 
-    let json_iter = json_iter.map(|(i, json_candidate)| {
-        (
-            i.to_string(),
-            serde_json::from_str::<Value>(&json_candidate),
-        )
-    });
-    let json_iter = json_iter.to_err_filtered(errors.new_ref());
+    let json_iter =
+        json_iter.map(|(i, json_candidate)| (RecordLocation::from(i), parse_json(json_candidate)));
+    let json_iter = json_iter.to_err_filtered(errors);
 
     Box::new(json_iter)
 }
@@ -371,7 +578,7 @@ pub fn parse_ndjson_bufreader<'a>(
 /// parse as JSON to the `errors` container. Single threaded.
 ///
 /// See also: [`parse_ndjson_bufreader`], [`parse_ndjson_file_path`] & [`parse_ndjson_receiver`]
-pub fn parse_ndjson_file<'a>(args: &Cli, file: File, errors: &NDJSONErrors) -> IdJSONIter<'a> {
+pub fn parse_ndjson_file<'a>(args: &Cli, file: File, errors: &'a dyn ErrorSink) -> IdJSONIter<'a> {
     let reader = io::BufReader::new(file);
     parse_ndjson_bufreader(args, reader, errors)
 }
@@ -384,37 +591,36 @@ pub fn parse_ndjson_file<'a>(args: &Cli, file: File, errors: &NDJSONErrors) -> I
 pub fn parse_ndjson_file_path<'a>(
     args: &Cli,
     file_path: &PathBuf,
-    errors: &NDJSONErrors,
+    errors: &'a dyn ErrorSink,
 ) -> Result<IdJSONIter<'a>, NDJSONError> {
     let reader = get_bufreader(args, file_path)?;
     Ok(parse_ndjson_bufreader(args, reader, errors))
 }
 
-/// Handles the jsonpath query expansion of the Iterators values. Single threaded
+/// Handles the jsonpath query expansion of the Iterators values. Single threaded.
+/// Only the first `--jsonpath` query is applied, see [`expand_jsonpath_query_result`] for all
 ///
 /// See also [`expand_jsonpath_query_par`]
 pub fn expand_jsonpath_query<'a>(
     settings: &'a Settings,
     json_iter: impl Iterator<Item = IdJSON> + 'a,
-    errors: &NDJSONErrors,
+    errors: &'a dyn ErrorSink,
 ) -> IdJSONIter<'a> {
-    let missing = errors.new_ref();
+    let missing = errors;
     let json_iter_out: IdJSONIter<'a>;
-    if let Some(ref selector) = settings.jsonpath_selector {
-        let path = settings.args.jsonpath.to_owned();
-        let path = path.expect("must exist for jsonpath_selector to exist");
+    if let Some(query) = settings.queries.first() {
         let expanded = json_iter.flat_map(move |(ref id, ref json)| {
-            let selected = selector.query(json);
+            let selected = query.selector.query(json);
             if selected.is_empty() {
-                missing.push(IndexedNDJSONError::new(
+                missing.record(IndexedNDJSONError::new(
                     id.to_owned(),
-                    NDJSONError::EmptyQuery,
+                    settings.empty_query(json),
                 ))
             }
             selected
                 .into_iter()
                 .enumerate()
-                .map(|(i, json)| (format!("{id}:{path}[{i}]"), json.to_owned()))
+                .map(|(i, json)| (id.clone().with_match(i), json.to_owned()))
                 .collect::<Vec<_>>()
         });
         json_iter_out = Box::new(expanded);
@@ -425,31 +631,29 @@ pub fn expand_jsonpath_query<'a>(
 }
 
 /// Handles the jsonpath query expansion of the Iterators values. Multi-threaded.
+/// Only the first `--jsonpath` query is applied, see [`expand_jsonpath_query_result_par`] for all
 ///
 /// See also [`expand_jsonpath_query`]
 pub fn expand_jsonpath_query_par<'a>(
     settings: &'a Settings,
     json_iter: impl ParallelIterator<Item = IdJSON> + 'a,
-    errors: &NDJSONErrorsPar,
+    errors: &'a dyn ErrorSink,
 ) -> impl ParallelIterator<Item = IdJSON> + 'a {
-    let missing = errors.new_ref();
+    let missing = errors;
 
     json_iter.flat_map(move |(id, json)| {
-        if let Some(ref selector) = settings.jsonpath_selector {
-            let path = settings.args.jsonpath.to_owned();
-            let path = path.expect("must exist for jsonpath_selector to exist");
-
-            let selected = selector.query(&json);
+        if let Some(query) = settings.queries.first() {
+            let selected = query.selector.query(&json);
             if selected.is_empty() {
-                missing.push(IndexedNDJSONError::new(
+                missing.record(IndexedNDJSONError::new(
                     id.to_owned(),
-                    NDJSONError::EmptyQuery,
+                    settings.empty_query(&json),
                 ))
             }
             selected
                 .into_iter()
                 .enumerate()
-                .map(|(i, json)| (format!("{id}:{path}[{i}]"), json.to_owned()))
+                .map(|(i, json)| (id.clone().with_match(i), json.to_owned()))
                 .collect::<Vec<_>>()
         } else {
             vec![(id, json)]
@@ -457,17 +661,62 @@ pub fn expand_jsonpath_query_par<'a>(
     })
 }
 
+/// Drops the JSONs not matching the `--where` predicates, recording them to the `errors`
+/// container. Single threaded
+///
+/// See also [`filter_predicates_par`]
+pub fn filter_predicates<'a>(
+    settings: &'a Settings,
+    json_iter: impl Iterator<Item = IdJSON> + 'a,
+    errors: &'a dyn ErrorSink,
+) -> impl Iterator<Item = IdJSON> + 'a {
+    let filtered = errors;
+    json_iter.filter(move |(id, json)| {
+        let matches = settings.matches_predicates(json);
+        if !matches {
+            filtered.record(IndexedNDJSONError::new(
+                id.to_owned(),
+                NDJSONError::FilteredOut,
+            ))
+        }
+        matches
+    })
+}
+
+/// Drops the JSONs not matching the `--where` predicates, recording them to the `errors`
+/// container. Multi-threaded
+///
+/// See also [`filter_predicates`]
+pub fn filter_predicates_par<'a>(
+    settings: &'a Settings,
+    json_iter: impl ParallelIterator<Item = IdJSON> + 'a,
+    errors: &'a dyn ErrorSink,
+) -> impl ParallelIterator<Item = IdJSON> + 'a {
+    let filtered = errors;
+    json_iter.filter(move |(id, json)| {
+        let matches = settings.matches_predicates(json);
+        if !matches {
+            filtered.record(IndexedNDJSONError::new(
+                id.to_owned(),
+                NDJSONError::FilteredOut,
+            ))
+        }
+        matches
+    })
+}
+
 /// Apply pre-processing based on settings from CLI args. Single threaded.
 ///
 /// See also [`apply_settings_par`]
 pub fn apply_settings<'a>(
     settings: &'a Settings,
     json_iter: impl Iterator<Item = IdJSON> + 'a,
-    errors: &NDJSONErrors,
+    errors: &'a dyn ErrorSink,
 ) -> IdJSONIter<'a> {
     let args = &settings.args;
 
-    let json_iter = limit(args, json_iter);
+    let json_iter = sample(args, limit(args, json_iter));
+    let json_iter = filter_predicates(settings, json_iter, errors);
     expand_jsonpath_query(settings, json_iter, errors)
 }
 
@@ -477,64 +726,200 @@ pub fn apply_settings<'a>(
 pub fn apply_settings_par<'a>(
     settings: &'a Settings,
     json_iter: impl ParallelIterator<Item = IdJSON> + 'a,
-    errors: &NDJSONErrorsPar,
+    errors: &'a dyn ErrorSink,
 ) -> impl ParallelIterator<Item = IdJSON> + 'a {
+    let json_iter = filter_predicates_par(settings, json_iter, errors);
     expand_jsonpath_query_par(settings, json_iter, errors)
 }
 
 /// Main function processing the JSON data, collecting key information about the content.
-/// Single threaded.
+/// Single threaded. Only the overall stats of the first `--jsonpath` query are returned.
 ///
-/// See also [`process_json_result_iterable_par`]
+/// See also [`process_json_result_iterable_par`] & [`process_json_result_iterable_by_section`]
 pub fn process_json_result_iterable(
     settings: &Settings,
     json_iter: impl Iterator<Item = IdJSONResult>,
 ) -> Stats {
-    let mut fs = Stats::new();
+    first_query_stats(process_json_result_iterable_by_section(settings, json_iter))
+}
+
+/// Records a JSON towards the stats
+fn add_json(settings: &Settings, fs: &mut Stats, json: &Value, path_type: &mut String) {
+    fs.line_count += 1;
+    *fs.depth_histogram.entry(json.depth()).or_insert(0) += 1;
+
+    for value_path in json.value_paths_with(&settings.walk_options) {
+        let path = value_path.jsonpath();
+        let counter = fs.keys_count.entry(path.to_owned()).or_insert(0);
+        *counter += 1;
+
+        let type_ = value_path.value.value_type();
+        // TODO: consider doing more reduction of allocations like this:
+        path_type.clear();
+        write!(path_type, "{}::{}", path, type_).unwrap();
+        let (_, counter) = fs
+            .keys_types_count
+            .raw_entry_mut_v1()
+            .from_key(path_type.as_str())
+            .or_insert_with(|| (path_type.to_owned(), 0));
+        *counter += 1;
+    }
+}
+
+/// Records a line that could not be analysed towards the stats
+fn add_error(fs: &mut Stats, id: &RecordLocation, error: &NDJSONError) {
+    match error {
+        NDJSONError::JSONParsingError { .. } | NDJSONError::IOError(_) => {
+            fs.bad_lines.push(id);
+            if let Some(diagnostic) = error.parse_diagnostic() {
+                fs.add_parse_error(id, diagnostic);
+            }
+        }
+        NDJSONError::EmptyQuery { .. } => fs.empty_lines.push(id),
+        NDJSONError::FilteredOut => fs.filtered_out += 1,
+    }
+}
+
+/// Stats of a `--jsonpath` query, overall and for each of the `--group-by` groups
+struct QuerySections {
+    overall: Stats,
+    groups: IndexMap<Group, Stats>,
+    checks: RecordChecks,
+    limits: LineIdLimits,
+}
+
+impl QuerySections {
+    fn new(settings: &Settings) -> Self {
+        let limits = settings.line_id_limits;
+        Self {
+            overall: Stats::with_line_id_limits(limits),
+            groups: IndexMap::new(),
+            checks: RecordChecks::new(settings),
+            limits,
+        }
+    }
+
+    fn into_stats(mut self, settings: &Settings) -> (Stats, IndexMap<Group, Stats>) {
+        self.checks.add_to(settings, &mut self.overall);
+        (self.overall, self.groups)
+    }
+
+    /// The stats affected by a result of the `group`: the overall stats and those of the group
+    fn affected(&mut self, group: Option<&Group>) -> impl Iterator<Item = &mut Stats> {
+        let group = group.map(|group| match self.groups.get_index_of(group) {
+            Some(i) => &mut self.groups[i],
+            None => self
+                .groups
+                .entry(group.to_owned())
+                .or_insert_with(|| Stats::with_line_id_limits(self.limits)),
+        });
+        std::iter::once(&mut self.overall).chain(group)
+    }
+}
+
+/// Main function processing the JSON data, collecting key information about the content
+/// separately for each of the `--jsonpath` queries, and within them for each of the
+/// `--group-by` groups. Single threaded.
+///
+/// See also [`process_json_result_iterable_by_section_par`]
+pub fn process_json_result_iterable_by_section(
+    settings: &Settings,
+    json_iter: impl Iterator<Item = IdJSONResult>,
+) -> IndexMap<Section, Stats> {
+    let args = &settings.args;
+    let json_iter = sample(args, limit(args, json_iter));
+    collect_sections(settings, &*settings.quarantine(), json_iter)
+}
+
+/// Body of [`process_json_result_iterable_by_section`], passing the lines that could not be
+/// analysed on to the `errors` sink, such as the quarantine. The lines are expected to be
+/// limited already, as by [`ToNDJSON::parse_ndjson`]
+fn collect_sections(
+    settings: &Settings,
+    errors: &dyn ErrorSink,
+    json_iter: impl Iterator<Item = IdJSONResult>,
+) -> IndexMap<Section, Stats> {
+    let mut sections: Vec<QuerySections> = settings
+        .query_names()
+        .map(|_| QuerySections::new(settings))
+        .collect();
     let args = &settings.args;
 
-    let json_iter = limit(args, json_iter);
     let json_iter = expand_jsonpath_query_result(settings, json_iter);
 
     let spinner = progress_spinner(args);
+    let tracker = settings.budget.tracker(sections.len());
 
     let mut path_type = String::with_capacity(100);
-    for (id, json_result) in json_iter {
+    for (query_index, group, (id, json_result)) in json_iter {
         match json_result {
             Ok(json) => {
                 spinner.inc(1);
-                fs.line_count += 1;
-
-                for value_path in json.value_paths(args.explode_arrays, args.inspect_arrays) {
-                    let path = value_path.jsonpath();
-                    let counter = fs.keys_count.entry(path.to_owned()).or_insert(0);
-                    *counter += 1;
-
-                    let type_ = value_path.value.value_type();
-                    // TODO: consider doing more reduction of allocations like this:
-                    path_type.clear();
-                    write!(path_type, "{}::{}", path, type_).unwrap();
-                    let (_, counter) = fs
-                        .keys_types_count
-                        .raw_entry_mut_v1()
-                        .from_key(&path_type)
-                        .or_insert_with(|| (path_type.to_owned(), 0));
-                    *counter += 1;
+                let query_sections =
+                    &mut sections[query_index.expect("JSONs are selected by a query")];
+                query_sections.checks.check(settings, &id, &json);
+                for fs in query_sections.affected(group.as_ref()) {
+                    add_json(settings, fs, &json, &mut path_type);
                 }
             }
             Err(error) => {
-                match error {
-                    // TODO: use or syntax here?
-                    NDJSONError::JSONParsingError(_) => fs.bad_lines.push(id),
-                    NDJSONError::EmptyQuery => fs.empty_lines.push(id),
-                    NDJSONError::IOError(_) => fs.bad_lines.push(id),
+                let affected = match query_index {
+                    Some(i) => &mut sections[i..=i],
+                    None => &mut sections[..],
+                };
+                for query_sections in affected {
+                    for fs in query_sections.affected(group.as_ref()) {
+                        add_error(fs, &id, &error);
+                    }
+                }
+                let exceeded = tracker
+                    .as_ref()
+                    .is_some_and(|tracker| tracker.exceeded_by(query_index, &error));
+                let query = settings.query_name(query_index);
+                errors.record_query(query, IndexedNDJSONError::new(id, error));
+                if exceeded {
+                    break;
                 }
             }
         };
     }
     spinner.finish();
 
-    fs
+    let sections = sections
+        .into_iter()
+        .map(|query_sections| query_sections.into_stats(settings))
+        .collect();
+    named_sections(settings, sections)
+}
+
+/// Names the per query overall stats and `groups` of `sections` and finishes them off.
+/// Each query's groups are ordered by name, followed by its overall stats
+fn named_sections(
+    settings: &Settings,
+    sections: Vec<(Stats, IndexMap<Group, Stats>)>,
+) -> IndexMap<Section, Stats> {
+    let mut named = IndexMap::new();
+    for (name, (overall, mut groups)) in settings.query_names().zip(sections) {
+        groups.sort_unstable_keys();
+        let groups = groups
+            .into_iter()
+            .map(|(group, fs)| (Section::new(name, Some(group)), fs));
+        for (section, mut fs) in groups.chain([(Section::from(name), overall)]) {
+            fs.collapse_map_paths(&settings.walk_options.maps);
+            fs.sampling = settings.args.sampling();
+            named.insert(section, fs);
+        }
+    }
+    named
+}
+
+/// Overall stats of the first query, for callers expecting a single set of stats
+fn first_query_stats(sections: IndexMap<Section, Stats>) -> Stats {
+    sections
+        .into_iter()
+        .find(|(section, _)| section.group.is_none())
+        .map(|(_, fs)| fs)
+        .unwrap_or_default()
 }
 
 /// Main function processing the JSON data, collecting key information about the content.
@@ -544,7 +929,7 @@ pub fn process_json_result_iterable(
 pub fn process_json_iterable(
     settings: &Settings,
     json_iter: impl Iterator<Item = IdJSON>,
-    errors: &NDJSONErrors,
+    errors: &dyn ErrorSink,
 ) -> Stats {
     let mut fs = Stats::new();
     let args = &settings.args;
@@ -556,8 +941,9 @@ pub fn process_json_iterable(
     for (_id, json) in json_iter {
         spinner.inc(1);
         fs.line_count += 1;
+        *fs.depth_histogram.entry(json.depth()).or_insert(0) += 1;
 
-        for value_path in json.value_paths(args.explode_arrays, args.inspect_arrays) {
+        for value_path in json.value_paths_with(&settings.walk_options) {
             let path = value_path.jsonpath();
             let counter = fs.keys_count.entry(path.to_owned()).or_insert(0);
             *counter += 1;
@@ -570,85 +956,227 @@ pub fn process_json_iterable(
     }
     spinner.finish();
 
-    for indexed_error in errors.container.borrow().as_slice() {
-        let IndexedNDJSONError { location, error } = indexed_error;
-        let location = location.to_owned();
-        match error {
-            // TODO: use or syntax here?
-            NDJSONError::JSONParsingError(_) => fs.bad_lines.push(location),
-            NDJSONError::EmptyQuery => fs.empty_lines.push(location),
-            NDJSONError::IOError(_) => fs.bad_lines.push(location),
-        }
-    }
+    errors
+        .replay(&mut |IndexedNDJSONError { location, error }| add_error(&mut fs, location, error));
+    fs.collapse_map_paths(&settings.walk_options.maps);
+    fs.sampling = settings.args.sampling();
     fs
 }
 
 /// Main function processing the JSON data, collecting key information about the content.
 /// Multi-threaded version of [`process_json_result_iterable`].
+/// Only the overall stats of the first `--jsonpath` query are returned.
 ///
-/// See also [`process_json_result_iterable`]
+/// See also [`process_json_result_iterable`] & [`process_json_result_iterable_by_section_par`]
 pub fn process_json_result_iterable_par<'a>(
     settings: &Settings,
     json_iter: impl ParallelIterator<Item = IdJSONResult> + 'a,
 ) -> Stats {
-    let args = &settings.args;
+    first_query_stats(process_json_result_iterable_by_section_par(
+        settings, json_iter,
+    ))
+}
 
-    let keys_count: DashMap<String, usize> = DashMap::new();
-    let keys_types_count: DashMap<String, usize> = DashMap::new();
-    let line_count = AtomicUsize::new(0);
+/// Thread safe counterpart of [`Stats`] for collecting stats in parallel
+#[derive(Default)]
+struct StatsPar {
+    keys_count: DashMap<String, usize>,
+    keys_types_count: DashMap<String, usize>,
+    line_count: AtomicUsize,
+    depth_histogram: DashMap<usize, usize>,
+    bad_lines: Mutex<LineIds>,
+    parse_errors: Mutex<IndexMap<ParseErrorKind, ParseErrorSummary>>,
+    empty_lines: Mutex<LineIds>,
+    filtered_out: AtomicUsize,
+}
 
-    let json_iter = expand_jsonpath_query_result_par(settings, json_iter);
+impl StatsPar {
+    fn new(limits: LineIdLimits) -> Self {
+        Self {
+            bad_lines: Mutex::new(LineIds::new(limits)),
+            empty_lines: Mutex::new(LineIds::new(limits)),
+            ..Default::default()
+        }
+    }
 
-    let spinner = progress_spinner(args);
+    /// Records a JSON towards the stats
+    fn add_json(&self, settings: &Settings, json: &Value) {
+        self.line_count.fetch_add(1, Ordering::Release);
+        *self.depth_histogram.entry(json.depth()).or_insert(0) += 1;
 
-    let bad_lines = Mutex::new(Vec::default());
-    let empty_lines = Mutex::new(Vec::default());
+        for value_path in json.value_paths_with(&settings.walk_options) {
+            let path = value_path.jsonpath();
+            let mut counter = self.keys_count.entry(path.to_owned()).or_insert(0);
+            *counter.value_mut() += 1;
 
-    json_iter.for_each(|(id, json_result)| match json_result {
-        Ok(json) => {
-            line_count.fetch_add(1, Ordering::Release);
+            let type_ = value_path.value.value_type();
+            let path_type = format!("{}::{}", path, type_);
+            let mut counter = self.keys_types_count.entry(path_type).or_insert(0);
+            *counter.value_mut() += 1;
+        }
+    }
+
+    /// Records a line that could not be analysed towards the stats
+    fn add_error(&self, id: &RecordLocation, error: &NDJSONError) {
+        match error {
+            NDJSONError::JSONParsingError { .. } | NDJSONError::IOError(_) => {
+                let mut bad_lines = self.bad_lines.lock().unwrap();
+                bad_lines.push_unordered(id);
+                if let Some(diagnostic) = error.parse_diagnostic() {
+                    let mut parse_errors = self.parse_errors.lock().unwrap();
+                    let summary = parse_errors.entry(diagnostic.kind).or_default();
+                    summary.add(id, diagnostic);
+                }
+            }
+            NDJSONError::EmptyQuery { .. } => {
+                let mut empty_lines = self.empty_lines.lock().unwrap();
+                empty_lines.push_unordered(id);
+            }
+            NDJSONError::FilteredOut => {
+                self.filtered_out.fetch_add(1, Ordering::Release);
+            }
+        }
+    }
+
+    fn into_stats(self) -> Stats {
+        let mut fs = Stats::new();
+        fs.keys_count = self
+            .keys_count
+            .into_read_only()
+            .iter()
+            .map(|(k, v)| (k.to_owned(), v.to_owned()))
+            .collect();
+        fs.line_count = self.line_count.load(Ordering::Acquire);
+        fs.depth_histogram = self.depth_histogram.into_iter().collect();
+        fs.keys_types_count = self
+            .keys_types_count
+            .into_read_only()
+            .iter()
+            .map(|(k, v)| (k.to_owned(), v.to_owned()))
+            .collect();
+        fs.bad_lines = self.bad_lines.into_inner().unwrap();
+        fs.bad_lines.sort();
+        fs.parse_errors = self.parse_errors.into_inner().unwrap();
+        fs.empty_lines = self.empty_lines.into_inner().unwrap();
+        fs.empty_lines.sort();
+        fs.filtered_out = self.filtered_out.load(Ordering::Acquire);
+        fs
+    }
+}
 
-            for value_path in json.value_paths(args.explode_arrays, args.inspect_arrays) {
-                let path = value_path.jsonpath();
-                let mut counter = keys_count.entry(path.to_owned()).or_insert(0);
-                *counter.value_mut() += 1;
+/// Thread safe counterpart of [`QuerySections`] for collecting stats in parallel
+struct QuerySectionsPar {
+    overall: StatsPar,
+    groups: DashMap<Group, StatsPar>,
+    checks: RecordChecks,
+    limits: LineIdLimits,
+}
 
-                let type_ = value_path.value.value_type();
-                let path_type = format!("{}::{}", path, type_);
-                let mut counter = keys_types_count.entry(path_type).or_insert(0);
-                *counter.value_mut() += 1;
+impl QuerySectionsPar {
+    fn new(settings: &Settings) -> Self {
+        let limits = settings.line_id_limits;
+        Self {
+            overall: StatsPar::new(limits),
+            groups: DashMap::new(),
+            checks: RecordChecks::new(settings),
+            limits,
+        }
+    }
+
+    /// Applies `add` to the stats affected by a result of the `group`: the overall stats and
+    /// those of the group
+    fn for_affected(&self, group: Option<&Group>, add: impl Fn(&StatsPar)) {
+        add(&self.overall);
+        if let Some(group) = group {
+            match self.groups.get(group) {
+                Some(fs) => add(&fs),
+                None => add(&self
+                    .groups
+                    .entry(group.to_owned())
+                    .or_insert_with(|| StatsPar::new(self.limits))),
             }
-            spinner.inc(1);
         }
-        Err(error) => match error {
-            NDJSONError::JSONParsingError(_) | NDJSONError::IOError(_) => {
-                let mut bad_lines = bad_lines.lock().unwrap();
-                bad_lines.push(id);
+    }
+
+    fn into_stats(self, settings: &Settings) -> (Stats, IndexMap<Group, Stats>) {
+        let groups = self
+            .groups
+            .into_iter()
+            .map(|(group, fs)| (group, fs.into_stats()))
+            .collect();
+        let mut overall = self.overall.into_stats();
+        self.checks.add_to(settings, &mut overall);
+        (overall, groups)
+    }
+}
+
+/// Main function processing the JSON data, collecting key information about the content
+/// separately for each of the `--jsonpath` queries, and within them for each of the
+/// `--group-by` groups.
+/// Multi-threaded version of [`process_json_result_iterable_by_section`].
+///
+/// See also [`process_json_result_iterable_by_section`]
+pub fn process_json_result_iterable_by_section_par<'a>(
+    settings: &Settings,
+    json_iter: impl ParallelIterator<Item = IdJSONResult> + 'a,
+) -> IndexMap<Section, Stats> {
+    collect_sections_par(settings, &*settings.quarantine(), json_iter)
+}
+
+/// Body of [`process_json_result_iterable_by_section_par`], passing the lines that could not
+/// be analysed on to the `errors` sink, such as the quarantine
+fn collect_sections_par<'a>(
+    settings: &Settings,
+    errors: &dyn ErrorSink,
+    json_iter: impl ParallelIterator<Item = IdJSONResult> + 'a,
+) -> IndexMap<Section, Stats> {
+    let args = &settings.args;
+
+    let sections: Vec<QuerySectionsPar> = settings
+        .query_names()
+        .map(|_| QuerySectionsPar::new(settings))
+        .collect();
+
+    let json_iter = expand_jsonpath_query_result_par(settings, json_iter);
+
+    let spinner = progress_spinner(args);
+    let tracker = settings.budget.tracker(sections.len());
+
+    // `None` stops the processing once the lines are over the `--fail-fast` budget
+    let _ = json_iter.try_for_each(
+        |(query_index, group, (id, json_result))| match json_result {
+            Ok(json) => {
+                let query_sections = &sections[query_index.expect("JSONs are selected by a query")];
+                query_sections.checks.check(settings, &id, &json);
+                query_sections.for_affected(group.as_ref(), |fs| fs.add_json(settings, &json));
+                spinner.inc(1);
+                Some(())
             }
-            NDJSONError::EmptyQuery => {
-                let mut empty_lines = empty_lines.lock().unwrap();
-                empty_lines.push(id);
+            Err(error) => {
+                let affected = match query_index {
+                    Some(i) => &sections[i..=i],
+                    None => &sections[..],
+                };
+                for query_sections in affected {
+                    query_sections.for_affected(group.as_ref(), |fs| fs.add_error(&id, &error));
+                }
+                let exceeded = tracker
+                    .as_ref()
+                    .is_some_and(|tracker| tracker.exceeded_by(query_index, &error));
+                let query = settings.query_name(query_index);
+                errors.record_query(query, IndexedNDJSONError::new(id, error));
+                (!exceeded).then_some(())
             }
         },
-    });
+    );
 
     spinner.finish();
 
-    let mut fs = Stats::new();
-    fs.keys_count = keys_count
-        .into_read_only()
-        .iter()
-        .map(|(k, v)| (k.to_owned(), v.to_owned()))
-        .collect();
-    fs.line_count = line_count.load(Ordering::Acquire);
-    fs.keys_types_count = keys_types_count
-        .into_read_only()
-        .iter()
-        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+    let sections = sections
+        .into_iter()
+        .map(|query_sections| query_sections.into_stats(settings))
         .collect();
-    fs.bad_lines = bad_lines.into_inner().unwrap();
-    fs.empty_lines = empty_lines.into_inner().unwrap();
-    fs
+    named_sections(settings, sections)
 }
 
 /// Main function processing the JSON data, collecting key information about the content.
@@ -658,7 +1186,7 @@ pub fn process_json_result_iterable_par<'a>(
 pub fn process_json_iterable_par<'a>(
     settings: &Settings,
     json_iter: impl ParallelIterator<Item = IdJSON> + 'a,
-    errors: &'a NDJSONErrorsPar,
+    errors: &'a dyn ErrorSink,
 ) -> Stats {
     let mut fs = Stats::new();
     let args = &settings.args;
@@ -666,6 +1194,7 @@ pub fn process_json_iterable_par<'a>(
     let keys_count: DashMap<String, usize> = DashMap::new();
     let keys_types_count: DashMap<String, usize> = DashMap::new();
     let line_count = AtomicUsize::new(0);
+    let depth_histogram: DashMap<usize, usize> = DashMap::new();
 
     let json_iter = apply_settings_par(settings, json_iter, errors);
 
@@ -673,8 +1202,9 @@ pub fn process_json_iterable_par<'a>(
 
     json_iter.for_each(|(_id, json)| {
         line_count.fetch_add(1, Ordering::Release);
+        *depth_histogram.entry(json.depth()).or_insert(0) += 1;
 
-        for value_path in json.value_paths(args.explode_arrays, args.inspect_arrays) {
+        for value_path in json.value_paths_with(&settings.walk_options) {
             let path = value_path.jsonpath();
             let mut counter = keys_count.entry(path.to_owned()).or_insert(0);
             *counter.value_mut() += 1;
@@ -689,15 +1219,11 @@ pub fn process_json_iterable_par<'a>(
 
     spinner.finish();
 
-    for indexed_error in errors.container.lock().unwrap().as_slice() {
-        let IndexedNDJSONError { location, error } = indexed_error;
-        let location = location.to_owned();
-        match error {
-            NDJSONError::JSONParsingError(_) => fs.bad_lines.push(location),
-            NDJSONError::EmptyQuery => fs.empty_lines.push(location),
-            NDJSONError::IOError(_) => fs.bad_lines.push(location),
-        }
-    }
+    errors
+        .replay(&mut |IndexedNDJSONError { location, error }| add_error(&mut fs, location, error));
+    // Recorded in the order the threads got to them
+    fs.bad_lines.sort();
+    fs.empty_lines.sort();
 
     fs.keys_count = keys_count
         .into_read_only()
@@ -705,25 +1231,32 @@ pub fn process_json_iterable_par<'a>(
         .map(|(k, v)| (k.to_owned(), v.to_owned()))
         .collect();
     fs.line_count = line_count.load(Ordering::Acquire);
+    fs.depth_histogram = depth_histogram.into_iter().collect();
     fs.keys_types_count = keys_types_count
         .into_read_only()
         .iter()
         .map(|(k, v)| (k.to_owned(), v.to_owned()))
         .collect();
+    fs.collapse_map_paths(&settings.walk_options.maps);
+    fs.sampling = settings.args.sampling();
     fs
 }
 
-/// Apply line limiting from the arg to the Iterator
+/// Apply line limiting from the args (`--skip`, `--range`, `--lines` and `--tail`) to the
+/// Iterator
 ///
 /// See also [`parse_iter`]
-pub fn limit<'a, I, T>(args: &Cli, iter: I) -> Box<dyn Iterator<Item = T> + 'a>
-where
-    I: Iterator<Item = T> + 'a,
-{
-    if let Some(n) = args.lines {
-        Box::new(iter.take(n))
-    } else {
-        Box::new(iter)
+pub fn limit<I: Iterator>(args: &Cli, iter: I) -> Window<I> {
+    args.line_window().apply(iter)
+}
+
+/// Apply random sampling from the args to the Iterator, after any line limiting
+///
+/// See also [`limit`]
+pub fn sample<I: Iterator>(args: &Cli, iter: I) -> Sample<I> {
+    match args.sampling() {
+        Some(sampling) => sampling.sample(iter),
+        None => Sample::All(iter),
     }
 }
 
@@ -746,48 +1279,73 @@ where
 
 pub struct StatsResult {
     pub stats: Stats,
-    pub errors: Box<dyn NDJSONProcessingErrors>,
+    pub errors: Box<dyn ErrorSink>,
 }
 
-pub trait JSONStats {
-    fn json_stats(self, settings: &Settings) -> Result<Stats, NDJSONError>;
+pub trait JSONStats: Sized {
+    /// Overall stats of the JSON data, for the first `--jsonpath` query when there are several
+    fn json_stats(self, settings: &Settings) -> Result<Stats, NDJSONError> {
+        self.json_stats_by_section(settings).map(first_query_stats)
+    }
+
+    /// Stats of the JSON data for each of the `--jsonpath` queries and `--group-by` groups,
+    /// from a single read
+    fn json_stats_by_section(
+        self,
+        settings: &Settings,
+    ) -> Result<IndexMap<Section, Stats>, NDJSONError>;
 }
 
 // TODO: Add tests
 impl JSONStats for io::Stdin {
-    fn json_stats(self, settings: &Settings) -> Result<Stats, NDJSONError> {
+    fn json_stats_by_section(
+        self,
+        settings: &Settings,
+    ) -> Result<IndexMap<Section, Stats>, NDJSONError> {
+        let checks = LineChecks::new(settings);
+        let quarantine = settings.quarantine();
+        let quarantine = quarantine.of_source("<stdin>");
         let stats = if settings.args.parallel {
             let stdin = self.background_read_lines(1_000_000);
-            let json_iter = stdin.into_iter().parse_ndjson_par(&settings.args);
-            process_json_result_iterable_par(settings, json_iter)
+            let json_iter = stdin.into_iter().parse_ndjson_par(settings, &checks);
+            collect_sections_par(settings, &quarantine, json_iter)
         } else {
             let stdin = self.lock();
-            let json_iter = stdin.lines().parse_ndjson(&settings.args);
-            process_json_result_iterable(settings, json_iter)
+            let json_iter = stdin.offset_lines().parse_ndjson(settings, &checks);
+            collect_sections(settings, &quarantine, json_iter)
         };
-        Ok(stats)
+        Ok(checks.add_to(stats))
     }
 }
 
 impl JSONStats for &PathBuf {
-    fn json_stats(self, settings: &Settings) -> Result<Stats, NDJSONError> {
+    fn json_stats_by_section(
+        self,
+        settings: &Settings,
+    ) -> Result<IndexMap<Section, Stats>, NDJSONError> {
         let stats;
         let reader = get_bufreader(&settings.args, self)?;
+        let source = self.to_string_lossy();
+        let quarantine = settings.quarantine();
+        let quarantine = quarantine.of_source(&source);
+        let checks = LineChecks::new(settings);
         if settings.args.parallel {
-            let json_iter = reader.lines().parse_ndjson_par(&settings.args);
-            stats = process_json_result_iterable_par(settings, json_iter);
+            let json_iter = reader.offset_lines().parse_ndjson_par(settings, &checks);
+            stats = collect_sections_par(settings, &quarantine, json_iter);
         } else {
-            let json_iter = reader.lines().parse_ndjson(&settings.args);
-            stats = process_json_result_iterable(settings, json_iter);
+            let json_iter = reader.offset_lines().parse_ndjson(settings, &checks);
+            stats = collect_sections(settings, &quarantine, json_iter);
         }
-        Ok(stats)
+        Ok(checks.add_to(stats))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::json::IndexMap;
+    use crate::json::ndjson::errors::collection::{BoundedErrors, Errors, IgnoreErrors};
     use serde_json::json;
+    use std::collections::BTreeMap;
 
     use super::*;
     use std::fs::File;
@@ -829,12 +1387,20 @@ mod tests {
         let expected = Stats {
             keys_count: IndexMap::from([("$.key1".to_string(), 2), ("$.key2".to_string(), 1)]),
             line_count: 3,
-            bad_lines: vec![],
+            bad_lines: Default::default(),
             keys_types_count: IndexMap::from([
                 ("$.key1::Number".to_string(), 2),
                 ("$.key2::Number".to_string(), 1),
             ]),
-            empty_lines: vec![],
+            empty_lines: Default::default(),
+            parse_errors: IndexMap::new(),
+            repairs: IndexMap::new(),
+            duplicate_keys: IndexMap::new(),
+            duplicate_records: None,
+            candidate_keys: None,
+            filtered_out: 0,
+            depth_histogram: BTreeMap::from([(1, 3)]),
+            sampling: None,
         };
 
         let args = Cli::default();
@@ -856,12 +1422,20 @@ mod tests {
         let expected = Stats {
             keys_count: IndexMap::from([("$.key1".to_string(), 2), ("$.key2".to_string(), 1)]),
             line_count: 3,
-            bad_lines: vec![],
+            bad_lines: Default::default(),
             keys_types_count: IndexMap::from([
                 ("$.key1::Number".to_string(), 2),
                 ("$.key2::Number".to_string(), 1),
             ]),
-            empty_lines: vec![],
+            empty_lines: Default::default(),
+            parse_errors: IndexMap::new(),
+            repairs: IndexMap::new(),
+            duplicate_keys: IndexMap::new(),
+            duplicate_records: None,
+            candidate_keys: None,
+            filtered_out: 0,
+            depth_histogram: BTreeMap::from([(1, 3)]),
+            sampling: None,
         };
 
         let args = Cli {
@@ -884,9 +1458,9 @@ mod tests {
         let reader = io::BufReader::new(tmpfile);
 
         let expected: Vec<IdJSON> = vec![
-            (1.to_string(), json!({"key1": 123})),
-            (2.to_string(), json!({"key2": 123})),
-            (3.to_string(), json!({"key1": 123})),
+            (RecordLocation::from(1), json!({"key1": 123})),
+            (RecordLocation::from(2), json!({"key2": 123})),
+            (RecordLocation::from(3), json!({"key1": 123})),
         ];
 
         let args = Cli::default();
@@ -894,7 +1468,7 @@ mod tests {
 
         let json_iter = parse_ndjson_bufreader(&args, reader, &errors);
         assert_eq!(expected, json_iter.collect::<Vec<IdJSON>>());
-        assert!(errors.container.borrow().is_empty())
+        assert!(errors.container.lock().unwrap().is_empty())
     }
 
     #[test]
@@ -907,8 +1481,8 @@ mod tests {
         let reader = io::BufReader::new(tmpfile);
 
         let expected: Vec<IdJSON> = vec![
-            (1.to_string(), json!({"key1": 123})),
-            (3.to_string(), json!({"key1": 123})),
+            (RecordLocation::from(1), json!({"key1": 123})),
+            (RecordLocation::from(3), json!({"key1": 123})),
         ];
 
         let args = Cli::default();
@@ -916,44 +1490,44 @@ mod tests {
 
         let json_iter = parse_ndjson_bufreader(&args, reader, &errors);
         assert_eq!(expected, json_iter.collect::<Vec<IdJSON>>());
-        assert!(errors.container.borrow().len() == 1)
+        assert!(errors.container.lock().unwrap().len() == 1)
     }
 
     #[test]
     fn simple_expand_jsonpath_query() {
         let json_iter_in: Vec<IdJSON> = vec![
-            (1.to_string(), json!({"key1": [1, 2, 3]})),
-            (2.to_string(), json!({"key2": 123})),
-            (3.to_string(), json!({"key1": [4, 5]})),
+            (RecordLocation::from(1), json!({"key1": [1, 2, 3]})),
+            (RecordLocation::from(2), json!({"key2": 123})),
+            (RecordLocation::from(3), json!({"key1": [4, 5]})),
         ];
         let json_iter_in = json_iter_in.iter().cloned();
 
         let args = Cli {
-            jsonpath: Some("$.key1[*]".to_string()),
+            jsonpath: vec!["$.key1[*]".to_string()],
             ..Default::default()
         };
         let settings = Settings::init(args).unwrap();
         let errors = Errors::default();
 
         let expected: Vec<IdJSON> = vec![
-            ("1:$.key1[*][0]".to_string(), json!(1)),
-            ("1:$.key1[*][1]".to_string(), json!(2)),
-            ("1:$.key1[*][2]".to_string(), json!(3)),
-            ("3:$.key1[*][0]".to_string(), json!(4)),
-            ("3:$.key1[*][1]".to_string(), json!(5)),
+            (RecordLocation::from(1).with_match(0), json!(1)),
+            (RecordLocation::from(1).with_match(1), json!(2)),
+            (RecordLocation::from(1).with_match(2), json!(3)),
+            (RecordLocation::from(3).with_match(0), json!(4)),
+            (RecordLocation::from(3).with_match(1), json!(5)),
         ];
 
         let json_iter = expand_jsonpath_query(&settings, json_iter_in, &errors);
         assert_eq!(expected, json_iter.collect::<Vec<IdJSON>>());
-        assert!(errors.container.borrow().len() == 1)
+        assert!(errors.container.lock().unwrap().len() == 1)
     }
 
     #[test]
     fn simple_process_json_iterable() {
         let json_iter_in: Vec<IdJSON> = vec![
-            (1.to_string(), json!({"key1": 123})),
-            (2.to_string(), json!({"key2": 123})),
-            (3.to_string(), json!({"key1": 123})),
+            (RecordLocation::from(1), json!({"key1": 123})),
+            (RecordLocation::from(2), json!({"key2": 123})),
+            (RecordLocation::from(3), json!({"key1": 123})),
         ];
         let json_iter_in = json_iter_in.iter().cloned();
 
@@ -968,20 +1542,21 @@ mod tests {
                 ("$.key1::Number".to_string(), 2),
                 ("$.key2::Number".to_string(), 1),
             ]),
+            depth_histogram: BTreeMap::from([(1, 3)]),
             ..Default::default()
         };
 
         let stats = process_json_iterable(&settings, json_iter_in, &errors);
         assert_eq!(expected, stats);
-        assert!(errors.container.borrow().is_empty())
+        assert!(errors.container.lock().unwrap().is_empty())
     }
 
     #[test]
     fn simple_process_json_result_iterable() {
         let json_iter_in: Vec<IdJSONResult> = vec![
-            (1.to_string(), Ok(json!({"key1": 123}))),
-            (2.to_string(), Ok(json!({"key2": 123}))),
-            (3.to_string(), Ok(json!({"key1": 123}))),
+            (RecordLocation::from(1), Ok(json!({"key1": 123}))),
+            (RecordLocation::from(2), Ok(json!({"key2": 123}))),
+            (RecordLocation::from(3), Ok(json!({"key1": 123}))),
         ];
         let json_iter_in = json_iter_in.into_iter();
 
@@ -995,6 +1570,7 @@ mod tests {
                 ("$.key1::Number".to_string(), 2),
                 ("$.key2::Number".to_string(), 1),
             ]),
+            depth_histogram: BTreeMap::from([(1, 3)]),
             ..Default::default()
         };
 
@@ -1005,14 +1581,14 @@ mod tests {
     #[test]
     fn bad_process_json_iterable_path_query() {
         let json_iter_in: Vec<IdJSON> = vec![
-            (1.to_string(), json!({"key1": 123})),
-            (2.to_string(), json!({"key2": 123})),
-            (3.to_string(), json!({"key1": 123})),
+            (RecordLocation::from(1), json!({"key1": 123})),
+            (RecordLocation::from(2), json!({"key2": 123})),
+            (RecordLocation::from(3), json!({"key1": 123})),
         ];
         let json_iter_in = json_iter_in.iter().cloned();
 
         let args = Cli {
-            jsonpath: Some("$.key1".to_string()),
+            jsonpath: vec!["$.key1".to_string()],
             ..Default::default()
         };
         let settings = Settings::init(args).unwrap();
@@ -1022,26 +1598,27 @@ mod tests {
             keys_count: IndexMap::from([("$".to_string(), 2)]),
             line_count: 2,
             keys_types_count: IndexMap::from([("$::Number".to_string(), 2)]),
-            empty_lines: vec![2.to_string()],
+            empty_lines: vec![RecordLocation::from(2)].into(),
+            depth_histogram: BTreeMap::from([(0, 2)]),
             ..Default::default()
         };
 
         let stats = process_json_iterable(&settings, json_iter_in, &errors);
         assert_eq!(expected, stats);
-        assert!(errors.container.borrow().len() == 1)
+        assert!(errors.container.lock().unwrap().len() == 1)
     }
 
     #[test]
     fn bad_process_json_result_iterable_path_query() {
         let json_iter_in: Vec<IdJSONResult> = vec![
-            (1.to_string(), Ok(json!({"key1": 123}))),
-            (2.to_string(), Ok(json!({"key2": 123}))),
-            (3.to_string(), Ok(json!({"key1": 123}))),
+            (RecordLocation::from(1), Ok(json!({"key1": 123}))),
+            (RecordLocation::from(2), Ok(json!({"key2": 123}))),
+            (RecordLocation::from(3), Ok(json!({"key1": 123}))),
         ];
         let json_iter_in = json_iter_in.into_iter();
 
         let args = Cli {
-            jsonpath: Some("$.key1".to_string()),
+            jsonpath: vec!["$.key1".to_string()],
             ..Default::default()
         };
         let settings = Settings::init(args).unwrap();
@@ -1050,7 +1627,8 @@ mod tests {
             keys_count: IndexMap::from([("$".to_string(), 2)]),
             line_count: 2,
             keys_types_count: IndexMap::from([("$::Number".to_string(), 2)]),
-            empty_lines: vec![2.to_string()],
+            empty_lines: vec![RecordLocation::from(2)].into(),
+            depth_histogram: BTreeMap::from([(0, 2)]),
             ..Default::default()
         };
 
@@ -1058,12 +1636,732 @@ mod tests {
         assert_eq!(expected, stats);
     }
 
+    #[test]
+    fn process_json_result_iterable_by_sections() {
+        let json_iter_in: Vec<IdJSONResult> = vec![
+            (
+                RecordLocation::from(1),
+                Ok(json!({"a": {"x": 1}, "b": {"y": "z"}})),
+            ),
+            (RecordLocation::from(2), Ok(json!({"a": {"x": 2}}))),
+            (
+                RecordLocation::from(3),
+                Err(NDJSONError::EmptyQuery { line: None }),
+            ),
+        ];
+
+        let args = Cli {
+            jsonpath: vec!["first=$.a".to_string(), "$.b".to_string()],
+            ..Default::default()
+        };
+        let settings = Settings::init(args).unwrap();
+
+        let expected = IndexMap::from([
+            (
+                Section::from("first"),
+                Stats {
+                    keys_count: IndexMap::from([("$.x".to_string(), 2)]),
+                    line_count: 2,
+                    keys_types_count: IndexMap::from([("$.x::Number".to_string(), 2)]),
+                    empty_lines: vec![RecordLocation::from(3)].into(),
+                    depth_histogram: BTreeMap::from([(1, 2)]),
+                    ..Default::default()
+                },
+            ),
+            (
+                Section::from("$.b"),
+                Stats {
+                    keys_count: IndexMap::from([("$.y".to_string(), 1)]),
+                    line_count: 1,
+                    keys_types_count: IndexMap::from([("$.y::String".to_string(), 1)]),
+                    empty_lines: vec![RecordLocation::from(2), RecordLocation::from(3)].into(),
+                    depth_histogram: BTreeMap::from([(1, 1)]),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        let stats = process_json_result_iterable_by_section(&settings, json_iter_in.into_iter());
+        assert_eq!(expected, stats);
+        assert_eq!(
+            stats.keys().collect::<Vec<_>>(),
+            expected.keys().collect::<Vec<_>>()
+        );
+
+        let json_iter_in: Vec<IdJSONResult> = vec![
+            (
+                RecordLocation::from(1),
+                Ok(json!({"a": {"x": 1}, "b": {"y": "z"}})),
+            ),
+            (RecordLocation::from(2), Ok(json!({"a": {"x": 2}}))),
+        ];
+        let mut stats = process_json_result_iterable_by_section_par(
+            &settings,
+            json_iter_in.into_iter().par_bridge(),
+        );
+        let (first, b) = (Section::from("first"), Section::from("$.b"));
+        assert_eq!(stats[&first].keys_count, expected[&first].keys_count);
+        assert_eq!(stats[&b].line_count, 1);
+        assert_eq!(stats.swap_remove(&b).unwrap().empty_lines, ["2"]);
+    }
+
+    #[test]
+    fn process_json_result_iterable_by_groups() {
+        let json_iter_in = || -> Vec<IdJSONResult> {
+            vec![
+                (RecordLocation::from(1), Ok(json!({"type": "b", "x": 1}))),
+                (RecordLocation::from(2), Ok(json!({"type": "a", "y": 2}))),
+                (RecordLocation::from(3), Ok(json!({"x": 3}))),
+                (RecordLocation::from(4), Ok(json!({"type": "b"}))),
+                (
+                    RecordLocation::from(5),
+                    Err(serde_json::from_str::<Value>("{").unwrap_err().into()),
+                ),
+            ]
+        };
+
+        let args = Cli {
+            group_by: Some("$.type".to_string()),
+            jsonpath: vec!["$.x".to_string()],
+            ..Default::default()
+        };
+        let settings = Settings::init(args).unwrap();
+
+        let section = |group: Option<&str>| {
+            Section::new("$.x", group.map(|group| Group::Value(group.to_string())))
+        };
+        let expected = IndexMap::from([
+            (
+                section(Some(r#""a""#)),
+                Stats {
+                    empty_lines: vec![RecordLocation::from(2)].into(),
+                    ..Default::default()
+                },
+            ),
+            (
+                section(Some(r#""b""#)),
+                Stats {
+                    keys_count: IndexMap::from([("$".to_string(), 1)]),
+                    line_count: 1,
+                    keys_types_count: IndexMap::from([("$::Number".to_string(), 1)]),
+                    empty_lines: vec![RecordLocation::from(4)].into(),
+                    depth_histogram: BTreeMap::from([(0, 1)]),
+                    ..Default::default()
+                },
+            ),
+            (
+                Section::new("$.x", Some(Group::Missing)),
+                Stats {
+                    keys_count: IndexMap::from([("$".to_string(), 1)]),
+                    line_count: 1,
+                    keys_types_count: IndexMap::from([("$::Number".to_string(), 1)]),
+                    depth_histogram: BTreeMap::from([(0, 1)]),
+                    ..Default::default()
+                },
+            ),
+            (section(None), {
+                let mut stats = Stats {
+                    keys_count: IndexMap::from([("$".to_string(), 2)]),
+                    line_count: 2,
+                    keys_types_count: IndexMap::from([("$::Number".to_string(), 2)]),
+                    bad_lines: vec![RecordLocation::from(5)].into(),
+                    empty_lines: vec![RecordLocation::from(2), RecordLocation::from(4)].into(),
+                    depth_histogram: BTreeMap::from([(0, 2)]),
+                    ..Default::default()
+                };
+                let error = serde_json::from_str::<Value>("{").unwrap_err();
+                stats.add_parse_error(
+                    &RecordLocation::from(5),
+                    errors::ParseDiagnostic::from(&error),
+                );
+                stats
+            }),
+        ]);
+
+        let stats = process_json_result_iterable_by_section(&settings, json_iter_in().into_iter());
+        assert_eq!(expected, stats);
+        assert_eq!(
+            stats.keys().collect::<Vec<_>>(),
+            expected.keys().collect::<Vec<_>>()
+        );
+
+        let stats = process_json_result_iterable_by_section_par(
+            &settings,
+            json_iter_in().into_iter().par_bridge(),
+        );
+        assert_eq!(expected, stats);
+        assert_eq!(
+            stats.keys().collect::<Vec<_>>(),
+            expected.keys().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn process_json_result_iterable_where() {
+        let json_iter_in: Vec<IdJSONResult> = vec![
+            (RecordLocation::from(1), Ok(json!({"type": "a", "n": 1}))),
+            (RecordLocation::from(2), Ok(json!({"type": "b", "n": 2}))),
+            (RecordLocation::from(3), Ok(json!({"type": "a"}))),
+            (
+                RecordLocation::from(4),
+                Err(NDJSONError::EmptyQuery { line: None }),
+            ),
+        ];
+
+        let args = Cli {
+            where_: vec!["$.type == a".to_string(), "$.n".to_string()],
+            ..Default::default()
+        };
+        let settings = Settings::init(args).unwrap();
+
+        let expected = Stats {
+            keys_count: IndexMap::from([("$.type".to_string(), 1), ("$.n".to_string(), 1)]),
+            line_count: 1,
+            keys_types_count: IndexMap::from([
+                ("$.type::String".to_string(), 1),
+                ("$.n::Number".to_string(), 1),
+            ]),
+            empty_lines: vec![RecordLocation::from(4)].into(),
+            filtered_out: 2,
+            depth_histogram: BTreeMap::from([(1, 1)]),
+            ..Default::default()
+        };
+
+        let stats = process_json_result_iterable(&settings, json_iter_in.into_iter());
+        assert_eq!(expected, stats);
+
+        let json_iter_in: Vec<IdJSONResult> = vec![
+            (RecordLocation::from(1), Ok(json!({"type": "a", "n": 1}))),
+            (RecordLocation::from(2), Ok(json!({"type": "b", "n": 2}))),
+            (RecordLocation::from(3), Ok(json!({"type": "a"}))),
+        ];
+        let stats =
+            process_json_result_iterable_par(&settings, json_iter_in.into_iter().par_bridge());
+        assert_eq!(stats.line_count, 1);
+        assert_eq!(stats.filtered_out, 2);
+    }
+
+    #[test]
+    fn process_json_result_iterable_sampled() {
+        let json_iter_in = || {
+            (1..=100).map(|i| match i % 10 {
+                0 => (
+                    RecordLocation::from(i),
+                    Err(NDJSONError::EmptyQuery { line: None }),
+                ),
+                _ => (RecordLocation::from(i), Ok(json!({"i": i}))),
+            })
+        };
+
+        let args = Cli {
+            sample: Some(20),
+            seed: Some(3),
+            ..Default::default()
+        };
+        let settings = Settings::init(args).unwrap();
+
+        let stats = process_json_result_iterable(&settings, json_iter_in());
+        assert_eq!(stats.line_count + stats.empty_lines.len(), 20);
+        assert!(stats.empty_lines.iter().all(|id| id.line % 10 == 0));
+        assert_eq!(stats.sampling, settings.args.sampling());
+        assert_eq!(
+            stats,
+            process_json_result_iterable(&settings, json_iter_in())
+        );
+
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        for i in 1..=100 {
+            writeln!(tmpfile, r#"{{"i": {i}}}"#).unwrap();
+        }
+        let path = tmpfile.path().to_path_buf();
+        let settings = Settings::init(Cli {
+            sample_rate: Some("0.5".parse().unwrap()),
+            parallel: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let stats = path.json_stats(&settings).unwrap();
+        assert!((30..70).contains(&stats.line_count), "{}", stats.line_count);
+        assert!(
+            stats
+                .sampling
+                .is_some_and(|sampling| sampling.seed == settings.args.seed.unwrap())
+        );
+    }
+
+    #[test]
+    fn json_stats_line_windows() {
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        for i in 1..=10 {
+            match i % 3 {
+                0 => writeln!(tmpfile, "not json").unwrap(),
+                _ => writeln!(tmpfile, r#"{{"i": {i}}}"#).unwrap(),
+            }
+        }
+        let path = tmpfile.path().to_path_buf();
+
+        for parallel in [false, true] {
+            let json_stats = |args: Cli| {
+                let settings = Settings::init(Cli { parallel, ..args }).unwrap();
+                let stats = path.json_stats(&settings).unwrap();
+                let lines: Vec<usize> = stats.bad_lines.iter().map(|id| id.line).collect();
+                (stats.line_count, lines)
+            };
+            let ids = |ids: &[usize]| ids.to_vec();
+
+            let args = Cli {
+                skip: Some(4),
+                lines: Some(3),
+                ..Default::default()
+            };
+            assert_eq!(json_stats(args), (2, ids(&[6])));
+            let args = Cli {
+                range: Some("2..=6".parse().unwrap()),
+                ..Default::default()
+            };
+            assert_eq!(json_stats(args), (3, ids(&[3, 6])));
+            let args = Cli {
+                tail: Some(4),
+                ..Default::default()
+            };
+            assert_eq!(json_stats(args), (3, ids(&[9])));
+        }
+    }
+
+    #[test]
+    fn json_stats_parse_errors() {
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        for line in [
+            r#"{"a": 1}"#,
+            r#"{"a": 1"#,
+            r#"{"a": 1,}"#,
+            r#"{"a": 2"#,
+            r#"{"a": 3}{"a": 4}"#,
+            r#"{"a": 5"#,
+            r#"{"a": 6"#,
+        ] {
+            writeln!(tmpfile, "{line}").unwrap();
+        }
+        let path = tmpfile.path().to_path_buf();
+
+        for parallel in [false, true] {
+            let settings = Settings::init(Cli {
+                parallel,
+                ..Default::default()
+            })
+            .unwrap();
+            let stats = path.json_stats(&settings).unwrap();
+            let counts: BTreeMap<_, _> = stats
+                .parse_errors
+                .iter()
+                .map(|(kind, summary)| (kind.name(), summary.count))
+                .collect();
+            assert_eq!(
+                counts,
+                BTreeMap::from([
+                    ("trailing_characters", 1),
+                    ("trailing_comma", 1),
+                    ("truncated", 4),
+                ])
+            );
+
+            let truncated = &stats.parse_errors[&ParseErrorKind::Truncated];
+            assert_eq!(truncated.examples.len(), stats::MAX_PARSE_ERROR_EXAMPLES);
+            for example in &truncated.examples {
+                assert_eq!(example.diagnostic.column, 7);
+                assert_eq!(example.diagnostic.offset, 6);
+                assert!(
+                    example
+                        .diagnostic
+                        .snippet
+                        .as_ref()
+                        .unwrap()
+                        .starts_with(r#"{"a": "#)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn process_json_iterable_par_error_sinks() {
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        for line in [r#"{"a": 1}"#, "bad", "worse", r#"{"a": 2}"#, "worst"] {
+            writeln!(tmpfile, "{line}").unwrap();
+        }
+        let path = tmpfile.path().to_path_buf();
+        let settings = Settings::init(Cli::default()).unwrap();
+
+        let bounded = BoundedErrors::new(1);
+        let json_iter = parse_ndjson_bufreader_par(&settings.args, &path, &bounded).unwrap();
+        let stats = process_json_iterable_par(&settings, json_iter, &bounded);
+        assert_eq!(stats.line_count, 2);
+        assert_eq!(stats.bad_lines.len(), 1);
+        assert_eq!(bounded.counts(), IndexMap::from([("invalid_json", 3)]));
+        assert_eq!(bounded.dropped(), 2);
+
+        let json_iter = parse_ndjson_bufreader_par(&settings.args, &path, &IgnoreErrors).unwrap();
+        let stats = process_json_iterable_par(&settings, json_iter, &IgnoreErrors);
+        assert_eq!(stats.line_count, 2);
+        assert!(stats.bad_lines.is_empty());
+    }
+
+    #[test]
+    fn quarantine_bad_and_empty_lines() {
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        for line in [r#"{"a": 1}"#, r#"{"a": 1"#, r#"{"b": 2}"#, "not json"] {
+            writeln!(tmpfile, "{line}").unwrap();
+        }
+        let path = tmpfile.path().to_path_buf();
+        let bad_lines_out = tempfile::NamedTempFile::new().unwrap();
+        let empty_lines_out = tempfile::NamedTempFile::new().unwrap();
+        let read_lines = |out: &tempfile::NamedTempFile| -> Vec<Value> {
+            let mut lines: Vec<Value> = std::fs::read_to_string(out.path())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            lines.sort_by_key(|line| line["id"]["line"].as_u64());
+            lines
+        };
+        let init_settings = |parallel| {
+            Settings::init(Cli {
+                parallel,
+                jsonpath: vec!["$.a".to_string()],
+                bad_lines_out: Some(bad_lines_out.path().to_path_buf()),
+                empty_lines_out: Some(empty_lines_out.path().to_path_buf()),
+                ..Default::default()
+            })
+            .unwrap()
+        };
+        let file = path.to_string_lossy();
+
+        for parallel in [false, true] {
+            let settings = init_settings(parallel);
+            let stats = path.json_stats(&settings).unwrap();
+            assert_eq!(stats.line_count, 1);
+            settings.quarantine().finish().unwrap();
+
+            let bad_lines = read_lines(&bad_lines_out);
+            assert_eq!(bad_lines.len(), 2);
+            assert_eq!(bad_lines[0]["file"], json!(file));
+            assert_eq!(bad_lines[0]["id"], json!({"line": 2, "offset": 9}));
+            assert_eq!(bad_lines[0]["kind"], json!("truncated"));
+            assert_eq!(bad_lines[0]["line"], json!(r#"{"a": 1"#));
+            assert_eq!(bad_lines[1]["line"], json!("not json"));
+            assert_eq!(
+                read_lines(&empty_lines_out),
+                [json!({
+                    "file": file,
+                    "id": {"line": 3, "offset": 17},
+                    "query": "$.a",
+                    "error": "Line returned empty for the given query",
+                    "line": r#"{"b":2}"#,
+                })]
+            );
+        }
+
+        // The containers of the `ErrFiltered` and `ErrorsPar` flows write as they record
+        let settings = init_settings(false);
+        let errors = Errors::default().with_quarantine(settings.quarantine());
+        let json_iter = parse_ndjson_file_path(&settings.args, &path, &errors).unwrap();
+        let stats = process_json_iterable(&settings, json_iter, &errors);
+        assert_eq!(stats.bad_lines, ["2", "4"]);
+        settings.quarantine().finish().unwrap();
+        assert_eq!(read_lines(&bad_lines_out).len(), 2);
+        assert_eq!(read_lines(&empty_lines_out)[0]["line"], json!(r#"{"b":2}"#));
+
+        let settings = init_settings(true);
+        let errors = Errors::default().with_quarantine(settings.quarantine());
+        let json_iter = parse_ndjson_bufreader_par(&settings.args, &path, &errors).unwrap();
+        process_json_iterable_par(&settings, json_iter, &errors);
+        settings.quarantine().finish().unwrap();
+        assert_eq!(read_lines(&bad_lines_out)[1]["line"], json!("not json"));
+        assert_eq!(read_lines(&empty_lines_out).len(), 1);
+    }
+
+    #[test]
+    fn json_stats_clean_out() {
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        for line in [r#"{"b": 1, "a": 2}"#, "not json", r#"{"a": 3}"#, r#"[4]"#] {
+            writeln!(tmpfile, "{line}").unwrap();
+        }
+        let path = tmpfile.path().to_path_buf();
+        let clean_out = tempfile::NamedTempFile::new().unwrap();
+
+        for (parallel, canonical) in [(false, false), (true, true)] {
+            let settings = Settings::init(Cli {
+                parallel,
+                canonical,
+                skip: Some(1),
+                clean_out: Some(clean_out.path().to_path_buf()),
+                ..Default::default()
+            })
+            .unwrap();
+            let stats = path.json_stats(&settings).unwrap();
+            assert_eq!(stats.line_count, 2);
+            settings.clean_copy.as_ref().unwrap().finish().unwrap();
+
+            let mut copied: Vec<String> = std::fs::read_to_string(clean_out.path())
+                .unwrap()
+                .lines()
+                .map(str::to_string)
+                .collect();
+            copied.sort();
+            let expected = if canonical {
+                [r#"[4]"#, r#"{"a":3}"#]
+            } else {
+                [r#"[4]"#, r#"{"a": 3}"#]
+            };
+            assert_eq!(copied, expected);
+        }
+    }
+
+    #[test]
+    fn json_stats_repair() {
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        for line in [
+            "\u{feff}{\"a\": 1}",
+            r#"{"a": 2,}"#,
+            r#"{"a": NaN}"#,
+            r#"{'a': 'four'}"#,
+            r#"{"a": 5} {"a": 6}"#,
+            r#"{"a": {"b": 7,"#,
+            r#""c": 8}}"#,
+            r#"{"a": 9"#,
+        ] {
+            writeln!(tmpfile, "{line}").unwrap();
+        }
+        let path = tmpfile.path().to_path_buf();
+
+        for parallel in [false, true] {
+            let settings = Settings::init(Cli {
+                parallel,
+                repair: true,
+                ..Default::default()
+            })
+            .unwrap();
+            let stats = path.json_stats(&settings).unwrap();
+            assert_eq!(stats.line_count, 7);
+            assert_eq!(stats.bad_lines, ["8"]);
+            assert_eq!(
+                stats.repairs,
+                IndexMap::from([
+                    (Repair::Bom, 1),
+                    (Repair::TrailingCommas, 1),
+                    (Repair::NonFiniteNumbers, 1),
+                    (Repair::SingleQuotes, 1),
+                    (Repair::GluedRecords, 1),
+                    (Repair::SplitRecord, 1),
+                ])
+            );
+            assert_eq!(stats.keys_count["$.a.b"], 1);
+        }
+
+        let settings = Settings::init(Cli::default()).unwrap();
+        let stats = path.json_stats(&settings).unwrap();
+        assert_eq!(stats.line_count, 0);
+        assert_eq!(stats.bad_lines.len(), 8);
+        assert!(stats.repairs.is_empty());
+    }
+
+    #[test]
+    fn json_stats_duplicate_keys() {
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        for line in [
+            r#"{"id": 1, "id": 2}"#,
+            r#"{"id": 3, "a": {"b": 1}}"#,
+            r#"{"id": 4, "a": [{"b": 1, "b": 2}], "id": 5}"#,
+        ] {
+            writeln!(tmpfile, "{line}").unwrap();
+        }
+        let path = tmpfile.path().to_path_buf();
+
+        for parallel in [false, true] {
+            let settings = Settings::init(Cli {
+                parallel,
+                duplicate_keys: true,
+                ..Default::default()
+            })
+            .unwrap();
+            let stats = path.json_stats(&settings).unwrap();
+            assert_eq!(stats.line_count, 3);
+            assert!(stats.bad_lines.is_empty());
+            assert_eq!(
+                stats.duplicate_keys.keys().collect::<Vec<_>>(),
+                ["$.a[*].b", "$.id"]
+            );
+            assert_eq!(stats.duplicate_keys["$.id"], ["1", "3"]);
+            assert_eq!(stats.duplicate_keys["$.a[*].b"], ["3"]);
+        }
+
+        let settings = Settings::init(Cli {
+            duplicate_keys: true,
+            duplicate_keys_bad: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let stats = path.json_stats(&settings).unwrap();
+        assert_eq!(stats.line_count, 1);
+        assert_eq!(stats.bad_lines, ["1", "3"]);
+        assert_eq!(stats.parse_errors[&ParseErrorKind::DuplicateKey].count, 2);
+        assert_eq!(stats.duplicate_keys["$.id"], ["1", "3"]);
+
+        let stats = path
+            .json_stats(&Settings::init(Cli::default()).unwrap())
+            .unwrap();
+        assert_eq!(stats.line_count, 3);
+        assert!(stats.duplicate_keys.is_empty());
+    }
+
+    #[test]
+    fn json_stats_duplicates_and_candidate_keys() {
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        for line in [
+            r#"{"id": 1, "event": {"id": "a", "at": 10}}"#,
+            r#"{"id": 2, "event": {"id": "b", "at": 10}}"#,
+            r#"{"event": {"at": 10, "id": "a"}, "id": 1}"#,
+            r#"{"id": 4, "event": {"id": "a", "at": 11}}"#,
+        ] {
+            writeln!(tmpfile, "{line}").unwrap();
+        }
+        let path = tmpfile.path().to_path_buf();
+
+        for parallel in [false, true] {
+            let settings = Settings::init(Cli {
+                parallel,
+                duplicates: true,
+                candidate_keys: true,
+                ..Default::default()
+            })
+            .unwrap();
+            let stats = path.json_stats(&settings).unwrap();
+            let duplicates = stats.duplicate_records.unwrap();
+            assert_eq!((duplicates.records, duplicates.duplicates), (4, 1));
+            assert_eq!(duplicates.lines, ["3"]);
+            let keys = stats.candidate_keys.unwrap();
+            assert_eq!(keys.records, 4);
+            assert!(keys.candidates.is_empty());
+            assert_eq!(keys.paths["$.id"].distinct, 3);
+            assert_eq!(keys.paths["$.event.at"].distinct, 2);
+        }
+
+        let settings = Settings::init(Cli {
+            duplicates: true,
+            duplicates_on: vec!["$.event.id".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        let duplicates = path.json_stats(&settings).unwrap().duplicate_records;
+        assert_eq!(duplicates.unwrap().lines, ["3", "4"]);
+
+        // The records are compared once filtered by the predicates
+        let settings = Settings::init(Cli {
+            duplicates: true,
+            candidate_keys: true,
+            where_: vec!["$.id != 2".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        let stats = path.json_stats(&settings).unwrap();
+        let duplicates = stats.duplicate_records.unwrap();
+        assert_eq!((duplicates.records, duplicates.duplicates), (3, 1));
+        assert_eq!(stats.candidate_keys.unwrap().records, 3);
+
+        let settings = Settings::init(Cli {
+            candidate_keys: true,
+            lines: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+        let keys = path.json_stats(&settings).unwrap().candidate_keys.unwrap();
+        assert_eq!(keys.candidates, ["$.event.id", "$.id"]);
+    }
+
+    #[test]
+    fn json_stats_fail_fast() {
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        for i in 1..=100 {
+            if i % 10 == 0 {
+                writeln!(tmpfile, "not json").unwrap();
+            } else {
+                writeln!(tmpfile, r#"{{"a": {i}}}"#).unwrap();
+            }
+        }
+        let path = tmpfile.path().to_path_buf();
+
+        let settings = Settings::init(Cli {
+            max_bad_lines: Some(Threshold::Count(2)),
+            fail_fast: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let stats = path.json_stats(&settings).unwrap();
+        assert_eq!(stats.bad_lines, ["10", "20", "30"]);
+        assert_eq!(stats.line_count, 27);
+        assert!(settings.budget.check(&stats).is_err());
+
+        let settings = Settings::init(Cli {
+            parallel: true,
+            fail_fast: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let stats = path.json_stats(&settings).unwrap();
+        assert!(!stats.bad_lines.is_empty());
+        assert!(stats.lines_read() <= 100);
+
+        let settings = Settings::init(Cli {
+            max_bad_lines: Some(Threshold::Count(2)),
+            ..Default::default()
+        })
+        .unwrap();
+        let stats = path.json_stats(&settings).unwrap();
+        assert_eq!(stats.lines_read(), 100);
+        assert!(settings.budget.check(&stats).is_err());
+    }
+
+    #[test]
+    fn json_stats_max_error_samples() {
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        for i in 1..=50 {
+            if (11..=40).contains(&i) || i == 45 {
+                writeln!(tmpfile, "not json").unwrap();
+            } else {
+                writeln!(tmpfile, r#"{{"a": {i}}}"#).unwrap();
+            }
+        }
+        let path = tmpfile.path().to_path_buf();
+
+        let settings = Settings::init(Cli {
+            max_error_samples: Some(1),
+            line_ranges: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let stats = path.json_stats(&settings).unwrap();
+        assert_eq!(stats.bad_lines.len(), 31);
+        assert_eq!(stats.bad_lines.samples()[0].to_string(), "11-40");
+        assert_eq!(stats.bad_lines.samples()[0].offset, Some(91));
+        assert!(stats.bad_lines.is_truncated());
+        assert_eq!(stats.lines_read(), 50);
+
+        let settings = Settings::init(Cli {
+            max_error_samples: Some(2),
+            parallel: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let stats = path.json_stats(&settings).unwrap();
+        assert_eq!(stats.bad_lines.len(), 31);
+        assert_eq!(stats.bad_lines.samples().len(), 2);
+    }
+
     #[test]
     fn simple_process_json_iterable_par() {
-        let iter: Vec<(String, Value)> = vec![
-            (1.to_string(), json!({"key1": 123})),
-            (2.to_string(), json!({"key2": 123})),
-            (3.to_string(), json!({"key1": 123})),
+        let iter: Vec<IdJSON> = vec![
+            (RecordLocation::from(1), json!({"key1": 123})),
+            (RecordLocation::from(2), json!({"key2": 123})),
+            (RecordLocation::from(3), json!({"key1": 123})),
         ];
         let iter = iter.into_iter().par_bridge();
 
@@ -1074,22 +2372,23 @@ mod tests {
                 ("$.key1::Number".to_string(), 2),
                 ("$.key2::Number".to_string(), 1),
             ]),
+            depth_histogram: BTreeMap::from([(1, 3)]),
             ..Default::default()
         };
 
         let args = Cli::default();
         let settings = Settings::init(args).unwrap();
-        let errors = ErrorsPar::default();
+        let errors = Errors::default();
         let stats = process_json_iterable_par(&settings, iter, &errors);
         assert_eq!(expected, stats);
     }
 
     #[test]
     fn simple_process_json_iterable_par_jsonpath() {
-        let iter: Vec<(String, Value)> = vec![
-            (1.to_string(), json!({"key1": 123})),
-            (2.to_string(), json!({"a": {"key2": 123}})),
-            (3.to_string(), json!({"key1": 123})),
+        let iter: Vec<IdJSON> = vec![
+            (RecordLocation::from(1), json!({"key1": 123})),
+            (RecordLocation::from(2), json!({"a": {"key2": 123}})),
+            (RecordLocation::from(3), json!({"key1": 123})),
         ];
         let iter = iter.into_iter().par_bridge();
 
@@ -1097,29 +2396,44 @@ mod tests {
             keys_count: IndexMap::from([("$.key2".to_string(), 1)]),
             line_count: 1,
             keys_types_count: IndexMap::from([("$.key2::Number".to_string(), 1)]),
-            empty_lines: vec![1.to_string(), 3.to_string()],
+            empty_lines: vec![RecordLocation::from(1), RecordLocation::from(3)].into(),
+            depth_histogram: BTreeMap::from([(1, 1)]),
             ..Default::default()
         };
 
         let args = Cli {
-            jsonpath: Some("$.a".to_string()),
+            jsonpath: vec!["$.a".to_string()],
             ..Default::default()
         };
         let settings = Settings::init(args).unwrap();
-        let errors = ErrorsPar::default();
-        let mut stats = process_json_iterable_par(&settings, iter, &errors);
-        stats.empty_lines.sort_by(|a, b| {
-            a.parse::<usize>()
-                .unwrap()
-                .cmp(&b.parse::<usize>().unwrap())
-        });
+        let errors = Errors::default();
+        let stats = process_json_iterable_par(&settings, iter, &errors);
         assert_eq!(expected, stats);
     }
 
     #[test]
     fn add_filestats() {
+        let parse_errors = |ids: &[&str]| {
+            let error = serde_json::from_str::<Value>("{").unwrap_err();
+            let examples: Vec<_> = ids
+                .iter()
+                .map(|id| stats::ParseErrorExample {
+                    id: id.parse().unwrap(),
+                    diagnostic: errors::ParseDiagnostic::from(&error),
+                })
+                .collect();
+            IndexMap::from([(
+                ParseErrorKind::Truncated,
+                ParseErrorSummary {
+                    count: ids.len(),
+                    examples,
+                },
+            )])
+        };
         let lhs = stats::FileStats {
             file_path: "file/1.json".to_string(),
+            query: None,
+            group: None,
             stats: Stats {
                 keys_count: IndexMap::from([("$.key1".to_string(), 3), ("$.key2".to_string(), 2)]),
                 line_count: 5,
@@ -1127,12 +2441,30 @@ mod tests {
                     ("$.key1::Number".to_string(), 3),
                     ("$.key2::Number".to_string(), 2),
                 ]),
-                bad_lines: vec!["4".to_string()],
-                empty_lines: vec!["5".to_string()],
+                bad_lines: vec![RecordLocation::from(4)].into(),
+                empty_lines: vec![RecordLocation::from(5)].into(),
+                parse_errors: parse_errors(&["4"]),
+                repairs: IndexMap::from([(Repair::Bom, 1)]),
+                duplicate_keys: IndexMap::from([(
+                    "$.id".to_string(),
+                    vec![RecordLocation::from(1)].into(),
+                )]),
+                duplicate_records: Some(DuplicateRecords {
+                    records: 5,
+                    duplicates: 1,
+                    lines: vec![RecordLocation::from(3)].into(),
+                    ..Default::default()
+                }),
+                candidate_keys: None,
+                filtered_out: 0,
+                depth_histogram: BTreeMap::from([(1, 4)]),
+                sampling: None,
             },
         };
         let rhs = stats::FileStats {
             file_path: "file/2.json".to_string(),
+            query: None,
+            group: None,
             stats: Stats {
                 keys_count: IndexMap::from([("$.key3".to_string(), 3), ("$.key2".to_string(), 2)]),
                 line_count: 7,
@@ -1140,8 +2472,24 @@ mod tests {
                     ("$.key3::Number".to_string(), 3),
                     ("$.key2::Number".to_string(), 2),
                 ]),
-                bad_lines: vec!["1".to_string()],
-                empty_lines: vec!["2".to_string()],
+                bad_lines: vec![RecordLocation::from(1)].into(),
+                empty_lines: vec![RecordLocation::from(2)].into(),
+                parse_errors: parse_errors(&["1"]),
+                repairs: IndexMap::from([(Repair::TrailingCommas, 1), (Repair::Bom, 2)]),
+                duplicate_keys: IndexMap::from([(
+                    "$.id".to_string(),
+                    vec![RecordLocation::from(3)].into(),
+                )]),
+                duplicate_records: Some(DuplicateRecords {
+                    records: 7,
+                    duplicates: 1,
+                    lines: vec![RecordLocation::from(6)].into(),
+                    ..Default::default()
+                }),
+                candidate_keys: None,
+                filtered_out: 0,
+                depth_histogram: BTreeMap::from([(1, 5), (2, 1)]),
+                sampling: None,
             },
         };
         let expected = Stats {
@@ -1156,8 +2504,40 @@ mod tests {
                 ("$.key2::Number".to_string(), 4),
                 ("$.key3::Number".to_string(), 3),
             ]),
-            bad_lines: vec!["file/1.json:4".to_string(), "file/2.json:1".to_string()],
-            empty_lines: vec!["file/1.json:5".to_string(), "file/2.json:2".to_string()],
+            bad_lines: vec![
+                RecordLocation::from(4).in_file("file/1.json"),
+                RecordLocation::from(1).in_file("file/2.json"),
+            ]
+            .into(),
+            empty_lines: vec![
+                RecordLocation::from(5).in_file("file/1.json"),
+                RecordLocation::from(2).in_file("file/2.json"),
+            ]
+            .into(),
+            parse_errors: parse_errors(&["file/1.json:4", "file/2.json:1"]),
+            repairs: IndexMap::from([(Repair::Bom, 3), (Repair::TrailingCommas, 1)]),
+            duplicate_keys: IndexMap::from([(
+                "$.id".to_string(),
+                vec![
+                    RecordLocation::from(1).in_file("file/1.json"),
+                    RecordLocation::from(3).in_file("file/2.json"),
+                ]
+                .into(),
+            )]),
+            duplicate_records: Some(DuplicateRecords {
+                records: 12,
+                duplicates: 2,
+                lines: vec![
+                    RecordLocation::from(3).in_file("file/1.json"),
+                    RecordLocation::from(6).in_file("file/2.json"),
+                ]
+                .into(),
+                ..Default::default()
+            }),
+            candidate_keys: None,
+            filtered_out: 0,
+            depth_histogram: BTreeMap::from([(1, 9), (2, 1)]),
+            sampling: None,
         };
 
         let vec_of_file_stats = [lhs.clone(), rhs.clone()];
@@ -0,0 +1,239 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use thiserror::Error;
+
+use super::Stats;
+use super::errors::NDJSONError;
+
+/// Wrapper around the errors we can encounter while parsing a [`Threshold`]
+#[derive(Error, Debug)]
+pub enum ThresholdError {
+    #[error("Expected a number of lines like `100` or a percentage of the lines like `2.5%`")]
+    Invalid,
+    #[error("Percentage must be between 0 and 100, got {0}")]
+    OutOfRange(f64),
+}
+
+/// Most lines of a kind allowed by `--max-bad-lines` or `--max-empty-lines`, either as a
+/// number of lines, e.g. `100`, or as a percentage of the lines read, e.g. `2.5%`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Threshold {
+    Count(usize),
+    Percent(f64),
+}
+
+// Percentages are checked to be in range, so are never NaN
+impl Eq for Threshold {}
+
+impl Threshold {
+    /// Whether `count` lines of the `total` read are over the threshold
+    pub fn exceeded(self, count: usize, total: usize) -> bool {
+        match self {
+            Self::Count(max) => count > max,
+            Self::Percent(max) => total > 0 && count as f64 * 100.0 > max * total as f64,
+        }
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = ThresholdError;
+
+    fn from_str(threshold: &str) -> Result<Self, Self::Err> {
+        let threshold = threshold.trim();
+        let Some(percent) = threshold.strip_suffix('%') else {
+            return threshold
+                .parse()
+                .map(Self::Count)
+                .map_err(|_| ThresholdError::Invalid);
+        };
+        let percent: f64 = percent.parse().map_err(|_| ThresholdError::Invalid)?;
+        if (0.0..=100.0).contains(&percent) {
+            Ok(Self::Percent(percent))
+        } else {
+            Err(ThresholdError::OutOfRange(percent))
+        }
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Count(max) => write!(f, "{max}"),
+            Self::Percent(max) => write!(f, "{max}%"),
+        }
+    }
+}
+
+/// Stats over the error budget of a [`Budget`]
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BudgetExceeded {
+    #[error("{count} bad lines of {total} read exceed --max-bad-lines {max}")]
+    BadLines {
+        count: usize,
+        total: usize,
+        max: Threshold,
+    },
+    #[error("{count} empty lines of {total} read exceed --max-empty-lines {max}")]
+    EmptyLines {
+        count: usize,
+        total: usize,
+        max: Threshold,
+    },
+}
+
+/// Lines that may fail before the data is rejected, from `--max-bad-lines`,
+/// `--max-empty-lines` and `--fail-fast`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub max_bad_lines: Option<Threshold>,
+    pub max_empty_lines: Option<Threshold>,
+    /// Stop reading as soon as a count is exceeded, rather than once the data is read
+    pub fail_fast: bool,
+}
+
+impl Budget {
+    /// Checks the counts of the `stats` against the budget
+    pub fn check(&self, stats: &Stats) -> Result<(), BudgetExceeded> {
+        let total = stats.lines_read();
+        if let Some(max) = self.max_bad_lines {
+            let count = stats.bad_lines.len();
+            if max.exceeded(count, total) {
+                return Err(BudgetExceeded::BadLines { count, total, max });
+            }
+        }
+        if let Some(max) = self.max_empty_lines {
+            let count = stats.empty_lines.len();
+            if max.exceeded(count, total) {
+                return Err(BudgetExceeded::EmptyLines { count, total, max });
+            }
+        }
+        Ok(())
+    }
+
+    /// Tracks the failing lines of an input read by `queries`, to stop at the first over the
+    /// budget when failing fast
+    pub(crate) fn tracker(&self, queries: usize) -> Option<BudgetTracker> {
+        self.fail_fast.then(|| BudgetTracker {
+            budget: *self,
+            bad_lines: AtomicUsize::new(0),
+            empty_lines: (0..queries).map(|_| AtomicUsize::new(0)).collect(),
+        })
+    }
+}
+
+/// Running counts of the failing lines for `--fail-fast`, shared by the threads processing
+/// the lines. Only thresholds given as a number of lines can be exceeded before the data is
+/// read, percentages are left to [`Budget::check`]
+pub(crate) struct BudgetTracker {
+    budget: Budget,
+    bad_lines: AtomicUsize,
+    /// Per `--jsonpath` query, as the records are empty for each query separately
+    empty_lines: Vec<AtomicUsize>,
+}
+
+impl BudgetTracker {
+    /// Counts the `error` of a line selected by the query `query_index`, if any, returning
+    /// whether it takes the lines over the budget
+    pub(crate) fn exceeded_by(&self, query_index: Option<usize>, error: &NDJSONError) -> bool {
+        let (count, max) = match (error, query_index) {
+            (NDJSONError::IOError(_) | NDJSONError::JSONParsingError { .. }, _) => {
+                (&self.bad_lines, self.budget.max_bad_lines)
+            }
+            (NDJSONError::EmptyQuery { .. }, Some(i)) => {
+                (&self.empty_lines[i], self.budget.max_empty_lines)
+            }
+            _ => return false,
+        };
+        let Some(Threshold::Count(max)) = max else {
+            return false;
+        };
+        count.fetch_add(1, Ordering::Relaxed) + 1 > max
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::ndjson::RecordLocation;
+
+    #[test]
+    fn thresholds() {
+        assert_eq!("10".parse::<Threshold>().unwrap(), Threshold::Count(10));
+        assert_eq!(
+            " 2.5% ".parse::<Threshold>().unwrap(),
+            Threshold::Percent(2.5)
+        );
+        assert!(matches!(
+            "101%".parse::<Threshold>(),
+            Err(ThresholdError::OutOfRange(_))
+        ));
+        assert!(matches!(
+            "-1".parse::<Threshold>(),
+            Err(ThresholdError::Invalid)
+        ));
+        assert!(matches!(
+            "NaN%".parse::<Threshold>(),
+            Err(ThresholdError::OutOfRange(_))
+        ));
+
+        assert!(!Threshold::Count(2).exceeded(2, 10));
+        assert!(Threshold::Count(2).exceeded(3, 10));
+        assert!(!Threshold::Percent(20.0).exceeded(2, 10));
+        assert!(Threshold::Percent(20.0).exceeded(3, 10));
+        assert!(!Threshold::Percent(0.0).exceeded(0, 0));
+    }
+
+    #[test]
+    fn check_budget() {
+        let stats = Stats {
+            line_count: 6,
+            bad_lines: vec![RecordLocation::from(1), RecordLocation::from(2)].into(),
+            empty_lines: vec![RecordLocation::from(3)].into(),
+            filtered_out: 1,
+            ..Default::default()
+        };
+        let budget = |max_bad_lines: &str, max_empty_lines: &str| Budget {
+            max_bad_lines: Some(max_bad_lines.parse().unwrap()),
+            max_empty_lines: Some(max_empty_lines.parse().unwrap()),
+            fail_fast: false,
+        };
+        assert_eq!(budget("2", "10%").check(&stats), Ok(()));
+        assert_eq!(
+            budget("1", "0").check(&stats),
+            Err(BudgetExceeded::BadLines {
+                count: 2,
+                total: 10,
+                max: Threshold::Count(1)
+            })
+        );
+        assert_eq!(
+            budget("20%", "5%").check(&stats),
+            Err(BudgetExceeded::EmptyLines {
+                count: 1,
+                total: 10,
+                max: Threshold::Percent(5.0)
+            })
+        );
+        assert_eq!(Budget::default().check(&stats), Ok(()));
+    }
+
+    #[test]
+    fn track_budget() {
+        let budget = Budget {
+            max_bad_lines: Some(Threshold::Count(1)),
+            max_empty_lines: Some(Threshold::Percent(0.0)),
+            fail_fast: true,
+        };
+        let tracker = budget.tracker(2).unwrap();
+        let bad = || NDJSONError::IOError(std::io::Error::other("unreadable"));
+        let empty = NDJSONError::EmptyQuery { line: None };
+        assert!(!tracker.exceeded_by(None, &bad()));
+        assert!(!tracker.exceeded_by(Some(0), &empty));
+        assert!(!tracker.exceeded_by(None, &NDJSONError::FilteredOut));
+        assert!(tracker.exceeded_by(None, &bad()));
+
+        assert!(Budget::default().tracker(1).is_none());
+    }
+}
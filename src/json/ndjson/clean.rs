@@ -0,0 +1,129 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use crate::json::Value;
+
+/// Copy of the lines that parsed as JSON, written for `--clean-out` as they are parsed so a
+/// single pass gives both the stats and a sanitised dataset.
+/// The copy is gzip compressed when its path ends with `.gz`, like the inputs read.
+///
+/// Writing carries on with the analysis should it fail, the first failure being returned by
+/// [`CleanCopy::finish`]
+pub struct CleanCopy {
+    writer: Mutex<CleanWriter>,
+    canonical: bool,
+}
+
+struct CleanWriter {
+    encoder: Encoder,
+    error: Option<io::Error>,
+}
+
+enum Encoder {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl Encoder {
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Self::Plain(writer) => writer,
+            Self::Gzip(writer) => writer,
+        }
+    }
+}
+
+impl CleanCopy {
+    /// Creates the file at `path` to copy the lines to, re-serialising them in compact
+    /// canonical form, with the keys of objects sorted, when `canonical`
+    pub fn create(path: &Path, canonical: bool) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let encoder = if path.extension().and_then(OsStr::to_str) == Some("gz") {
+            Encoder::Gzip(GzEncoder::new(file, Compression::default()))
+        } else {
+            Encoder::Plain(file)
+        };
+        Ok(Self {
+            writer: Mutex::new(CleanWriter {
+                encoder,
+                error: None,
+            }),
+            canonical,
+        })
+    }
+
+    /// Copies the `line` that parsed as the `json`
+    pub fn write(&self, line: &str, json: &Value) {
+        let mut writer = self.writer.lock().unwrap();
+        if writer.error.is_some() {
+            return;
+        }
+        let encoder = writer.encoder.writer();
+        let written = if self.canonical {
+            // Without serde_json's `preserve_order` objects are sorted maps, so are written
+            // with their keys in order
+            serde_json::to_writer(&mut *encoder, json).map_err(io::Error::from)
+        } else {
+            encoder.write_all(line.as_bytes())
+        };
+        writer.error = written.and_then(|()| writeln!(encoder)).err();
+    }
+
+    /// Completes the copy, returning the first error writing it if any
+    pub fn finish(&self) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        if let Some(error) = writer.error.take() {
+            return Err(error);
+        }
+        match &mut writer.encoder {
+            Encoder::Plain(writer) => writer.flush(),
+            Encoder::Gzip(writer) => {
+                writer.try_finish()?;
+                writer.get_mut().flush()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn clean_copies() {
+        let line = r#"{"b": 1,  "a": {"d": [1, 2], "c": null}}"#;
+        let json: Value = serde_json::from_str(line).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+
+        let copy = |file_name: &str, canonical| {
+            let path = dir.path().join(file_name);
+            let clean_copy = CleanCopy::create(&path, canonical).unwrap();
+            clean_copy.write(line, &json);
+            clean_copy.write(line, &json);
+            clean_copy.finish().unwrap();
+            path
+        };
+
+        let path = copy("clean.json", false);
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            format!("{line}\n{line}\n")
+        );
+
+        let path = copy("clean.json.gz", true);
+        let mut copied = String::new();
+        GzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut copied)
+            .unwrap();
+        let canonical = r#"{"a":{"c":null,"d":[1,2]},"b":1}"#;
+        assert_eq!(copied, format!("{canonical}\n{canonical}\n"));
+    }
+}
@@ -0,0 +1,241 @@
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, Read};
+use std::rc::Rc;
+use std::sync::Mutex;
+
+use serde::de::{DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use super::errors::NDJSONError;
+use super::location::RecordLocation;
+use super::stats::{LineIdLimits, LineIds};
+use crate::json::IndexMap;
+use crate::json::paths::{PathSegment, PathStyle};
+
+/// Lines with a key given more than once in an object, by path of the key, as found by
+/// `--duplicate-keys`. Shared by the threads parsing the lines
+#[derive(Debug, Default)]
+pub struct DuplicateKeys {
+    lines: Mutex<IndexMap<String, LineIds>>,
+    limits: LineIdLimits,
+    /// Whether the lines with duplicate keys are bad lines, from `--duplicate-keys-bad`
+    bad: bool,
+}
+
+impl DuplicateKeys {
+    pub fn new(limits: LineIdLimits, bad: bool) -> Self {
+        Self {
+            limits,
+            bad,
+            ..Default::default()
+        }
+    }
+
+    /// Checks the record `text` of the line `id` for duplicate keys, counting the line under
+    /// the path of each. Gives the error the line fails with when duplicate keys are bad
+    pub fn check(&self, id: &RecordLocation, text: &str) -> Result<(), NDJSONError> {
+        let Some(scan) = scan_keys(text) else {
+            return Ok(());
+        };
+        {
+            let mut lines = self.lines.lock().unwrap();
+            for path in scan.duplicates {
+                lines
+                    .entry(path)
+                    .or_insert_with(|| LineIds::new(self.limits))
+                    .push_unordered(id);
+            }
+        }
+        match scan.first_duplicate {
+            Some((path, offset)) if self.bad => {
+                Err(NDJSONError::duplicate_key(&path, offset, text.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The lines found by path, the paths and samples sorted as the lines may have been
+    /// checked in parallel
+    pub fn into_inner(self) -> IndexMap<String, LineIds> {
+        let mut lines = self.lines.into_inner().unwrap();
+        lines.sort_unstable_keys();
+        lines.values_mut().for_each(LineIds::sort);
+        lines
+    }
+}
+
+/// JSONpaths of the keys given more than once in an object of the JSON `text`, each given
+/// once, with the elements of arrays grouped together under `[*]`. Text that is not valid
+/// JSON has none
+pub fn duplicate_key_paths(text: &str) -> Vec<String> {
+    scan_keys(text)
+        .map(|scan| scan.duplicates)
+        .unwrap_or_default()
+}
+
+/// Scans the keys of the JSON `text`, unless it is not valid JSON
+fn scan_keys(text: &str) -> Option<KeyScan> {
+    let mut scan = KeyScan::default();
+    let reader = CountingReader {
+        text: text.as_bytes(),
+        read: Rc::clone(&scan.read),
+    };
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    scan.seed().deserialize(&mut deserializer).ok()?;
+    deserializer.end().ok()?;
+    Some(scan)
+}
+
+/// Walk of a JSON as it is parsed, keeping track of the path of the value being parsed.
+/// Walking the text rather than the parsed [`Value`](crate::json::Value) is needed as the
+/// latter only keeps the last of the values of a duplicate key
+#[derive(Default)]
+struct KeyScan {
+    path: Vec<PathSegment>,
+    duplicates: Vec<String>,
+    /// Path of the first duplicate key, with the byte offset of the end of the key, for
+    /// diagnosing the line as bad
+    first_duplicate: Option<(String, usize)>,
+    /// Bytes of the text read by the parser so far, shared with its [`CountingReader`]
+    read: Rc<Cell<usize>>,
+}
+
+impl KeyScan {
+    fn seed(&mut self) -> KeyScanSeed<'_> {
+        KeyScanSeed(self)
+    }
+}
+
+/// Reader of the text scanned by a [`KeyScan`], counting the bytes read to locate the keys.
+/// The parser reads a byte at a time from readers, so the count is its position
+struct CountingReader<'a> {
+    text: &'a [u8],
+    read: Rc<Cell<usize>>,
+}
+
+impl Read for CountingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.read.get();
+        let n = (&self.text[read..]).read(buf)?;
+        self.read.set(read + n);
+        Ok(n)
+    }
+}
+
+struct KeyScanSeed<'a>(&'a mut KeyScan);
+
+impl<'de> DeserializeSeed<'de> for KeyScanSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeyScanSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let scan = self.0;
+        scan.path.push(PathSegment::AnyIndex);
+        while seq.next_element_seed(scan.seed())?.is_some() {}
+        scan.path.pop();
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let scan = self.0;
+        let mut keys = HashSet::new();
+        while let Some(key) = map.next_key::<String>()? {
+            scan.path.push(PathSegment::Key(key.clone()));
+            if !keys.insert(key) {
+                let path = PathStyle::Jsonpath.format(&scan.path);
+                if scan.first_duplicate.is_none() {
+                    scan.first_duplicate = Some((path.clone(), scan.read.get()));
+                }
+                if !scan.duplicates.contains(&path) {
+                    scan.duplicates.push(path);
+                }
+            }
+            map.next_value_seed(scan.seed())?;
+            scan.path.pop();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::ndjson::errors::ParseErrorKind;
+
+    #[test]
+    fn duplicate_keys_at_any_depth() {
+        assert!(duplicate_key_paths(r#"{"a": 1, "b": {"a": 2}}"#).is_empty());
+        assert_eq!(duplicate_key_paths(r#"{"id": 1, "id": 2}"#), ["$.id"]);
+        assert_eq!(
+            duplicate_key_paths(
+                r#"{"a": {"b": 1, "b": 2}, "c": [{"d": 1, "d": 2}, {"d": 3, "d": 4}], "a": 0}"#
+            ),
+            ["$.a.b", "$.c[*].d", "$.a"]
+        );
+        assert!(duplicate_key_paths(r#"{"id": 1, "id": 2"#).is_empty());
+    }
+
+    #[test]
+    fn check_duplicate_keys() {
+        let duplicate_keys = DuplicateKeys::new(LineIdLimits::default(), false);
+        let text = r#"{"id": 1, "id": 2}"#;
+        assert!(duplicate_keys.check(&RecordLocation::from(3), text).is_ok());
+        assert!(duplicate_keys.check(&RecordLocation::from(1), text).is_ok());
+        assert!(
+            duplicate_keys
+                .check(&RecordLocation::from(2), r#"{"id": 1}"#)
+                .is_ok()
+        );
+        let lines = duplicate_keys.into_inner();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines["$.id"], ["1", "3"]);
+
+        let duplicate_keys = DuplicateKeys::new(LineIdLimits::default(), true);
+        let error = duplicate_keys
+            .check(&RecordLocation::from(1), text)
+            .unwrap_err();
+        let diagnostic = error.parse_diagnostic().unwrap();
+        assert_eq!(diagnostic.kind, ParseErrorKind::DuplicateKey);
+        assert_eq!(diagnostic.message, "duplicate key at `$.id`");
+        assert_eq!((diagnostic.line, diagnostic.column), (1, 15));
+        assert_eq!(diagnostic.snippet.as_deref(), Some(text));
+        assert_eq!(error.line(), Some(text));
+        assert_eq!(duplicate_keys.into_inner()["$.id"], ["1"]);
+    }
+}
@@ -0,0 +1,818 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hasher;
+use std::io;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json_path::JsonPath;
+use siphasher::sip::SipHasher13;
+
+use super::location::RecordLocation;
+use super::stats::{LineIdLimits, LineIds};
+use crate::json::paths::maps::MapDetector;
+use crate::json::paths::{PathSegment, ValuePath, ValuePaths, WalkOptions};
+use crate::json::{IndexMap, Value};
+
+pub mod hyperloglog;
+
+pub use self::hyperloglog::HyperLogLog;
+
+/// Default number of distinct hashes counted exactly by the [`Distinct`]s of a `--duplicates`
+/// or `--candidate-keys` count between them, from `--max-exact-hashes`
+pub const DEFAULT_MAX_EXACT_HASHES: usize = 1_000_000;
+
+/// Algorithm of the hashes of the records and values, saved along with them so that only
+/// hashes made alike are merged. Fixed, unlike that of `std`'s `DefaultHasher`, for stats
+/// saved by one build to be merged by another
+pub const HASH_ALGORITHM: &str = "siphash-1-3";
+
+/// Share of the values of a path that may repeat by the estimates of a [`HyperLogLog`] for
+/// the path to still be a candidate key, allowing for the error of the estimates
+const ESTIMATE_TOLERANCE: f64 = 0.02;
+
+/// Count of the distinct hashes added, exact while there is room left in a budget of hashes
+/// shared with other counts, and estimated beyond by a [`HyperLogLog`] sketch, so that the
+/// memory of the counts stays bounded. Saved by `--save-stats` as hex, the sorted hashes or
+/// the registers of the sketch, for the counts of several inputs to be merged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Distinct {
+    Exact(HashSet<u64>),
+    Estimated(HyperLogLog),
+}
+
+impl Default for Distinct {
+    fn default() -> Self {
+        Self::Exact(HashSet::new())
+    }
+}
+
+impl Distinct {
+    /// Adds the `hash`, a new one taking one of the hashes left in the exact `budget`. Once the
+    /// budget is spent, switches to estimating, handing the hashes it held back to the budget.
+    /// Tells whether the hash was added before while still counting exactly
+    pub fn insert(&mut self, hash: u64, budget: &mut usize) -> Option<bool> {
+        match self {
+            Self::Exact(hashes) if hashes.contains(&hash) => Some(true),
+            Self::Exact(hashes) if *budget > 0 => {
+                hashes.insert(hash);
+                *budget -= 1;
+                Some(false)
+            }
+            Self::Exact(_) => {
+                self.estimate(budget);
+                self.insert(hash, budget);
+                Some(false)
+            }
+            Self::Estimated(sketch) => {
+                sketch.insert(hash);
+                None
+            }
+        }
+    }
+
+    /// Switches to estimating, handing the hashes held back to the `budget`
+    fn estimate(&mut self, budget: &mut usize) {
+        if let Self::Exact(hashes) = self {
+            *budget += hashes.len();
+            let mut sketch = HyperLogLog::default();
+            hashes.iter().for_each(|&hash| sketch.insert(hash));
+            *self = Self::Estimated(sketch);
+        }
+    }
+
+    /// Adds the hashes of `rhs`, as [`Distinct::insert`] would each of them
+    pub fn merge(&mut self, rhs: Self, budget: &mut usize) {
+        match rhs {
+            Self::Exact(hashes) => hashes.into_iter().for_each(|hash| {
+                self.insert(hash, budget);
+            }),
+            Self::Estimated(sketch) => {
+                self.estimate(budget);
+                if let Self::Estimated(merged) = self {
+                    merged.merge(&sketch);
+                }
+            }
+        }
+    }
+
+    /// Number of hashes held to count exactly, taken from the budget
+    fn exact_len(&self) -> usize {
+        match self {
+            Self::Exact(hashes) => hashes.len(),
+            Self::Estimated(_) => 0,
+        }
+    }
+
+    /// Number of distinct hashes added, estimated once there were too many to count exactly
+    pub fn count(&self) -> usize {
+        match self {
+            Self::Exact(hashes) => hashes.len(),
+            Self::Estimated(sketch) => sketch.estimate(),
+        }
+    }
+
+    pub fn is_estimated(&self) -> bool {
+        matches!(self, Self::Estimated(_))
+    }
+}
+
+/// A [`Distinct`] as saved, in hex
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SavedDistinct {
+    Exact(String),
+    Estimated(String),
+}
+
+impl Serialize for Distinct {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let saved = match self {
+            Self::Exact(hashes) => {
+                let mut hashes: Vec<_> = hashes.iter().collect();
+                hashes.sort_unstable();
+                SavedDistinct::Exact(hashes.iter().map(|hash| format!("{hash:016x}")).collect())
+            }
+            Self::Estimated(sketch) => SavedDistinct::Estimated(
+                sketch
+                    .registers()
+                    .iter()
+                    .map(|rank| format!("{rank:02x}"))
+                    .collect(),
+            ),
+        };
+        saved.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Distinct {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let invalid = || serde::de::Error::custom("invalid saved hashes");
+        match SavedDistinct::deserialize(deserializer)? {
+            SavedDistinct::Exact(hex) => from_hex(&hex, u64::from_str_radix)
+                .map(Self::Exact)
+                .ok_or_else(invalid),
+            SavedDistinct::Estimated(hex) => from_hex(&hex, u8::from_str_radix)
+                .and_then(HyperLogLog::from_registers)
+                .map(Self::Estimated)
+                .ok_or_else(invalid),
+        }
+    }
+}
+
+/// Numbers of the width of `T` read from their fixed width `hex`
+fn from_hex<T, C: FromIterator<T>>(
+    hex: &str,
+    parse: fn(&str, u32) -> Result<T, std::num::ParseIntError>,
+) -> Option<C> {
+    let width = 2 * std::mem::size_of::<T>();
+    if !hex.is_ascii() || hex.len() % width != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(width)
+        .map(|start| parse(&hex[start..start + width], 16).ok())
+        .collect()
+}
+
+/// Hash of a JSON value, alike for equal values whatever the order of their keys
+pub fn hash_value(value: &Value) -> u64 {
+    let mut hasher = hasher();
+    write_value(&mut hasher, value);
+    hasher.finish()
+}
+
+/// Hasher of the [`HASH_ALGORITHM`], with fixed keys so that the hashes are alike from one
+/// run to the next
+fn hasher() -> SipHasher13 {
+    SipHasher13::new_with_keys(0, 0)
+}
+
+/// Feeds the `value` to the `hasher` as compact JSON, the keys of objects being sorted
+fn write_value(hasher: &mut SipHasher13, value: &Value) {
+    serde_json::to_writer(HashWriter(hasher), value).expect("hashing does not fail");
+}
+
+/// Writer feeding what is written to a hasher, to hash JSON without writing it out
+struct HashWriter<'a>(&'a mut SipHasher13);
+
+impl io::Write for HashWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Records of an input the same as an earlier record, from `--duplicates`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateRecords {
+    /// `--duplicates-on` paths of the values the records were compared on, the whole records
+    /// when there are none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    /// Number of records compared
+    pub records: usize,
+    /// Number of records the same as an earlier one
+    pub duplicates: usize,
+    /// Whether there were too many distinct records to count them exactly, in which case the
+    /// duplicates are no longer sampled
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub estimated: bool,
+    /// Ids of the records the same as an earlier one
+    pub lines: LineIds,
+    /// `--max-exact-hashes` of the count, kept along with its hashes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_exact_hashes: Option<usize>,
+    /// [`HASH_ALGORITHM`] of the hashes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_algorithm: Option<String>,
+    /// The distinct records, kept to merge the count with that of other inputs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hashes: Option<Distinct>,
+}
+
+impl DuplicateRecords {
+    pub fn with_file_path(self, file_path: &str) -> Self {
+        Self {
+            lines: self.lines.with_file_path(file_path),
+            ..self
+        }
+    }
+
+    /// Adds the counts and samples of `rhs`, with the sampled ids located in `file_path`.
+    /// The records repeated across the inputs are counted by the union of their hashes, as for
+    /// one input, but only sampled within each input. Without the hashes of both made alike,
+    /// as saved before they were or by another algorithm, the counts are added up
+    pub fn merge(&mut self, rhs: Self, file_path: &str) {
+        if self.paths.is_empty() {
+            self.paths = rhs.paths;
+        }
+        self.records += rhs.records;
+        self.lines.merge(rhs.lines, file_path);
+        let max_exact = self
+            .max_exact_hashes
+            .zip(rhs.max_exact_hashes)
+            .filter(|_| same_hashes(&self.hash_algorithm, &rhs.hash_algorithm));
+        match (&mut self.hashes, rhs.hashes, max_exact) {
+            (Some(distinct), Some(hashes), Some((lhs_max, rhs_max))) => {
+                let max_exact = lhs_max.min(rhs_max);
+                let mut budget = max_exact.saturating_sub(distinct.exact_len());
+                distinct.merge(hashes, &mut budget);
+                self.max_exact_hashes = Some(max_exact);
+                self.duplicates = self.records.saturating_sub(distinct.count());
+                self.estimated = distinct.is_estimated();
+            }
+            _ => {
+                self.duplicates += rhs.duplicates;
+                self.estimated |= rhs.estimated;
+                self.forget_hashes();
+            }
+        }
+    }
+
+    /// Drops the hashes kept to merge the count, which are only saved by `--save-stats`
+    pub fn forget_hashes(&mut self) {
+        self.max_exact_hashes = None;
+        self.hash_algorithm = None;
+        self.hashes = None;
+    }
+}
+
+/// Comparison of the records of an input with those before them, for `--duplicates`.
+/// Shared by the threads parsing the lines, in which case the records may be compared out of
+/// order and the line each hash was first seen at is kept while counting exactly, so that
+/// the later of two same records is the one reported
+#[derive(Debug)]
+pub struct DuplicateRecordCounter {
+    /// `--duplicates-on` paths of the values compared, the whole records when empty
+    paths: Vec<JsonPath>,
+    max_exact: usize,
+    seen: Mutex<SeenRecords>,
+}
+
+/// The records compared so far by a [`DuplicateRecordCounter`]
+#[derive(Debug)]
+struct SeenRecords {
+    distinct: Distinct,
+    /// Hashes left to count the records exactly
+    budget: usize,
+    /// Line of the earliest record of each hash, when the records are compared out of order
+    firsts: Option<HashMap<u64, RecordLocation>>,
+    duplicates: DuplicateRecords,
+}
+
+impl DuplicateRecordCounter {
+    /// Compares on the values at `paths`, counting up to `max_exact` records exactly, the
+    /// records being compared `in_order` of their lines or not
+    pub fn new(
+        paths: Vec<JsonPath>,
+        max_exact: usize,
+        limits: LineIdLimits,
+        in_order: bool,
+    ) -> Self {
+        let duplicates = DuplicateRecords {
+            paths: paths.iter().map(ToString::to_string).collect(),
+            lines: LineIds::new(limits),
+            ..Default::default()
+        };
+        Self {
+            paths,
+            max_exact,
+            seen: Mutex::new(SeenRecords {
+                distinct: Distinct::default(),
+                budget: max_exact,
+                firsts: (!in_order).then(HashMap::new),
+                duplicates,
+            }),
+        }
+    }
+
+    /// Compares the record `json` of the line `id` with those before it
+    pub fn add(&self, id: &RecordLocation, json: &Value) {
+        let hash = self.hash(json);
+        let mut seen = self.seen.lock().unwrap();
+        let SeenRecords {
+            distinct,
+            budget,
+            firsts,
+            duplicates,
+        } = &mut *seen;
+        duplicates.records += 1;
+        match distinct.insert(hash, budget) {
+            Some(true) => {
+                let later = match firsts.as_mut().and_then(|firsts| firsts.get_mut(&hash)) {
+                    Some(first) if id < first => std::mem::replace(first, id.clone()),
+                    _ => id.clone(),
+                };
+                duplicates.lines.push_unordered(&later);
+            }
+            Some(false) if distinct.is_estimated() => *firsts = None,
+            Some(false) => {
+                if let Some(firsts) = firsts {
+                    firsts.insert(hash, id.clone());
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Hash of what the record `json` is compared on
+    fn hash(&self, json: &Value) -> u64 {
+        if self.paths.is_empty() {
+            return hash_value(json);
+        }
+        let mut hasher = hasher();
+        for path in &self.paths {
+            let nodes = path.query(json);
+            hasher.write_u64(nodes.len() as u64);
+            nodes.iter().for_each(|node| write_value(&mut hasher, node));
+        }
+        hasher.finish()
+    }
+
+    /// The duplicates found, their ids sorted as the records may have been compared in
+    /// parallel
+    pub fn into_inner(self) -> DuplicateRecords {
+        let SeenRecords {
+            distinct,
+            mut duplicates,
+            ..
+        } = self.seen.into_inner().unwrap();
+        duplicates.duplicates = duplicates.records.saturating_sub(distinct.count());
+        duplicates.estimated = distinct.is_estimated();
+        duplicates.lines.sort();
+        duplicates.max_exact_hashes = Some(self.max_exact);
+        duplicates.hash_algorithm = Some(HASH_ALGORITHM.to_string());
+        duplicates.hashes = Some(distinct);
+        duplicates
+    }
+}
+
+/// Whether hashes of the `lhs` and `rhs` algorithms can be merged, made by the same known one
+fn same_hashes(lhs: &Option<String>, rhs: &Option<String>) -> bool {
+    lhs.is_some() && lhs == rhs
+}
+
+/// How many distinct values each scalar path of the records of an input has, from
+/// `--candidate-keys`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CandidateKeys {
+    /// Number of records walked
+    pub records: usize,
+    /// The paths with a distinct value in every record, fit to be primary keys
+    pub candidates: Vec<String>,
+    /// Number of records with each path and of distinct values at it
+    pub paths: IndexMap<String, PathUniqueness>,
+    /// `--max-exact-hashes` of the counts, kept along with the hashes of the paths
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_exact_hashes: Option<usize>,
+    /// [`HASH_ALGORITHM`] of the hashes of the paths
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_algorithm: Option<String>,
+}
+
+/// Number of records with a path and of distinct values at it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathUniqueness {
+    pub present: usize,
+    pub distinct: usize,
+    /// Whether there were too many distinct values to count them exactly
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub estimated: bool,
+    /// The distinct values, kept to merge the count with that of other inputs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hashes: Option<Distinct>,
+}
+
+impl PathUniqueness {
+    /// Whether the path has a distinct value in each of the `records`, within the error of
+    /// the estimates when estimated
+    pub fn is_unique_in(&self, records: usize) -> bool {
+        if records == 0 || self.present != records {
+            return false;
+        }
+        if self.estimated {
+            self.distinct as f64 >= records as f64 * (1.0 - ESTIMATE_TOLERANCE)
+        } else {
+            self.distinct == records
+        }
+    }
+}
+
+impl CandidateKeys {
+    fn find_candidates(&mut self) {
+        self.candidates = self
+            .paths
+            .iter()
+            .filter(|(_, uniqueness)| uniqueness.is_unique_in(self.records))
+            .map(|(path, _)| path.to_owned())
+            .collect();
+    }
+
+    /// Adds the counts of `rhs`. The values repeated across the inputs are counted by the
+    /// union of their hashes, as for one input. Without the hashes of both made alike, as
+    /// saved before they were or by another algorithm, the counts are added up and the
+    /// candidates are those unique within each of the inputs
+    pub fn merge(&mut self, rhs: Self) {
+        self.records += rhs.records;
+        let max_exact = self
+            .max_exact_hashes
+            .zip(rhs.max_exact_hashes)
+            .filter(|_| same_hashes(&self.hash_algorithm, &rhs.hash_algorithm))
+            .map(|(lhs_max, rhs_max)| lhs_max.min(rhs_max));
+        if max_exact.is_none() {
+            self.forget_hashes();
+        }
+        let mut budget = max_exact.map(|max_exact| {
+            let exact: usize = self
+                .paths
+                .values()
+                .filter_map(|uniqueness| uniqueness.hashes.as_ref())
+                .map(Distinct::exact_len)
+                .sum();
+            max_exact.saturating_sub(exact)
+        });
+        for (path, uniqueness) in rhs.paths {
+            let merged = self.paths.entry(path).or_insert_with(|| PathUniqueness {
+                hashes: budget.map(|_| Distinct::default()),
+                ..Default::default()
+            });
+            merged.present += uniqueness.present;
+            match (&mut merged.hashes, uniqueness.hashes, &mut budget) {
+                (Some(distinct), Some(hashes), Some(budget)) => {
+                    distinct.merge(hashes, budget);
+                    merged.distinct = distinct.count();
+                    merged.estimated = distinct.is_estimated();
+                }
+                _ => {
+                    merged.distinct += uniqueness.distinct;
+                    merged.estimated |= uniqueness.estimated;
+                    merged.hashes = None;
+                }
+            }
+        }
+        self.max_exact_hashes = max_exact;
+        self.paths.sort_unstable_keys();
+        self.find_candidates();
+    }
+
+    /// Drops the hashes kept to merge the counts, which are only saved by `--save-stats`
+    pub fn forget_hashes(&mut self) {
+        self.max_exact_hashes = None;
+        self.hash_algorithm = None;
+        for uniqueness in self.paths.values_mut() {
+            uniqueness.hashes = None;
+        }
+    }
+}
+
+/// Count of the distinct values of each scalar path of the records of an input, for
+/// `--candidate-keys`. Shared by the threads parsing the lines
+#[derive(Debug)]
+pub struct CandidateKeyCounter {
+    max_exact: usize,
+    counts: Mutex<PathCounts>,
+}
+
+/// Number of records walked, and of records with each path along with its distinct values
+#[derive(Debug, Default)]
+struct PathCounts {
+    records: usize,
+    paths: IndexMap<String, (usize, Distinct)>,
+    /// Hashes left to count the values exactly, shared by all of the paths
+    budget: usize,
+}
+
+impl CandidateKeyCounter {
+    /// Counts the distinct values of all the paths exactly up to `max_exact` hashes between
+    /// them, estimating those of the paths out of room beyond
+    pub fn new(max_exact: usize) -> Self {
+        Self {
+            max_exact,
+            counts: Mutex::new(PathCounts {
+                budget: max_exact,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Counts the values of the scalar paths of the record `json`, walked as set by `options`
+    pub fn add(&self, json: &Value, options: &WalkOptions) {
+        let values: Vec<_> = json
+            .value_paths_with(options)
+            .into_iter()
+            .filter(is_record_value)
+            .map(|value_path| (value_path.jsonpath(), hash_value(value_path.value)))
+            .collect();
+        let mut counts = self.counts.lock().unwrap();
+        let PathCounts {
+            records,
+            paths,
+            budget,
+        } = &mut *counts;
+        *records += 1;
+        for (path, hash) in values {
+            let (present, distinct) = paths.entry(path).or_default();
+            *present += 1;
+            distinct.insert(hash, budget);
+        }
+    }
+
+    /// The counts by path, sorted as the records may have been walked in parallel. The paths
+    /// into objects found to be `maps` once all the records were walked are left out, as
+    /// their keys are data
+    pub fn into_inner(self, maps: &MapDetector) -> CandidateKeys {
+        let PathCounts {
+            records, mut paths, ..
+        } = self.counts.into_inner().unwrap();
+        paths.retain(|path, _| maps.collapse(path).is_none());
+        paths.sort_unstable_keys();
+        let mut keys = CandidateKeys {
+            records,
+            candidates: vec![],
+            paths: paths
+                .into_iter()
+                .map(|(path, (present, distinct))| {
+                    let uniqueness = PathUniqueness {
+                        present,
+                        distinct: distinct.count(),
+                        estimated: distinct.is_estimated(),
+                        hashes: Some(distinct),
+                    };
+                    (path, uniqueness)
+                })
+                .collect(),
+            max_exact_hashes: Some(self.max_exact),
+            hash_algorithm: Some(HASH_ALGORITHM.to_string()),
+        };
+        keys.find_candidates();
+        keys
+    }
+}
+
+/// Whether the `value_path` is a scalar value found once per record, at a path of keys.
+/// Values of arrays and maps are left out, as their elements are not a value per record
+fn is_record_value(value_path: &ValuePath) -> bool {
+    !matches!(value_path.value, Value::Object(_) | Value::Array(_))
+        && value_path
+            .path
+            .iter()
+            .all(|segment| matches!(segment, PathSegment::Key(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::paths::PathFilter;
+    use serde_json::json;
+
+    #[test]
+    fn distinct_switches_to_estimates() {
+        let hash = |n: u64| hash_value(&json!(n));
+        let mut budget = 4;
+        let mut other = Distinct::default();
+        assert_eq!(other.insert(hash(0), &mut budget), Some(false));
+        let mut distinct = Distinct::default();
+        assert_eq!(distinct.insert(hash(1), &mut budget), Some(false));
+        assert_eq!(distinct.insert(hash(1), &mut budget), Some(true));
+        assert_eq!(distinct.insert(hash(2), &mut budget), Some(false));
+        assert_eq!(distinct.insert(hash(3), &mut budget), Some(false));
+        assert!(!distinct.is_estimated());
+        assert_eq!(budget, 0);
+        assert_eq!(distinct.insert(hash(4), &mut budget), Some(false));
+        assert!(distinct.is_estimated());
+        assert_eq!(distinct.insert(hash(1), &mut budget), None);
+        assert_eq!(distinct.count(), 4);
+        // The hashes of the estimated count are left for the others
+        assert_eq!(budget, 3);
+        assert_eq!(other.insert(hash(5), &mut budget), Some(false));
+        assert!(!other.is_estimated());
+    }
+
+    #[test]
+    fn hash_values_whatever_key_order() {
+        let lhs: Value = serde_json::from_str(r#"{"a": 1, "b": [1, {"c": 2, "d": 3}]}"#).unwrap();
+        let rhs: Value = serde_json::from_str(r#"{"b": [1, {"d": 3, "c": 2}], "a": 1}"#).unwrap();
+        assert_eq!(hash_value(&lhs), hash_value(&rhs));
+        assert_ne!(hash_value(&json!([1, 2])), hash_value(&json!([2, 1])));
+        assert_ne!(hash_value(&json!("1")), hash_value(&json!(1)));
+        // The hashes are saved, so must not change from one build to the next
+        assert_eq!(hash_value(&lhs), 13928259449878623438);
+    }
+
+    #[test]
+    fn count_duplicate_records() {
+        let records = [
+            json!({"id": 1, "at": 10}),
+            json!({"id": 2, "at": 10}),
+            json!({"at": 10, "id": 1}),
+            json!({"id": 1, "at": 11}),
+        ];
+        let count = |paths: &[&str]| {
+            let paths = paths.iter().map(|p| JsonPath::parse(p).unwrap()).collect();
+            let counter = DuplicateRecordCounter::new(paths, 10, LineIdLimits::default(), true);
+            for (i, record) in records.iter().enumerate() {
+                counter.add(&RecordLocation::from(i + 1), record);
+            }
+            counter.into_inner()
+        };
+
+        let duplicates = count(&[]);
+        assert_eq!((duplicates.records, duplicates.duplicates), (4, 1));
+        assert_eq!(duplicates.lines, ["3"]);
+        assert!(duplicates.paths.is_empty());
+
+        let duplicates = count(&["$.id"]);
+        assert_eq!(duplicates.duplicates, 2);
+        assert_eq!(duplicates.lines, ["3", "4"]);
+        assert_eq!(duplicates.paths, ["$.id"]);
+
+        let duplicates = count(&["$.at", "$.missing"]);
+        assert_eq!(duplicates.lines, ["2", "3"]);
+
+        // Out of order, the later of the same records is still the one reported
+        let counter = DuplicateRecordCounter::new(vec![], 10, LineIdLimits::default(), false);
+        for i in [3, 4, 1, 2] {
+            counter.add(&RecordLocation::from(i), &records[i - 1]);
+        }
+        assert_eq!(counter.into_inner().lines, ["3"]);
+    }
+
+    #[test]
+    fn merge_duplicate_records() {
+        let count = |records: &[Value]| {
+            let counter = DuplicateRecordCounter::new(vec![], 3, LineIdLimits::default(), true);
+            for (i, record) in records.iter().enumerate() {
+                counter.add(&RecordLocation::from(i + 1), record);
+            }
+            counter.into_inner()
+        };
+        let lhs = count(&[json!(1), json!(2), json!(1)]);
+        let rhs = count(&[json!(2), json!(3)]);
+
+        let mut merged = lhs.clone();
+        merged.merge(rhs.clone(), "b.json");
+        assert_eq!((merged.records, merged.duplicates), (5, 2));
+        assert_eq!(merged.lines, ["3"]);
+        assert!(!merged.estimated);
+
+        // Out of exact hashes, the union is estimated
+        let mut merged = merged.clone();
+        merged.merge(count(&[json!(4)]), "c.json");
+        assert_eq!((merged.records, merged.duplicates), (6, 2));
+        assert!(merged.estimated);
+
+        // The hashes are saved to be merged as well once loaded
+        let saved: DuplicateRecords =
+            serde_json::from_str(&serde_json::to_string(&merged).unwrap()).unwrap();
+        assert_eq!(saved, merged);
+        let saved = serde_json::to_value(&lhs).unwrap();
+        assert_eq!(
+            saved["hashes"]["exact"].as_str().map(str::len),
+            Some(2 * 16)
+        );
+        assert_eq!(
+            serde_json::from_value::<DuplicateRecords>(saved).unwrap(),
+            lhs
+        );
+
+        let mut merged = lhs.clone();
+        merged.forget_hashes();
+        merged.merge(rhs.clone(), "b.json");
+        assert_eq!((merged.records, merged.duplicates), (5, 1));
+        assert!(merged.hashes.is_none());
+
+        // Nor are hashes of another algorithm merged
+        let mut merged = lhs.clone();
+        merged.hash_algorithm = Some("other".to_string());
+        merged.merge(rhs, "b.json");
+        assert_eq!((merged.records, merged.duplicates), (5, 1));
+        assert!(merged.hash_algorithm.is_none());
+    }
+
+    #[test]
+    fn find_candidate_keys() {
+        let options = WalkOptions::default();
+        let counter = CandidateKeyCounter::new(20);
+        for i in 0..5 {
+            let record = json!({"id": i, "kind": i % 2, "tags": [i], "user": {"id": i}});
+            counter.add(&record, &options);
+        }
+        counter.add(&json!({"id": 5, "kind": 1, "user": {}}), &options);
+        let keys = counter.into_inner(&options.maps);
+        assert_eq!(keys.records, 6);
+        assert_eq!(keys.candidates, ["$.id"]);
+        assert_eq!(
+            keys.paths.keys().collect::<Vec<_>>(),
+            ["$.id", "$.kind", "$.user.id"]
+        );
+        let user_id = &keys.paths["$.user.id"];
+        assert_eq!(
+            (user_id.present, user_id.distinct, user_id.estimated),
+            (5, 5, false)
+        );
+        assert_eq!(keys.paths["$.kind"].distinct, 2);
+
+        // Merged with itself, every value is repeated
+        let mut merged = keys.clone();
+        merged.merge(keys.clone());
+        assert_eq!(merged.records, 12);
+        assert_eq!(merged.paths["$.id"].distinct, 6);
+        assert!(merged.candidates.is_empty());
+
+        // Merged with other values, they are still distinct
+        let counter = CandidateKeyCounter::new(20);
+        counter.add(&json!({"id": 6, "kind": 0, "other": 1}), &options);
+        let mut merged = keys.clone();
+        merged.merge(counter.into_inner(&options.maps));
+        assert_eq!(merged.records, 7);
+        assert_eq!(merged.candidates, ["$.id"]);
+        assert_eq!(
+            merged.paths.keys().collect::<Vec<_>>(),
+            ["$.id", "$.kind", "$.other", "$.user.id"]
+        );
+
+        // Without the hashes, the counts are added up
+        let mut merged = keys.clone();
+        merged.forget_hashes();
+        merged.merge(keys);
+        assert_eq!(merged.paths["$.id"].distinct, 12);
+        assert_eq!(merged.candidates, ["$.id"]);
+
+        // The exact hashes are shared by the paths, the first out of room being estimated
+        let counter = CandidateKeyCounter::new(100);
+        for i in 0..1000 {
+            counter.add(&json!({"id": i, "kind": i % 2}), &options);
+        }
+        let keys = counter.into_inner(&options.maps);
+        assert!(keys.paths["$.id"].estimated);
+        assert!(!keys.paths["$.kind"].estimated);
+        assert_eq!(keys.candidates, ["$.id"]);
+    }
+
+    #[test]
+    fn candidate_keys_walk_options() {
+        let options = WalkOptions {
+            filter: PathFilter::new(vec![], vec!["$.secret".parse().unwrap()]),
+            max_depth: Some(2),
+            maps: MapDetector::new(vec!["$.by_id".parse().unwrap()], None),
+            ..Default::default()
+        };
+        let counter = CandidateKeyCounter::new(100);
+        for i in 0..3 {
+            let record = json!({
+                "id": i,
+                "secret": i,
+                "by_id": {format!("k{i}"): i},
+                "a": {"b": {"c": i}, "d": i},
+            });
+            counter.add(&record, &options);
+        }
+        let keys = counter.into_inner(&options.maps);
+        assert_eq!(keys.paths.keys().collect::<Vec<_>>(), ["$.a.d", "$.id"]);
+        assert_eq!(keys.candidates, ["$.a.d", "$.id"]);
+    }
+}
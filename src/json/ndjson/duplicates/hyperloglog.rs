@@ -0,0 +1,102 @@
+/// Bits of the hashes picking their register: 2^14 registers of a byte, for a standard error
+/// of about 0.8%
+const PRECISION: u32 = 14;
+const REGISTERS: usize = 1 << PRECISION;
+
+/// HyperLogLog sketch estimating the number of distinct hashes added to it, within a fixed
+/// 16 KiB however many there are
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; REGISTERS],
+        }
+    }
+}
+
+impl HyperLogLog {
+    /// Adds a 64 bit `hash`, expected to be uniformly distributed
+    pub fn insert(&mut self, hash: u64) {
+        let register = (hash >> (64 - PRECISION)) as usize;
+        // The marker bit bounds the rank when the remaining bits are all zeros
+        let rest = (hash << PRECISION) | (1 << (PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[register] = self.registers[register].max(rank);
+    }
+
+    /// Adds the hashes added to `rhs`, as if they had been added to this sketch
+    pub fn merge(&mut self, rhs: &Self) {
+        for (register, &rank) in self.registers.iter_mut().zip(&rhs.registers) {
+            *register = (*register).max(rank);
+        }
+    }
+
+    /// The ranks held by the registers, one byte each
+    pub fn registers(&self) -> &[u8] {
+        &self.registers
+    }
+
+    /// Sketch of the `registers` of another, unless there are not as many as a sketch holds
+    pub fn from_registers(registers: Vec<u8>) -> Option<Self> {
+        (registers.len() == REGISTERS).then_some(Self { registers })
+    }
+
+    /// Estimated number of distinct hashes added, counted by linear counting while few
+    pub fn estimate(&self) -> usize {
+        let m = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-i32::from(rank)))
+            .sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        let estimate = if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::{BuildHasher, RandomState};
+
+    #[test]
+    fn estimates_distinct_hashes() {
+        let state = RandomState::new();
+        let mut sketch = HyperLogLog::default();
+        assert_eq!(sketch.estimate(), 0);
+
+        for n in 0..200_000u64 {
+            // Each value twice, which should not be counted twice
+            sketch.insert(state.hash_one(n / 2));
+        }
+        let estimate = sketch.estimate() as f64;
+        assert!(
+            (estimate - 100_000.0).abs() < 100_000.0 * 0.05,
+            "{estimate}"
+        );
+
+        let mut sketch = HyperLogLog::default();
+        (0..100u64).for_each(|n| sketch.insert(state.hash_one(n)));
+        assert!(sketch.estimate().abs_diff(100) <= 2);
+
+        // Merged sketches count the hashes of both once
+        let mut rhs = HyperLogLog::default();
+        (50..150u64).for_each(|n| rhs.insert(state.hash_one(n)));
+        sketch.merge(&rhs);
+        assert!(sketch.estimate().abs_diff(150) <= 3);
+        let saved = HyperLogLog::from_registers(sketch.registers().to_vec()).unwrap();
+        assert_eq!(saved, sketch);
+        assert!(HyperLogLog::from_registers(vec![0; 10]).is_none());
+    }
+}
@@ -1,14 +1,82 @@
 use thiserror::Error;
 
 pub mod collection;
+pub mod diagnostic;
+
+pub use self::diagnostic::{ParseDiagnostic, ParseErrorKind};
 
 /// Wrapper around the various errors we can encounter while processing the data
 #[derive(Error, Debug)]
 pub enum NDJSONError {
     #[error("Failed to read input data")]
     IOError(#[from] std::io::Error),
-    #[error("Line failed to parse as valid JSON")]
-    JSONParsingError(#[from] serde_json::Error),
+    #[error("Line failed to parse as valid JSON: {diagnostic}")]
+    JSONParsingError {
+        diagnostic: Box<ParseDiagnostic>,
+        /// The line that failed to parse, when known
+        line: Option<String>,
+    },
     #[error("Line returned empty for the given query")]
-    EmptyQuery,
+    EmptyQuery {
+        /// The record as compact JSON, when kept for `--empty-lines-out`
+        line: Option<String>,
+    },
+    #[error("Line filtered out by the --where predicates")]
+    FilteredOut,
+}
+
+impl NDJSONError {
+    /// The `error` from parsing the line `text`, diagnosed with a snippet of the text, which
+    /// is kept with the error
+    pub fn invalid_json(error: &serde_json::Error, text: String) -> Self {
+        Self::JSONParsingError {
+            diagnostic: Box::new(ParseDiagnostic::new(error, &text)),
+            line: Some(text),
+        }
+    }
+
+    /// The record `text` giving the key at `path` more than once, the first of them ending at
+    /// byte `offset`, which is kept with the error
+    pub fn duplicate_key(path: &str, offset: usize, text: String) -> Self {
+        Self::JSONParsingError {
+            diagnostic: Box::new(ParseDiagnostic::duplicate_key(path, offset, &text)),
+            line: Some(text),
+        }
+    }
+
+    /// Name of the kind of error, e.g. `invalid_json`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::IOError(_) => "io_error",
+            Self::JSONParsingError { .. } => "invalid_json",
+            Self::EmptyQuery { .. } => "empty_query",
+            Self::FilteredOut => "filtered_out",
+        }
+    }
+
+    /// The line or record the error is about, when known
+    pub fn line(&self) -> Option<&str> {
+        match self {
+            Self::JSONParsingError { line, .. } | Self::EmptyQuery { line } => line.as_deref(),
+            Self::IOError(_) | Self::FilteredOut => None,
+        }
+    }
+
+    /// Diagnosis of the lines that could not be read or parsed
+    pub fn parse_diagnostic(&self) -> Option<ParseDiagnostic> {
+        match self {
+            Self::IOError(error) => Some(ParseDiagnostic::unreadable(error)),
+            Self::JSONParsingError { diagnostic, .. } => Some(diagnostic.as_ref().clone()),
+            Self::EmptyQuery { .. } | Self::FilteredOut => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for NDJSONError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::JSONParsingError {
+            diagnostic: Box::new(ParseDiagnostic::from(&error)),
+            line: None,
+        }
+    }
 }
@@ -1,24 +1,26 @@
-use std::cell::RefCell;
 use std::error::Error;
+use std::fmt;
 use std::fmt::Display;
+use std::io::{self, Write};
 use std::iter::Enumerate;
-use std::rc::Rc;
 use std::sync::{Arc, Mutex};
-use std::{fmt, io};
 
 use owo_colors::{OwoColorize, Stream};
 
 use super::NDJSONError;
+use crate::json::IndexMap;
+use crate::json::ndjson::location::RecordLocation;
+use crate::json::ndjson::quarantine::Quarantine;
 
 /// Holds linked position information for errors encountered while processing
 #[derive(Debug)]
 pub struct IndexedNDJSONError {
-    pub location: String,
+    pub location: RecordLocation,
     pub error: NDJSONError,
 }
 
 impl IndexedNDJSONError {
-    pub(crate) fn new(location: String, error: NDJSONError) -> Self {
+    pub(crate) fn new(location: RecordLocation, error: NDJSONError) -> Self {
         Self { location, error }
     }
 }
@@ -33,36 +35,144 @@ impl fmt::Display for IndexedNDJSONError {
     }
 }
 
-/// Threadsafe storage for errors encountered by parallel processing.
-/// Counterpart to [`Errors`]
-#[derive(Debug)]
-pub struct ErrorsPar<E> {
+/// Destination of the errors encountered while processing, shared by the iterator adapters
+/// such as [`ErrFiltered`] and the threads of the parallel processing.
+///
+/// Implemented by [`Errors`] to keep them all in memory, [`BoundedErrors`] to keep the first
+/// few, [`WriterErrors`] to stream them to a writer, [`IgnoreErrors`] to drop them and
+/// [`Quarantine`] to write their lines to the side files
+pub trait ErrorSink: Send + Sync {
+    /// Takes an error encountered while processing
+    fn record(&self, error: IndexedNDJSONError);
+
+    /// Takes an error of a record selected by the `--jsonpath` query named `query`, the same as
+    /// any other error unless the sink keeps track of the queries
+    fn record_query(&self, _query: Option<&str>, error: IndexedNDJSONError) {
+        self.record(error)
+    }
+
+    /// Passes each of the errors kept by the sink to `f`, for adding them to the stats once
+    /// processed. Sinks passing the errors on keep none
+    fn replay(&self, _f: &mut dyn FnMut(&IndexedNDJSONError)) {}
+
+    /// Prints the errors kept by the sink to stderr
+    fn eprint(&self) {
+        let mut errors = String::new();
+        self.replay(&mut |error| errors.push_str(&format!("{error}\n")));
+        if !errors.is_empty() {
+            let stream = Stream::Stderr;
+            eprintln!("{}", errors.if_supports_color(stream, |text| text.red()));
+        }
+    }
+}
+
+impl<S: ErrorSink + ?Sized> ErrorSink for &S {
+    fn record(&self, error: IndexedNDJSONError) {
+        (**self).record(error)
+    }
+
+    fn record_query(&self, query: Option<&str>, error: IndexedNDJSONError) {
+        (**self).record_query(query, error)
+    }
+
+    fn replay(&self, f: &mut dyn FnMut(&IndexedNDJSONError)) {
+        (**self).replay(f)
+    }
+}
+
+impl<S: ErrorSink + ?Sized> ErrorSink for Arc<S> {
+    fn record(&self, error: IndexedNDJSONError) {
+        (**self).record(error)
+    }
+
+    fn record_query(&self, query: Option<&str>, error: IndexedNDJSONError) {
+        (**self).record_query(query, error)
+    }
+
+    fn replay(&self, f: &mut dyn FnMut(&IndexedNDJSONError)) {
+        (**self).replay(f)
+    }
+}
+
+/// Storage for all the errors encountered by processing, single or multi-threaded
+pub struct Errors<E> {
     pub container: Arc<Mutex<Vec<E>>>,
+    quarantine: Option<Arc<Quarantine>>,
 }
 
-impl<E> ErrorsPar<E> {
+/// Former name of [`Errors`], which now serves parallel processing too
+#[deprecated(note = "Use `Errors`, which is threadsafe")]
+pub type ErrorsPar<E> = Errors<E>;
+
+impl<E> Errors<E> {
     pub fn new(container: Arc<Mutex<Vec<E>>>) -> Self {
-        Self { container }
+        Self {
+            container,
+            quarantine: None,
+        }
+    }
+
+    /// Also writes the lines of the errors recorded to the `quarantine`,
+    /// see [`Errors::record`]
+    pub fn with_quarantine(self, quarantine: Arc<Quarantine>) -> Self {
+        Self {
+            quarantine: Some(quarantine),
+            ..self
+        }
     }
 
     pub fn new_ref(&self) -> Self {
         Self {
             container: Arc::clone(&self.container),
+            quarantine: self.quarantine.clone(),
         }
     }
 
+    /// Stores the `value`, bypassing any quarantine
     pub fn push(&self, value: E) {
         self.container.lock().expect("not poisoned").push(value)
     }
 }
 
-impl<E> Default for ErrorsPar<E> {
+impl Errors<IndexedNDJSONError> {
+    /// Stores the `error`, writing its line to the quarantine if any
+    pub fn record(&self, error: IndexedNDJSONError) {
+        ErrorSink::record_query(self, None, error)
+    }
+}
+
+impl ErrorSink for Errors<IndexedNDJSONError> {
+    fn record(&self, error: IndexedNDJSONError) {
+        Errors::record(self, error)
+    }
+
+    fn record_query(&self, query: Option<&str>, error: IndexedNDJSONError) {
+        if let Some(quarantine) = &self.quarantine {
+            quarantine.record_error(None, query, &error);
+        }
+        self.push(error)
+    }
+
+    fn replay(&self, f: &mut dyn FnMut(&IndexedNDJSONError)) {
+        self.container.lock().unwrap().iter().for_each(f)
+    }
+}
+
+impl<E: fmt::Debug> fmt::Debug for Errors<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Errors")
+            .field("container", &self.container)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<E> Default for Errors<E> {
     fn default() -> Self {
         Self::new(Arc::new(Mutex::new(vec![])))
     }
 }
 
-impl<E: Display> fmt::Display for ErrorsPar<E> {
+impl<E: Display> fmt::Display for Errors<E> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for i in self.container.lock().unwrap().as_slice() {
             writeln!(f, "{i}")?;
@@ -71,82 +181,124 @@ impl<E: Display> fmt::Display for ErrorsPar<E> {
     }
 }
 
-// TODO: Consider blend with ErrorsContainer Trait?
-pub trait NDJSONProcessingErrors {
-    fn eprint(&self) {}
+/// Storage for the first `max` errors encountered by processing, only counting the others
+/// by kind so that memory stays bounded however corrupt the data
+#[derive(Debug)]
+pub struct BoundedErrors {
+    max: usize,
+    /// Behind a single lock so that the errors kept never outnumber those counted
+    recorded: Mutex<RecordedErrors>,
 }
 
-impl<E: Display> NDJSONProcessingErrors for ErrorsPar<E> {
-    fn eprint(&self) {
-        let stream = Stream::Stdout;
-        if !self.container.lock().unwrap().is_empty() {
-            eprintln!("{}", self.if_supports_color(stream, |text| text.red()));
+#[derive(Debug, Default)]
+struct RecordedErrors {
+    kept: Vec<IndexedNDJSONError>,
+    counts: IndexMap<&'static str, usize>,
+}
+
+impl BoundedErrors {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            recorded: Mutex::default(),
         }
     }
-}
 
-// TODO: Create ErrorContainer Trait?
-/// Storage for errors encountered by processing
-/// Counterpart to [`ErrorsPar`]
-#[derive(Debug)]
-pub struct Errors<E> {
-    pub container: Rc<RefCell<Vec<E>>>,
-}
+    /// Number of errors recorded of each kind, see [`NDJSONError::name`], kept or not
+    pub fn counts(&self) -> IndexMap<&'static str, usize> {
+        self.recorded.lock().unwrap().counts.clone()
+    }
 
-impl<E> Errors<E> {
-    pub fn new(container: Rc<RefCell<Vec<E>>>) -> Self {
-        Self { container }
+    /// Number of errors recorded beyond those kept
+    pub fn dropped(&self) -> usize {
+        let recorded = self.recorded.lock().unwrap();
+        recorded.counts.values().sum::<usize>() - recorded.kept.len()
     }
+}
 
-    pub fn new_ref(&self) -> Self {
-        Self {
-            container: Rc::clone(&self.container),
+impl ErrorSink for BoundedErrors {
+    fn record(&self, error: IndexedNDJSONError) {
+        let mut recorded = self.recorded.lock().unwrap();
+        *recorded.counts.entry(error.error.name()).or_insert(0) += 1;
+        if recorded.kept.len() < self.max {
+            recorded.kept.push(error);
         }
     }
 
-    pub fn push(&self, value: E) {
-        self.container.borrow_mut().push(value)
+    fn replay(&self, f: &mut dyn FnMut(&IndexedNDJSONError)) {
+        self.recorded.lock().unwrap().kept.iter().for_each(f)
     }
 }
 
-impl<E: Display> NDJSONProcessingErrors for Errors<E> {
-    fn eprint(&self) {
-        let stream = Stream::Stdout;
-        if !self.container.borrow().is_empty() {
-            eprintln!("{}", self.if_supports_color(stream, |text| text.red()));
+/// Writes the errors encountered by processing to a writer as they are recorded, one line
+/// each, keeping none of them.
+///
+/// Writing carries on with the processing should it fail, the first failure being returned
+/// by [`WriterErrors::finish`]
+pub struct WriterErrors<W> {
+    writer: Mutex<(W, Option<io::Error>)>,
+}
+
+impl<W: Write + Send> WriterErrors<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new((writer, None)),
         }
     }
+
+    /// Flushes the writer, returning the first error writing to it if any
+    pub fn finish(&self) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        match writer.1.take() {
+            Some(error) => Err(error),
+            None => writer.0.flush(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap().0
+    }
 }
 
-impl<E> Default for Errors<E> {
-    fn default() -> Self {
-        Self::new(Rc::new(RefCell::new(vec![])))
+impl<W: Write + Send> ErrorSink for WriterErrors<W> {
+    fn record(&self, error: IndexedNDJSONError) {
+        let mut writer = self.writer.lock().unwrap();
+        if writer.1.is_none() {
+            writer.1 = writeln!(writer.0, "{error}").err();
+        }
     }
 }
 
-impl<E: Display> fmt::Display for Errors<E> {
+impl<W> fmt::Debug for WriterErrors<W> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for i in self.container.borrow().as_slice() {
-            writeln!(f, "{i}")?;
-        }
-        Ok(())
+        f.debug_struct("WriterErrors").finish_non_exhaustive()
     }
 }
 
+/// Drops the errors encountered by processing
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IgnoreErrors;
+
+impl ErrorSink for IgnoreErrors {
+    fn record(&self, _error: IndexedNDJSONError) {}
+}
+
 /// Iterator that skips, but keeps track of, `Err`s while processing
-pub struct ErrFiltered<I, E> {
+pub struct ErrFiltered<I, S> {
     iter: I,
-    errors: Errors<E>,
+    errors: S,
 }
 
-impl<U, E, T, I: Iterator<Item = (U, Result<T, W>)>, W> ErrFiltered<I, E> {
-    pub fn new(iter: I, errors: Errors<E>) -> Self {
+impl<U, T, I: Iterator<Item = (U, Result<T, W>)>, W, S: ErrorSink> ErrFiltered<I, S> {
+    pub fn new(iter: I, errors: S) -> Self {
         Self { iter, errors }
     }
 }
 
-impl<U: Display, T, I: Iterator<Item = (U, Result<T, impl Into<NDJSONError>>)>> Iterator
-    for ErrFiltered<I, IndexedNDJSONError>
+impl<U: Into<RecordLocation>, T, W: Into<NDJSONError>, I, S> Iterator for ErrFiltered<I, S>
+where
+    I: Iterator<Item = (U, Result<T, W>)>,
+    S: ErrorSink,
 {
     type Item = (U, T);
     fn next(&mut self) -> Option<Self::Item> {
@@ -157,29 +309,29 @@ impl<U: Display, T, I: Iterator<Item = (U, Result<T, impl Into<NDJSONError>>)>>
                 Err(e) => {
                     let error: NDJSONError = e.into();
                     self.errors
-                        .push(IndexedNDJSONError::new(format!("{id}"), error));
+                        .record(IndexedNDJSONError::new(id.into(), error));
                 }
             }
         }
     }
 }
 
-pub trait IntoErrFiltered<U, E, T, W>: Iterator<Item = (U, Result<T, W>)> + Sized {
-    fn to_err_filtered(self, errors: Errors<E>) -> ErrFiltered<Self, E> {
+pub trait IntoErrFiltered<U, T, W>: Iterator<Item = (U, Result<T, W>)> + Sized {
+    fn to_err_filtered<S: ErrorSink>(self, errors: S) -> ErrFiltered<Self, S> {
         ErrFiltered::new(self, errors)
     }
 }
 
-impl<U, E, T, I: Iterator<Item = (U, Result<T, W>)>, W> IntoErrFiltered<U, E, T, W> for I {}
+impl<U, T, I: Iterator<Item = (U, Result<T, W>)>, W> IntoErrFiltered<U, T, W> for I {}
 
 /// Iterator that enumerates all items and skips, but keeps track of, `Err`s while processing
-pub struct EnumeratedErrFiltered<I, E> {
+pub struct EnumeratedErrFiltered<I, S> {
     iter: Enumerate<I>,
-    errors: Errors<E>,
+    errors: S,
 }
 
-impl<E, T, I: Iterator<Item = Result<T, W>>, W> EnumeratedErrFiltered<I, E> {
-    pub fn new(iter: I, errors: Errors<E>) -> Self {
+impl<T, I: Iterator<Item = Result<T, W>>, W, S: ErrorSink> EnumeratedErrFiltered<I, S> {
+    pub fn new(iter: I, errors: S) -> Self {
         Self {
             iter: iter.enumerate(),
             errors,
@@ -187,9 +339,10 @@ impl<E, T, I: Iterator<Item = Result<T, W>>, W> EnumeratedErrFiltered<I, E> {
     }
 }
 
-impl<T, I> Iterator for EnumeratedErrFiltered<I, IndexedNDJSONError>
+impl<T, I, S> Iterator for EnumeratedErrFiltered<I, S>
 where
     I: Iterator<Item = Result<T, io::Error>>,
+    S: ErrorSink,
 {
     type Item = (usize, T);
     fn next(&mut self) -> Option<Self::Item> {
@@ -199,8 +352,8 @@ where
             match next_item {
                 Ok(item) => break Some((i, item)),
                 Err(e) => {
-                    self.errors.push(IndexedNDJSONError::new(
-                        i.to_string(),
+                    self.errors.record(IndexedNDJSONError::new(
+                        RecordLocation::from(i),
                         NDJSONError::IOError(e),
                     ));
                 }
@@ -209,10 +362,72 @@ where
     }
 }
 
-pub trait IntoEnumeratedErrFiltered<E, T, W>: Iterator<Item = Result<T, W>> + Sized {
-    fn to_enumerated_err_filtered(self, errors: Errors<E>) -> EnumeratedErrFiltered<Self, E> {
+pub trait IntoEnumeratedErrFiltered<T, W>: Iterator<Item = Result<T, W>> + Sized {
+    fn to_enumerated_err_filtered<S: ErrorSink>(self, errors: S) -> EnumeratedErrFiltered<Self, S> {
         EnumeratedErrFiltered::new(self, errors)
     }
 }
 
-impl<E, T, I: Iterator<Item = Result<T, W>>, W> IntoEnumeratedErrFiltered<E, T, W> for I {}
+impl<T, I: Iterator<Item = Result<T, W>>, W> IntoEnumeratedErrFiltered<T, W> for I {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unreadable() -> NDJSONError {
+        NDJSONError::IOError(io::Error::other("unreadable"))
+    }
+
+    fn bad_line(line: usize) -> IndexedNDJSONError {
+        IndexedNDJSONError::new(RecordLocation::from(line), unreadable())
+    }
+
+    fn locations(sink: &dyn ErrorSink) -> Vec<usize> {
+        let mut locations = Vec::new();
+        sink.replay(&mut |error| locations.push(error.location.line));
+        locations
+    }
+
+    #[test]
+    fn error_sinks() {
+        let lines = [Some("a"), None, Some("b"), None, None];
+        let sink_all = |sink: &dyn ErrorSink| {
+            let ok: Vec<&str> = (1..)
+                .zip(lines.map(|line| line.ok_or_else(unreadable)))
+                .to_err_filtered(sink)
+                .map(|(_, line)| line)
+                .collect();
+            assert_eq!(ok, ["a", "b"]);
+        };
+
+        let errors = Errors::default();
+        sink_all(&errors);
+        assert_eq!(locations(&errors), [2, 4, 5]);
+
+        let bounded = BoundedErrors::new(2);
+        for line in 1..=3 {
+            bounded.record(bad_line(line));
+        }
+        bounded.record(IndexedNDJSONError::new(
+            RecordLocation::from(4),
+            NDJSONError::FilteredOut,
+        ));
+        assert_eq!(locations(&bounded), [1, 2]);
+        assert_eq!(
+            bounded.counts(),
+            IndexMap::from([("io_error", 3), ("filtered_out", 1)])
+        );
+        assert_eq!(bounded.dropped(), 2);
+
+        let writer = WriterErrors::new(Vec::new());
+        sink_all(&writer);
+        writer.finish().unwrap();
+        assert!(locations(&writer).is_empty());
+        let written = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(written.lines().count(), 3);
+        assert!(written.starts_with("Line 2: Failed to read input data; unreadable\n"));
+
+        sink_all(&IgnoreErrors);
+        assert!(locations(&IgnoreErrors).is_empty());
+    }
+}
@@ -0,0 +1,275 @@
+use std::{fmt, io};
+
+use serde::{Deserialize, Serialize};
+use serde_json::error::Category;
+
+/// Bytes of the line shown either side of a parse error in its snippet
+const SNIPPET_CONTEXT: usize = 20;
+
+/// Kind of failure of a line to parse as JSON, derived from [`serde_json::Error::classify`]
+/// and the parser's message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseErrorKind {
+    /// The line is blank
+    Blank,
+    /// The line ends before the JSON does, e.g. a record cut short
+    Truncated,
+    /// More data follows a complete JSON, e.g. two records glued on one line
+    TrailingCharacters,
+    /// A comma right before a closing bracket or brace
+    TrailingComma,
+    /// A value is missing or not JSON, e.g. `NaN` or a single quoted string
+    ExpectedValue,
+    /// A `:`, `,` or closing bracket is missing
+    ExpectedDelimiter,
+    /// An object key is not a string
+    KeyMustBeString,
+    /// An invalid `\` or unicode escape in a string
+    InvalidEscape,
+    /// An unescaped control character in a string
+    ControlCharacter,
+    /// A malformed or out of range number
+    InvalidNumber,
+    /// Nested too deeply for the parser
+    RecursionLimit,
+    /// An object has the same key more than once, a bad line with `--duplicate-keys-bad`
+    DuplicateKey,
+    /// The line could not be read, e.g. it is not valid UTF-8
+    Unreadable,
+    Other,
+}
+
+impl ParseErrorKind {
+    /// Classifies the `error` from parsing `text`
+    fn classify(error: &serde_json::Error, text: Option<&str>) -> Self {
+        match error.classify() {
+            Category::Io => Self::Unreadable,
+            Category::Eof if text.is_some_and(|text| text.trim().is_empty()) => Self::Blank,
+            Category::Eof => Self::Truncated,
+            Category::Data => Self::Other,
+            Category::Syntax => {
+                let message = message(error);
+                match message.as_str() {
+                    "trailing characters" => Self::TrailingCharacters,
+                    "trailing comma" => Self::TrailingComma,
+                    "expected value" | "expected ident" => Self::ExpectedValue,
+                    "key must be a string" => Self::KeyMustBeString,
+                    "recursion limit exceeded" => Self::RecursionLimit,
+                    "invalid number" | "number out of range" => Self::InvalidNumber,
+                    m if m.starts_with("expected `") => Self::ExpectedDelimiter,
+                    m if m.starts_with("control character") => Self::ControlCharacter,
+                    m if m.contains("escape") || m.contains("unicode") => Self::InvalidEscape,
+                    _ => Self::Other,
+                }
+            }
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Blank => "blank",
+            Self::Truncated => "truncated",
+            Self::TrailingCharacters => "trailing_characters",
+            Self::TrailingComma => "trailing_comma",
+            Self::ExpectedValue => "expected_value",
+            Self::ExpectedDelimiter => "expected_delimiter",
+            Self::KeyMustBeString => "key_must_be_string",
+            Self::InvalidEscape => "invalid_escape",
+            Self::ControlCharacter => "control_character",
+            Self::InvalidNumber => "invalid_number",
+            Self::RecursionLimit => "recursion_limit",
+            Self::DuplicateKey => "duplicate_key",
+            Self::Unreadable => "unreadable",
+            Self::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Why and where a line failed to parse as JSON
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseDiagnostic {
+    pub kind: ParseErrorKind,
+    /// The parser's description of the error, without its position
+    pub message: String,
+    /// Line of the error within the parsed text, 1 for single line records
+    pub line: usize,
+    /// Column of the error within its line, counting bytes from 1
+    pub column: usize,
+    /// Byte offset of the error within the parsed text
+    pub offset: usize,
+    /// The text around the error, when known and not blank
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+impl ParseDiagnostic {
+    /// Diagnoses the `error` from parsing `text`
+    pub fn new(error: &serde_json::Error, text: &str) -> Self {
+        let offset = byte_offset(text, error.line(), error.column());
+        Self {
+            kind: ParseErrorKind::classify(error, Some(text)),
+            offset,
+            snippet: (!text.trim().is_empty()).then(|| snippet(text, offset)),
+            ..Self::from(error)
+        }
+    }
+
+    /// Diagnoses the key at `path` given more than once in `text`, at byte `offset`
+    pub fn duplicate_key(path: &str, offset: usize, text: &str) -> Self {
+        let before = &text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            kind: ParseErrorKind::DuplicateKey,
+            message: format!("duplicate key at `{path}`"),
+            line: before.matches('\n').count() + 1,
+            column: offset - line_start + 1,
+            offset,
+            snippet: Some(snippet(text, offset)),
+        }
+    }
+
+    /// Diagnoses a line that failed to be read
+    pub fn unreadable(error: &io::Error) -> Self {
+        Self {
+            kind: ParseErrorKind::Unreadable,
+            message: error.to_string(),
+            line: 0,
+            column: 0,
+            offset: 0,
+            snippet: None,
+        }
+    }
+}
+
+/// Diagnoses the `error` without the text that was parsed, so without a snippet
+impl From<&serde_json::Error> for ParseDiagnostic {
+    fn from(error: &serde_json::Error) -> Self {
+        Self {
+            kind: ParseErrorKind::classify(error, None),
+            message: message(error),
+            line: error.line(),
+            column: error.column(),
+            offset: error.column().saturating_sub(1),
+            snippet: None,
+        }
+    }
+}
+
+/// E.g. ``truncated at column 8: EOF while parsing an object, near `{"a": 1` ``
+impl fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if self.line > 1 {
+            write!(f, " at line {} column {}", self.line, self.column)?;
+        } else if self.column > 0 {
+            write!(f, " at column {}", self.column)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(snippet) = &self.snippet {
+            write!(f, ", near `{snippet}`")?;
+        }
+        Ok(())
+    }
+}
+
+/// The parser's message for the `error`, without the position it appends
+fn message(error: &serde_json::Error) -> String {
+    let message = error.to_string();
+    let position = format!(" at line {} column {}", error.line(), error.column());
+    match message.strip_suffix(&position) {
+        Some(message) => message.to_string(),
+        None => message,
+    }
+}
+
+/// Byte offset in `text` of the 1-based `line` and `column` reported by the parser
+fn byte_offset(text: &str, line: usize, column: usize) -> usize {
+    let line_start: usize = text
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    (line_start + column.saturating_sub(1)).min(text.len())
+}
+
+/// The text either side of `offset`, marking where it has been cut with `…`
+fn snippet(text: &str, offset: usize) -> String {
+    let mut start = offset.saturating_sub(SNIPPET_CONTEXT);
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = (offset + SNIPPET_CONTEXT).min(text.len());
+    while !text.is_char_boundary(end) {
+        end += 1;
+    }
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    snippet.push_str(&text[start..end]);
+    if end < text.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn diagnose(text: &str) -> ParseDiagnostic {
+        let error = serde_json::from_str::<Value>(text).unwrap_err();
+        ParseDiagnostic::new(&error, text)
+    }
+
+    #[test]
+    fn classify_parse_errors() {
+        let kind = |text: &str| diagnose(text).kind;
+        assert_eq!(kind(""), ParseErrorKind::Blank);
+        assert_eq!(kind("  "), ParseErrorKind::Blank);
+        assert_eq!(kind(r#"{"a": 1"#), ParseErrorKind::Truncated);
+        assert_eq!(
+            kind(r#"{"a": 1}{"a": 2}"#),
+            ParseErrorKind::TrailingCharacters
+        );
+        assert_eq!(kind(r#"{"a": 1,}"#), ParseErrorKind::TrailingComma);
+        assert_eq!(kind(r#"{"a": NaN}"#), ParseErrorKind::ExpectedValue);
+        assert_eq!(kind(r#"{'a': 1}"#), ParseErrorKind::KeyMustBeString);
+        assert_eq!(kind(r#"{"a" 1}"#), ParseErrorKind::ExpectedDelimiter);
+        assert_eq!(kind(r#"["\q"]"#), ParseErrorKind::InvalidEscape);
+        assert_eq!(kind(r#"["\ud800"]"#), ParseErrorKind::InvalidEscape);
+        assert_eq!(kind("[\"\t\"]"), ParseErrorKind::ControlCharacter);
+        assert_eq!(kind("[1e999]"), ParseErrorKind::InvalidNumber);
+        assert_eq!(kind(&"[".repeat(200)), ParseErrorKind::RecursionLimit);
+    }
+
+    #[test]
+    fn parse_error_positions() {
+        let diagnostic = diagnose(r#"{"a": 1}, "#);
+        assert_eq!(diagnostic.message, "trailing characters");
+        assert_eq!((diagnostic.line, diagnostic.column), (1, 9));
+        assert_eq!(diagnostic.offset, 8);
+        assert_eq!(diagnostic.snippet.as_deref(), Some(r#"{"a": 1}, "#));
+        assert_eq!(
+            diagnostic.to_string(),
+            r#"trailing_characters at column 9: trailing characters, near `{"a": 1}, `"#
+        );
+
+        let text = format!(r#"{{"long": "{}", "é": x}}"#, "a".repeat(40));
+        let diagnostic = diagnose(&text);
+        assert_eq!(diagnostic.kind, ParseErrorKind::ExpectedValue);
+        assert_eq!(&text[diagnostic.offset..], "x}");
+        assert_eq!(
+            diagnostic.snippet.as_deref(),
+            Some(r#"…aaaaaaaaaaa", "é": x}"#)
+        );
+    }
+}
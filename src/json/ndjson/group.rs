@@ -0,0 +1,85 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json_path::{JsonPath, ParseError};
+
+use crate::json::Value;
+
+/// Name shown for the group of the records without a value at the `--group-by` path
+pub const MISSING_GROUP: &str = "<missing>";
+
+/// `--group-by` group of a record
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Group {
+    /// The value found at the path written as JSON, so that values of different types are
+    /// told apart, e.g. `"1"` and `1`
+    Value(String),
+    /// The records without a value at the path
+    Missing,
+}
+
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(value) => write!(f, "{value}"),
+            Self::Missing => write!(f, "{MISSING_GROUP}"),
+        }
+    }
+}
+
+/// `--group-by` path, partitioning the records by the value found at it
+#[derive(Debug, Clone)]
+pub struct GroupBy {
+    pub path: JsonPath,
+}
+
+impl GroupBy {
+    /// Group the `json` record belongs to, by the first value found at the path
+    pub fn group(&self, json: &Value) -> Group {
+        match self.path.query(json).first() {
+            Some(value) => Group::Value(value.to_string()),
+            None => Group::Missing,
+        }
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = ParseError;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            path: JsonPath::parse(path)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn record_groups() {
+        let group_by: GroupBy = "$.event.type".parse().unwrap();
+        let group = |json| group_by.group(&json).to_string();
+        assert_eq!(group(json!({"event": {"type": "click"}})), r#""click""#);
+        assert_eq!(group(json!({"event": {"type": 3}})), "3");
+        assert_eq!(group(json!({"event": {"type": null}})), "null");
+        assert_eq!(group(json!({"event": {}})), MISSING_GROUP);
+        assert_eq!(group_by.group(&json!([1, 2])), Group::Missing);
+
+        // Values of different types, or that read like the missing group, are kept apart
+        assert_ne!(
+            group_by.group(&json!({"event": {"type": "1"}})),
+            group_by.group(&json!({"event": {"type": 1}}))
+        );
+        assert_ne!(
+            group_by.group(&json!({"event": {"type": MISSING_GROUP}})),
+            group_by.group(&json!({"event": {}}))
+        );
+
+        assert!("event.type".parse::<GroupBy>().is_err());
+    }
+}
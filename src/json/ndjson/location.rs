@@ -0,0 +1,282 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Wrapper around the errors we can encounter while parsing a [`RecordLocation`]
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("Expected a record location like `file.json:12`, `3-4`, `5#2` or `7[0]`, got `{0}`")]
+pub struct RecordLocationError(String);
+
+/// Where a record comes from in the input: the line it is on, along with the byte offset
+/// of the line when known, and which of the records of the line it is when there are several
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "SavedRecordLocation")]
+pub struct RecordLocation {
+    /// The input the record is from, set once stats of several inputs are merged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Line number, counting from 1
+    pub line: usize,
+    /// Last line of a location spanning several: a record split across lines, or a range of
+    /// lines in [`LineIds`](super::LineIds) samples
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+    /// Which of the records glued on the line by `--repair` this is, counting from 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record: Option<usize>,
+    /// Which of the values selected from the record by a `--jsonpath` query this is,
+    /// counting from 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_index: Option<usize>,
+    /// Byte offset of the start of the line, in the decompressed data for `.gz` files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+}
+
+impl RecordLocation {
+    /// The line `line` at byte `offset`
+    pub fn at(line: usize, offset: u64) -> Self {
+        Self {
+            offset: Some(offset),
+            ..Self::from(line)
+        }
+    }
+
+    /// The location extended to the last line of `next`
+    pub fn through(self, next: &Self) -> Self {
+        Self {
+            end_line: Some(next.last_line()),
+            ..self
+        }
+    }
+
+    /// The `i`th of the records glued on the line, counting from 1
+    pub fn with_record(self, record: usize) -> Self {
+        Self {
+            record: Some(record),
+            ..self
+        }
+    }
+
+    /// The `i`th of the values selected by a `--jsonpath` query, counting from 0
+    pub fn with_match(self, match_index: usize) -> Self {
+        Self {
+            match_index: Some(match_index),
+            ..self
+        }
+    }
+
+    /// The location in `file`, unless already known to be in another
+    pub fn in_file(self, file: &str) -> Self {
+        Self {
+            file: self.file.or_else(|| Some(file.to_owned())),
+            ..self
+        }
+    }
+
+    pub fn last_line(&self) -> usize {
+        self.end_line.unwrap_or(self.line)
+    }
+
+    /// Whether the location covers whole lines, rather than one of several records of a line
+    pub fn is_whole_lines(&self) -> bool {
+        self.record.is_none() && self.match_index.is_none()
+    }
+}
+
+impl From<usize> for RecordLocation {
+    fn from(line: usize) -> Self {
+        Self {
+            line,
+            ..Default::default()
+        }
+    }
+}
+
+/// Compact form of the location, as in `file.json:3-4#2[0]`, without the byte offset
+impl fmt::Display for RecordLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{file}:")?;
+        }
+        write!(f, "{}", self.line)?;
+        if let Some(end_line) = self.end_line {
+            write!(f, "-{end_line}")?;
+        }
+        if let Some(record) = self.record {
+            write!(f, "#{record}")?;
+        }
+        if let Some(match_index) = self.match_index {
+            write!(f, "[{match_index}]")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RecordLocation {
+    type Err = RecordLocationError;
+
+    fn from_str(location: &str) -> Result<Self, Self::Err> {
+        let invalid = || RecordLocationError(location.to_owned());
+        if let Some((location, match_index)) = split_legacy_match(location) {
+            let location: Self = location.parse().map_err(|_| invalid())?;
+            return Ok(location.with_match(match_index));
+        }
+        let (file, rest) = match location.rsplit_once(':') {
+            Some((file, rest)) => (Some(file.to_owned()), rest),
+            None => (None, location),
+        };
+        let (rest, match_index) = match rest.strip_suffix(']').and_then(|s| s.rsplit_once('[')) {
+            Some((rest, i)) => (rest, Some(i.parse().map_err(|_| invalid())?)),
+            None => (rest, None),
+        };
+        let (rest, record) = match rest.split_once('#') {
+            Some((rest, i)) => (rest, Some(i.parse().map_err(|_| invalid())?)),
+            None => (rest, None),
+        };
+        let (line, end_line) = match rest.split_once('-') {
+            Some((line, end_line)) => (line, Some(end_line.parse().map_err(|_| invalid())?)),
+            None => (rest, None),
+        };
+        Ok(Self {
+            file,
+            line: line.parse().map_err(|_| invalid())?,
+            end_line,
+            record,
+            match_index,
+            offset: None,
+        })
+    }
+}
+
+/// Splits the ids of the values selected by a `--jsonpath` query in stats saved before
+/// locations were structured, `[file:]line:query[i]` as in `a.json:3:$.a[0]`, into the
+/// location of the record and the index of the value
+fn split_legacy_match(location: &str) -> Option<(&str, usize)> {
+    let (id, match_index) = location.strip_suffix(']')?.rsplit_once('[')?;
+    let match_index = match_index.parse().ok()?;
+    // The query follows the line, which is made of digits, `-` and `#` unlike the query
+    let is_line = |line: &str| {
+        line.starts_with(|c: char| c.is_ascii_digit())
+            && line
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '-' || c == '#')
+    };
+    id.match_indices(':').find_map(|(i, _)| {
+        let (record, query) = (&id[..i], &id[i + 1..]);
+        let line = record.rsplit(':').next().unwrap_or(record);
+        (is_line(line) && !query.starts_with(|c: char| c.is_ascii_digit()))
+            .then_some((record, match_index))
+    })
+}
+
+/// Saved stats give the locations as strings when from before they were structured
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedRecordLocation {
+    Text(String),
+    Structured {
+        #[serde(default)]
+        file: Option<String>,
+        line: usize,
+        #[serde(default)]
+        end_line: Option<usize>,
+        #[serde(default)]
+        record: Option<usize>,
+        #[serde(default)]
+        match_index: Option<usize>,
+        #[serde(default)]
+        offset: Option<u64>,
+    },
+}
+
+impl TryFrom<SavedRecordLocation> for RecordLocation {
+    type Error = RecordLocationError;
+
+    fn try_from(saved: SavedRecordLocation) -> Result<Self, Self::Error> {
+        match saved {
+            SavedRecordLocation::Text(location) => location.parse(),
+            SavedRecordLocation::Structured {
+                file,
+                line,
+                end_line,
+                record,
+                match_index,
+                offset,
+            } => Ok(Self {
+                file,
+                line,
+                end_line,
+                record,
+                match_index,
+                offset,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn record_locations() {
+        let location = RecordLocation::at(3, 120)
+            .through(&RecordLocation::from(4))
+            .with_record(2)
+            .with_match(0)
+            .in_file("in.json");
+        assert_eq!(location.to_string(), "in.json:3-4#2[0]");
+        assert_eq!(
+            "in.json:3-4#2[0]".parse(),
+            Ok(RecordLocation {
+                offset: None,
+                ..location.clone()
+            })
+        );
+        assert_eq!(location.clone().in_file("other.json"), location);
+        assert!("x".parse::<RecordLocation>().is_err());
+        assert!("3#".parse::<RecordLocation>().is_err());
+
+        let mut locations = [
+            RecordLocation::from(10),
+            RecordLocation::from(2).with_match(1),
+            RecordLocation::from(2),
+        ];
+        locations.sort();
+        assert_eq!(locations[0], RecordLocation::from(2));
+        assert_eq!(locations[2], RecordLocation::from(10));
+    }
+
+    #[test]
+    fn saved_record_locations() {
+        let location = RecordLocation::at(2, 15).with_match(0);
+        let saved = serde_json::to_value(&location).unwrap();
+        assert_eq!(saved, json!({"line": 2, "match_index": 0, "offset": 15}));
+        assert_eq!(
+            serde_json::from_value::<RecordLocation>(saved).unwrap(),
+            location
+        );
+        assert_eq!(
+            serde_json::from_value::<RecordLocation>(json!("a.json:4")).unwrap(),
+            RecordLocation::from(4).in_file("a.json")
+        );
+
+        // Ids of the values selected by a query named them before they were structured
+        let legacy = |id: &str| serde_json::from_value::<RecordLocation>(json!(id)).unwrap();
+        assert_eq!(legacy("3:$[0]"), RecordLocation::from(3).with_match(0));
+        assert_eq!(
+            legacy("a.json:3:$.a[1]"),
+            RecordLocation::from(3).with_match(1).in_file("a.json")
+        );
+        assert_eq!(
+            legacy("3#2:$['b:c'][0]"),
+            RecordLocation::from(3).with_record(2).with_match(0)
+        );
+        assert_eq!(legacy("a.json:3[0]"), legacy("a.json:3:users[0]"));
+        assert!("3:$[x]".parse::<RecordLocation>().is_err());
+    }
+}
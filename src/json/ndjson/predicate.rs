@@ -0,0 +1,262 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use regex::Regex;
+use serde_json::Number;
+use serde_json_path::{JsonPath, ParseError};
+use thiserror::Error;
+
+use crate::json::Value;
+
+/// Wrapper around the various errors we can encounter while parsing a [`Predicate`]
+#[derive(Error, Debug)]
+pub enum PredicateError {
+    #[error("Invalid path in predicate")]
+    InvalidPath(#[from] ParseError),
+    #[error("Invalid regular expression in predicate")]
+    InvalidRegex(#[from] regex::Error),
+    #[error("Missing value to compare against after `{0}`")]
+    MissingValue(String),
+}
+
+/// Comparison operators supported by [`Predicate`]s
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn holds(self, lhs: &Value, rhs: &Value) -> bool {
+        match self {
+            Self::Eq => values_equal(lhs, rhs),
+            Self::Ne => !values_equal(lhs, rhs),
+            Self::Lt => compare_values(lhs, rhs) == Some(Ordering::Less),
+            Self::Le => compare_values(lhs, rhs).is_some_and(Ordering::is_le),
+            Self::Gt => compare_values(lhs, rhs) == Some(Ordering::Greater),
+            Self::Ge => compare_values(lhs, rhs).is_some_and(Ordering::is_ge),
+        }
+    }
+}
+
+/// What a [`Predicate`] checks of the values found at its path
+#[derive(Debug, Clone)]
+pub enum Condition {
+    /// The path is present
+    Exists,
+    /// The path is absent
+    Missing,
+    /// A value at the path compares to the given value
+    Compare(Comparison, Value),
+    /// A string value at the path matches the regular expression
+    Matches(Regex),
+}
+
+/// Record level filter given with `--where`, e.g. `$.event_type == "purchase"`,
+/// `$.amount >= 10`, `$.user.id` (exists), `!$.deleted_at` (missing) or `$.email =~ "@example\."`.
+///
+/// Values are JSON, falling back to plain strings so quotes can be left off.
+/// A record matches when any value found at the path satisfies the comparison, except for
+/// `!=` which matches when none of them are equal, including when the path is absent
+#[derive(Debug, Clone)]
+pub struct Predicate {
+    pub path: JsonPath,
+    pub condition: Condition,
+}
+
+/// Operators, longest first so that `<=` is not read as `<`
+const OPERATORS: [(&str, Option<Comparison>); 7] = [
+    ("==", Some(Comparison::Eq)),
+    ("!=", Some(Comparison::Ne)),
+    ("<=", Some(Comparison::Le)),
+    (">=", Some(Comparison::Ge)),
+    ("=~", None),
+    ("<", Some(Comparison::Lt)),
+    (">", Some(Comparison::Gt)),
+];
+
+impl Predicate {
+    /// Whether the `json` record satisfies the predicate
+    pub fn matches(&self, json: &Value) -> bool {
+        let nodes = self.path.query(json).all();
+        match &self.condition {
+            Condition::Exists => !nodes.is_empty(),
+            Condition::Missing => nodes.is_empty(),
+            Condition::Compare(Comparison::Ne, value) => {
+                nodes.iter().all(|node| Comparison::Ne.holds(node, value))
+            }
+            Condition::Compare(comparison, value) => {
+                nodes.iter().any(|node| comparison.holds(node, value))
+            }
+            Condition::Matches(regex) => nodes
+                .iter()
+                .any(|node| node.as_str().is_some_and(|text| regex.is_match(text))),
+        }
+    }
+}
+
+impl FromStr for Predicate {
+    type Err = PredicateError;
+
+    fn from_str(predicate: &str) -> Result<Self, Self::Err> {
+        let predicate = predicate.trim();
+        let Some((position, operator, comparison)) = find_operator(predicate) else {
+            return Ok(match predicate.strip_prefix('!') {
+                Some(path) => Self {
+                    path: JsonPath::parse(path.trim())?,
+                    condition: Condition::Missing,
+                },
+                None => Self {
+                    path: JsonPath::parse(predicate)?,
+                    condition: Condition::Exists,
+                },
+            });
+        };
+
+        let path = JsonPath::parse(predicate[..position].trim())?;
+        let literal = predicate[position + operator.len()..].trim();
+        if literal.is_empty() {
+            return Err(PredicateError::MissingValue(operator.to_string()));
+        }
+        let value =
+            serde_json::from_str(literal).unwrap_or_else(|_| Value::String(literal.to_string()));
+        let condition = match comparison {
+            Some(comparison) => Condition::Compare(comparison, value),
+            None => {
+                let pattern = value.as_str().unwrap_or(literal);
+                Condition::Matches(Regex::new(pattern)?)
+            }
+        };
+        Ok(Self { path, condition })
+    }
+}
+
+/// Position of the first operator outside of the quotes and brackets of the path
+fn find_operator(predicate: &str) -> Option<(usize, &'static str, Option<Comparison>)> {
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (position, c) in predicate.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '\'' | '"' => quote = Some(c),
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => {
+                let rest = &predicate[position..];
+                let operator = OPERATORS
+                    .iter()
+                    .find(|(operator, _)| rest.starts_with(operator));
+                if let Some((operator, comparison)) = operator {
+                    return Some((position, operator, *comparison));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Equality treating numbers by value, so that `1` equals `1.0`
+fn values_equal(lhs: &Value, rhs: &Value) -> bool {
+    match (lhs, rhs) {
+        (Value::Number(lhs), Value::Number(rhs)) => {
+            compare_numbers(lhs, rhs) == Some(Ordering::Equal)
+        }
+        (lhs, rhs) => lhs == rhs,
+    }
+}
+
+/// Ordering of numbers and of strings, other values are not ordered
+fn compare_values(lhs: &Value, rhs: &Value) -> Option<Ordering> {
+    match (lhs, rhs) {
+        (Value::Number(lhs), Value::Number(rhs)) => compare_numbers(lhs, rhs),
+        (Value::String(lhs), Value::String(rhs)) => Some(lhs.cmp(rhs)),
+        _ => None,
+    }
+}
+
+/// Ordering of numbers, exact between integers, which `f64` cannot all tell apart beyond
+/// 2^53, and by their `f64` values otherwise
+fn compare_numbers(lhs: &Number, rhs: &Number) -> Option<Ordering> {
+    let integer = |number: &Number| {
+        number
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| number.as_u64().map(i128::from))
+    };
+    match (integer(lhs), integer(rhs)) {
+        (Some(lhs), Some(rhs)) => Some(lhs.cmp(&rhs)),
+        _ => lhs.as_f64()?.partial_cmp(&rhs.as_f64()?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn matches(predicate: &str, json: &Value) -> bool {
+        predicate.parse::<Predicate>().unwrap().matches(json)
+    }
+
+    #[test]
+    fn predicates() {
+        let json = json!({"type": "purchase", "amount": 10, "tags": ["a", "b"], "a=b": 1});
+        assert!(matches(r#"$.type == "purchase""#, &json));
+        assert!(matches("$.type == purchase", &json));
+        assert!(!matches("$.type != purchase", &json));
+        assert!(matches("$.missing != purchase", &json));
+        assert!(matches("$.amount == 10.0", &json));
+        assert!(matches("$.amount >= 10", &json));
+        assert!(!matches("$.amount < 10", &json));
+        assert!(matches("$.type > abc", &json));
+        assert!(!matches("$.type > 1", &json));
+        assert!(matches("$.tags[*] == b", &json));
+        assert!(matches("$.amount", &json));
+        assert!(matches("!$.deleted_at", &json));
+        assert!(!matches("!$.type", &json));
+        assert!(matches(r#"$.type =~ "^pur""#, &json));
+        assert!(!matches("$.amount =~ 1", &json));
+        assert!(matches("$['a=b'] == 1", &json));
+        assert!(matches("$.tags[?@ == 'a'] == a", &json));
+
+        // Large integers are compared exactly, not through f64
+        let json = json!({"id": 9007199254740992u64, "big": u64::MAX, "neg": -9007199254740993i64});
+        assert!(!matches("$.id == 9007199254740993", &json));
+        assert!(matches("$.id < 9007199254740993", &json));
+        assert!(matches("$.id == 9007199254740992", &json));
+        assert!(matches("$.big > 18446744073709551614", &json));
+        assert!(matches("$.neg < -9007199254740992", &json));
+        assert!(matches("$.id > -1", &json));
+    }
+
+    #[test]
+    fn invalid_predicates() {
+        assert!(matches!(
+            "$.type ==".parse::<Predicate>(),
+            Err(PredicateError::MissingValue(_))
+        ));
+        assert!(matches!(
+            "type == a".parse::<Predicate>(),
+            Err(PredicateError::InvalidPath(_))
+        ));
+        assert!(matches!(
+            "$.type =~ (".parse::<Predicate>(),
+            Err(PredicateError::InvalidRegex(_))
+        ));
+    }
+}
@@ -0,0 +1,257 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use super::errors::collection::{ErrorSink, IndexedNDJSONError};
+use super::errors::{NDJSONError, ParseErrorKind};
+use super::location::RecordLocation;
+
+/// Side files the lines that could not be analysed are written to as they are found, for
+/// `--bad-lines-out` and `--empty-lines-out`, so they can be pulled out of the data.
+/// Each line is written as a JSON of its id and error along with the line itself.
+///
+/// Writing carries on with the analysis should it fail, the first failure being returned by
+/// [`Quarantine::finish`]
+#[derive(Default)]
+pub struct Quarantine {
+    bad_lines: Option<Mutex<QuarantineFile>>,
+    empty_lines: Option<Mutex<QuarantineFile>>,
+}
+
+struct QuarantineFile {
+    writer: Box<dyn Write + Send>,
+    error: Option<io::Error>,
+}
+
+impl QuarantineFile {
+    fn new(writer: Box<dyn Write + Send>) -> Mutex<Self> {
+        Mutex::new(Self {
+            writer,
+            error: None,
+        })
+    }
+
+    fn create(path: &Path) -> io::Result<Mutex<Self>> {
+        let file = File::create(path)?;
+        Ok(Self::new(Box::new(BufWriter::new(file))))
+    }
+
+    fn write(&mut self, line: &QuarantinedLine) {
+        if self.error.is_some() {
+            return;
+        }
+        let written = serde_json::to_writer(&mut self.writer, line)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(self.writer));
+        self.error = written.err();
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+/// A line written to a [`Quarantine`] file
+#[derive(Debug, Serialize)]
+pub struct QuarantinedLine<'a> {
+    /// The input the line is from, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<&'a str>,
+    pub id: &'a RecordLocation,
+    /// The `--jsonpath` query the line returned empty for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<&'a str>,
+    pub error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ParseErrorKind>,
+    /// The line itself, or the record as compact JSON for empty lines, when known
+    pub line: Option<&'a str>,
+}
+
+impl Quarantine {
+    /// Writes the bad lines and empty lines to the given writers, if any
+    pub fn new(
+        bad_lines: Option<Box<dyn Write + Send>>,
+        empty_lines: Option<Box<dyn Write + Send>>,
+    ) -> Self {
+        Self {
+            bad_lines: bad_lines.map(QuarantineFile::new),
+            empty_lines: empty_lines.map(QuarantineFile::new),
+        }
+    }
+
+    /// Creates the files to write the bad lines and empty lines to, if any
+    pub fn create(
+        bad_lines_out: Option<&Path>,
+        empty_lines_out: Option<&Path>,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            bad_lines: bad_lines_out.map(QuarantineFile::create).transpose()?,
+            empty_lines: empty_lines_out.map(QuarantineFile::create).transpose()?,
+        })
+    }
+
+    /// Whether empty lines are being written, so their records need keeping
+    pub fn keeps_empty_lines(&self) -> bool {
+        self.empty_lines.is_some()
+    }
+
+    /// Writes the line `id` of the `file`, if it failed with an error being quarantined
+    pub fn record(
+        &self,
+        file: Option<&str>,
+        query: Option<&str>,
+        id: &RecordLocation,
+        error: &NDJSONError,
+    ) {
+        let (quarantine_file, query) = match error {
+            NDJSONError::IOError(_) | NDJSONError::JSONParsingError { .. } => {
+                (&self.bad_lines, None)
+            }
+            NDJSONError::EmptyQuery { .. } => (&self.empty_lines, query),
+            NDJSONError::FilteredOut => return,
+        };
+        let Some(quarantine_file) = quarantine_file else {
+            return;
+        };
+        let line = QuarantinedLine {
+            file,
+            id,
+            query,
+            error: error_message(error),
+            kind: error.parse_diagnostic().map(|diagnostic| diagnostic.kind),
+            line: error.line(),
+        };
+        quarantine_file.lock().unwrap().write(&line);
+    }
+
+    /// Writes the line of the `error` of the `source`, or of the file of its location when not
+    /// given, and of the `--jsonpath` query named `query`, if it is one being quarantined
+    pub fn record_error(
+        &self,
+        source: Option<&str>,
+        query: Option<&str>,
+        error: &IndexedNDJSONError,
+    ) {
+        let source = source.or(error.location.file.as_deref());
+        self.record(source, query, &error.location, &error.error);
+    }
+
+    /// The quarantine as a sink of the errors of the `source`, naming it in the lines written
+    pub fn of_source<'a>(&'a self, source: &'a str) -> SourceQuarantine<'a> {
+        SourceQuarantine {
+            quarantine: self,
+            source,
+        }
+    }
+
+    /// Flushes the files, returning the first error writing to them if any
+    pub fn finish(&self) -> io::Result<()> {
+        for quarantine_file in [&self.bad_lines, &self.empty_lines].into_iter().flatten() {
+            quarantine_file.lock().unwrap().finish()?;
+        }
+        Ok(())
+    }
+}
+
+impl ErrorSink for Quarantine {
+    fn record(&self, error: IndexedNDJSONError) {
+        self.record_error(None, None, &error)
+    }
+
+    fn record_query(&self, query: Option<&str>, error: IndexedNDJSONError) {
+        self.record_error(None, query, &error)
+    }
+}
+
+/// A [`Quarantine`] taking the errors of a single source, such as a file or stdin, see
+/// [`Quarantine::of_source`]
+pub struct SourceQuarantine<'a> {
+    quarantine: &'a Quarantine,
+    source: &'a str,
+}
+
+impl ErrorSink for SourceQuarantine<'_> {
+    fn record(&self, error: IndexedNDJSONError) {
+        self.record_query(None, error)
+    }
+
+    fn record_query(&self, query: Option<&str>, error: IndexedNDJSONError) {
+        self.quarantine
+            .record_error(Some(self.source), query, &error)
+    }
+}
+
+/// The message of the `error` followed by that of its source, if any
+fn error_message(error: &NDJSONError) -> String {
+    match std::error::Error::source(error) {
+        Some(source) => format!("{error}; {source}"),
+        None => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    #[test]
+    fn quarantine_lines() {
+        let bad_lines_out = tempfile::NamedTempFile::new().unwrap();
+        let empty_lines_out = tempfile::NamedTempFile::new().unwrap();
+        let quarantine =
+            Quarantine::create(Some(bad_lines_out.path()), Some(empty_lines_out.path())).unwrap();
+        assert!(quarantine.keeps_empty_lines());
+
+        let text = r#"{"a": 1"#;
+        let error = serde_json::from_str::<Value>(text).unwrap_err();
+        let invalid = NDJSONError::invalid_json(&error, text.to_string());
+        quarantine.record(
+            Some("in.json"),
+            Some("$.x"),
+            &RecordLocation::at(2, 8),
+            &invalid,
+        );
+        let empty = NDJSONError::EmptyQuery {
+            line: Some(r#"{"b":2}"#.to_string()),
+        };
+        let empty = IndexedNDJSONError::new(RecordLocation::from(3), empty);
+        quarantine.record_query(Some("$.x"), empty);
+        let filtered = IndexedNDJSONError::new(RecordLocation::from(4), NDJSONError::FilteredOut);
+        quarantine.of_source("in.json").record(filtered);
+        quarantine.finish().unwrap();
+
+        let read_lines = |path: &Path| -> Vec<Value> {
+            std::fs::read_to_string(path)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        };
+        assert_eq!(
+            read_lines(bad_lines_out.path()),
+            [json!({
+                "file": "in.json",
+                "id": {"line": 2, "offset": 8},
+                "error": invalid.to_string(),
+                "kind": "truncated",
+                "line": text,
+            })]
+        );
+        assert_eq!(
+            read_lines(empty_lines_out.path()),
+            [json!({
+                "id": {"line": 3},
+                "query": "$.x",
+                "error": "Line returned empty for the given query",
+                "line": r#"{"b":2}"#,
+            })]
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use std::str::FromStr;
+
+use serde_json_path::{JsonPath, ParseError};
+
+/// A `--jsonpath` query, with the name its results are reported under
+#[derive(Debug, Clone)]
+pub struct NamedQuery {
+    /// Given as `name=query`, otherwise the query itself
+    pub name: String,
+    pub selector: JsonPath,
+}
+
+impl NamedQuery {
+    pub fn new(name: impl Into<String>, query: &str) -> Result<Self, ParseError> {
+        Ok(Self {
+            name: name.into(),
+            selector: JsonPath::parse(query)?,
+        })
+    }
+}
+
+/// Parses `name=query` or just `query`.
+/// Queries start with `$`, so an `=` within a query (e.g. in a filter) is not taken as a name
+impl FromStr for NamedQuery {
+    type Err = ParseError;
+
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        match query.split_once('=') {
+            Some((name, named_query)) if !name.is_empty() && !name.starts_with('$') => {
+                Self::new(name, named_query)
+            }
+            _ => Self::new(query, query),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_named_queries() {
+        let query: NamedQuery = "requests=$.request".parse().unwrap();
+        assert_eq!(query.name, "requests");
+        assert_eq!(query.selector, JsonPath::parse("$.request").unwrap());
+
+        let query: NamedQuery = "$[?@.status == 'ok']".parse().unwrap();
+        assert_eq!(query.name, "$[?@.status == 'ok']");
+
+        assert!("name=not a query".parse::<NamedQuery>().is_err());
+    }
+}
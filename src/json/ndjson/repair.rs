@@ -0,0 +1,400 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use super::location::RecordLocation;
+use crate::json::{IndexMap, Value};
+
+/// A repair of a common corruption of NDJSON lines, attempted by `--repair` on the lines that
+/// fail to parse
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Repair {
+    /// Strips a UTF-8 byte order mark from the start of the line
+    Bom,
+    /// Turns single quoted strings into double quoted ones
+    SingleQuotes,
+    /// Drops commas before a closing bracket or brace, or at the end of the line
+    TrailingCommas,
+    /// Replaces `NaN` and `Infinity` literals, which JSON cannot represent, with `null`
+    NonFiniteNumbers,
+    /// Splits several records glued together on one line
+    GluedRecords,
+    /// Joins a record split across two lines back together
+    SplitRecord,
+}
+
+impl Repair {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bom => "bom",
+            Self::SingleQuotes => "single_quotes",
+            Self::TrailingCommas => "trailing_commas",
+            Self::NonFiniteNumbers => "non_finite_numbers",
+            Self::GluedRecords => "glued_records",
+            Self::SplitRecord => "split_record",
+        }
+    }
+}
+
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Number of lines fixed by each [`Repair`], shared by the threads parsing the lines
+#[derive(Debug, Default)]
+pub struct RepairCounts(Mutex<IndexMap<Repair, usize>>);
+
+impl RepairCounts {
+    /// Counts a line fixed by the `repairs`
+    pub fn add(&self, repairs: &[Repair]) {
+        let mut counts = self.0.lock().unwrap();
+        for repair in repairs {
+            *counts.entry(*repair).or_insert(0) += 1;
+        }
+    }
+
+    pub fn into_inner(self) -> IndexMap<Repair, usize> {
+        self.0.into_inner().unwrap()
+    }
+}
+
+/// The records of a line fixed by the `repairs`, each with its repaired text
+#[derive(Debug, PartialEq)]
+pub struct RepairedLine {
+    pub repairs: Vec<Repair>,
+    pub records: Vec<(String, Value)>,
+}
+
+/// Repair of the text of a line, giving the repaired text if there was anything to repair
+type TextRepair = fn(&str) -> Option<String>;
+
+/// Repairs of the text of a line, in the order they are applied. Single quotes go first so
+/// the later repairs only need to skip over double quoted strings
+const TEXT_REPAIRS: [(Repair, TextRepair); 3] = [
+    (Repair::SingleQuotes, single_quotes),
+    (Repair::TrailingCommas, trailing_commas),
+    (Repair::NonFiniteNumbers, non_finite_numbers),
+];
+
+/// Attempts to repair the `line` that failed to parse, returning its records if it could be
+/// repaired. `repairs` already applied to the line, i.e. [`Repair::SplitRecord`], are kept
+pub fn repair(line: &str, mut repairs: Vec<Repair>) -> Option<RepairedLine> {
+    let mut text = line.to_string();
+    if let Some(stripped) = text.strip_prefix('\u{feff}') {
+        text = stripped.to_string();
+        repairs.push(Repair::Bom);
+    }
+    for (repair, apply) in TEXT_REPAIRS {
+        if let Some(repaired) = apply(&text) {
+            text = repaired;
+            repairs.push(repair);
+        }
+    }
+    let records = match serde_json::from_str(&text) {
+        Ok(json) => vec![(text, json)],
+        Err(_) => {
+            let records = glued_records(&text)?;
+            repairs.push(Repair::GluedRecords);
+            records
+        }
+    };
+    (!repairs.is_empty()).then_some(RepairedLine { repairs, records })
+}
+
+/// Position of a scan over the text of a line, tracking double quoted strings
+#[derive(Default)]
+struct Scan {
+    in_string: bool,
+    escaped: bool,
+}
+
+impl Scan {
+    /// Whether the character `c` is part of a string, literal quotes included
+    fn in_string(&mut self, c: char) -> bool {
+        if self.escaped {
+            self.escaped = false;
+        } else if self.in_string && c == '\\' {
+            self.escaped = true;
+        } else if c == '"' {
+            self.in_string = !self.in_string;
+            return true;
+        }
+        self.in_string
+    }
+}
+
+/// [`Repair::SingleQuotes`], escaping the double quotes within the strings
+fn single_quotes(text: &str) -> Option<String> {
+    if !text.contains('\'') {
+        return None;
+    }
+    let mut repaired = String::with_capacity(text.len());
+    let mut scan = Scan::default();
+    let mut in_single = false;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if in_single {
+            match c {
+                '\'' => {
+                    repaired.push('"');
+                    in_single = false;
+                }
+                '"' => repaired.push_str("\\\""),
+                '\\' => match chars.next() {
+                    Some('\'') => repaired.push('\''),
+                    Some(escaped) => {
+                        repaired.push('\\');
+                        repaired.push(escaped);
+                    }
+                    None => repaired.push('\\'),
+                },
+                c => repaired.push(c),
+            }
+        } else if !scan.in_string(c) && c == '\'' {
+            repaired.push('"');
+            in_single = true;
+        } else {
+            repaired.push(c);
+        }
+    }
+    (repaired != text).then_some(repaired)
+}
+
+/// [`Repair::TrailingCommas`]
+fn trailing_commas(text: &str) -> Option<String> {
+    let mut repaired = String::with_capacity(text.len());
+    let mut scan = Scan::default();
+    for (i, c) in text.char_indices() {
+        if !scan.in_string(c) && c == ',' {
+            let next = text[i + 1..].trim_start().chars().next();
+            if matches!(next, None | Some('}' | ']')) {
+                continue;
+            }
+        }
+        repaired.push(c);
+    }
+    (repaired.len() != text.len()).then_some(repaired)
+}
+
+/// [`Repair::NonFiniteNumbers`], dropping any sign before them
+fn non_finite_numbers(text: &str) -> Option<String> {
+    if !text.contains("NaN") && !text.contains("Infinity") {
+        return None;
+    }
+    let mut repaired = String::with_capacity(text.len());
+    let mut scan = Scan::default();
+    let mut word = String::new();
+    let flush = |repaired: &mut String, word: &mut String| {
+        match word.trim_start_matches(['-', '+']) {
+            "NaN" | "Infinity" => repaired.push_str("null"),
+            _ => repaired.push_str(word),
+        }
+        word.clear();
+    };
+    for c in text.chars() {
+        if !scan.in_string(c) && (c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.')) {
+            word.push(c);
+            continue;
+        }
+        flush(&mut repaired, &mut word);
+        repaired.push(c);
+    }
+    flush(&mut repaired, &mut word);
+    (repaired != text).then_some(repaired)
+}
+
+/// [`Repair::GluedRecords`], the records in `text` separated by whitespace or commas, if
+/// there are several
+fn glued_records(text: &str) -> Option<Vec<(String, Value)>> {
+    let mut records = Vec::new();
+    let mut rest = text;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if rest.is_empty() {
+            break;
+        }
+        let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<Value>();
+        let json = stream.next()?.ok()?;
+        let (record, remainder) = rest.split_at(stream.byte_offset());
+        records.push((record.to_string(), json));
+        rest = remainder;
+    }
+    (records.len() > 1).then_some(records)
+}
+
+/// How a line ends: within how many unclosed brackets and braces, and whether within a string
+fn openness(line: &str) -> (isize, bool) {
+    let mut scan = Scan::default();
+    let mut depth = 0;
+    for c in line.chars() {
+        if scan.in_string(c) {
+            continue;
+        }
+        match c {
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    (depth, scan.in_string)
+}
+
+/// A line to parse, with its location, and whether it is two lines joined by
+/// [`Repair::SplitRecord`]
+pub type LineCandidate<E> = (RecordLocation, Result<String, E>, bool);
+
+/// Iterator joining records split across two consecutive lines back together when
+/// repairing, for [`Repair::SplitRecord`]. A line is joined with the next when it ends within
+/// a record that the next line closes. The joined lines take the location of the first,
+/// through the second, as in `2-3`.
+/// Runs before the lines are parsed, on their text alone, so it can precede parallel parsing
+pub struct JoinSplitRecords<I: Iterator> {
+    iter: Peekable<I>,
+    repair: bool,
+}
+
+impl<E, I: Iterator<Item = (RecordLocation, Result<String, E>)>> JoinSplitRecords<I> {
+    /// Joins the split records of `iter` if `repair`, otherwise only identifies its lines
+    pub fn new(iter: I, repair: bool) -> Self {
+        Self {
+            iter: iter.peekable(),
+            repair,
+        }
+    }
+}
+
+impl<E, I: Iterator<Item = (RecordLocation, Result<String, E>)>> Iterator for JoinSplitRecords<I> {
+    type Item = LineCandidate<E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (location, line) = self.iter.next()?;
+        let Ok(line) = line else {
+            return Some((location, line, false));
+        };
+        if !self.repair {
+            return Some((location, Ok(line), false));
+        }
+        let (depth, in_string) = openness(&line);
+        if depth <= 0 && !in_string {
+            return Some((location, Ok(line), false));
+        }
+        let follows = |next: &RecordLocation| next.line == location.line + 1;
+        let joined = match self.iter.peek() {
+            // A raw line break within a string is not valid JSON, so keep it escaped
+            Some((next, Ok(text))) if follows(next) && in_string => format!("{line}\\n{text}"),
+            Some((next, Ok(text))) if follows(next) => format!("{line}{text}"),
+            _ => return Some((location, Ok(line), false)),
+        };
+        if openness(&joined) != (0, false) {
+            return Some((location, Ok(line), false));
+        }
+        let (next, _) = self.iter.next().expect("peeked");
+        Some((location.through(&next), Ok(joined), true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn repaired(line: &str) -> Option<(Vec<Repair>, Vec<Value>)> {
+        assert!(serde_json::from_str::<Value>(line).is_err(), "{line}");
+        let repaired = repair(line, Vec::new())?;
+        let records = repaired.records.into_iter().map(|(_, json)| json).collect();
+        Some((repaired.repairs, records))
+    }
+
+    #[test]
+    fn repair_lines() {
+        use Repair::*;
+        assert_eq!(
+            repaired("\u{feff}{\"a\": 1}"),
+            Some((vec![Bom], vec![json!({"a": 1})]))
+        );
+        assert_eq!(
+            repaired(r#"{'a': 'it\'s "quoted"', "b": "'"}"#),
+            Some((
+                vec![SingleQuotes],
+                vec![json!({"a": "it's \"quoted\"", "b": "'"})]
+            ))
+        );
+        assert_eq!(
+            repaired(r#"{"a": [1, 2,], "b": ",]",},"#),
+            Some((vec![TrailingCommas], vec![json!({"a": [1, 2], "b": ",]"})]))
+        );
+        assert_eq!(
+            repaired(r#"{"a": NaN, "b": -Infinity, "c": "NaN", "Infinity": 1.5}"#),
+            Some((
+                vec![NonFiniteNumbers],
+                vec![json!({"a": null, "b": null, "c": "NaN", "Infinity": 1.5})]
+            ))
+        );
+        assert_eq!(
+            repaired(r#"{"a": 1}{"a": 2}, {"a": 3}"#),
+            Some((
+                vec![GluedRecords],
+                vec![json!({"a": 1}), json!({"a": 2}), json!({"a": 3})]
+            ))
+        );
+        assert_eq!(
+            repaired("{'a': NaN,}"),
+            Some((
+                vec![SingleQuotes, TrailingCommas, NonFiniteNumbers],
+                vec![json!({"a": null})]
+            ))
+        );
+        assert_eq!(repaired(r#"{"a": 1"#), None);
+        assert_eq!(repaired(r#"{"a": nan}"#), None);
+        assert_eq!(repaired(r#"{"a": 1} x"#), None);
+    }
+
+    #[test]
+    fn repaired_text() {
+        let repaired = repair(r#"{"a": 1} {"b": 2}"#, Vec::new()).unwrap();
+        let texts: Vec<&str> = repaired.records.iter().map(|(text, _)| &**text).collect();
+        assert_eq!(texts, [r#"{"a": 1}"#, r#"{"b": 2}"#]);
+    }
+
+    #[test]
+    fn join_split_records() {
+        let lines = [
+            r#"{"a": 1}"#,
+            r#"{"a": {"b": 2,"#,
+            r#""c": 3}}"#,
+            r#"{"a": "line"#,
+            r#"break"}"#,
+            r#"{"a": 4"#,
+            r#"{"a": 5}"#,
+        ];
+        let join = |repair| -> Vec<(String, String, bool)> {
+            let iter = (1..)
+                .map(RecordLocation::from)
+                .zip(lines.map(|line| Ok::<_, ()>(line.to_string())));
+            JoinSplitRecords::new(iter, repair)
+                .map(|(location, line, joined)| (location.to_string(), line.unwrap(), joined))
+                .collect()
+        };
+        let ids = |candidates: Vec<(String, String, bool)>| -> Vec<String> {
+            candidates.into_iter().map(|(id, _, _)| id).collect()
+        };
+        assert_eq!(ids(join(false)), ["1", "2", "3", "4", "5", "6", "7"]);
+
+        let joined = join(true);
+        assert_eq!(
+            joined[1],
+            (
+                "2-3".to_string(),
+                r#"{"a": {"b": 2,"c": 3}}"#.to_string(),
+                true
+            )
+        );
+        assert_eq!(joined[2].1, r#"{"a": "line\nbreak"}"#);
+        assert_eq!(ids(joined), ["1", "2-3", "4-5", "6", "7"]);
+    }
+}
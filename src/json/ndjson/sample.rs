@@ -0,0 +1,244 @@
+use std::fmt;
+use std::str::FromStr;
+
+use fastrand::Rng;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Wrapper around the errors we can encounter while parsing the sampling arguments
+#[derive(Error, Debug)]
+pub enum SampleError {
+    #[error("Invalid sample rate, expected a number")]
+    InvalidNumber(#[from] std::num::ParseFloatError),
+    #[error("Sample rate must be greater than 0 and at most 1, got {0}")]
+    OutOfRange(f64),
+}
+
+/// Probability of each line being kept by `--sample-rate`, greater than 0 and at most 1
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "f64", into = "f64")]
+pub struct SampleRate(f64);
+
+// NaN is out of range, so the rate always equals itself
+impl Eq for SampleRate {}
+
+impl SampleRate {
+    pub fn get(self) -> f64 {
+        self.0
+    }
+}
+
+impl TryFrom<f64> for SampleRate {
+    type Error = SampleError;
+
+    fn try_from(rate: f64) -> Result<Self, Self::Error> {
+        if rate > 0.0 && rate <= 1.0 {
+            Ok(Self(rate))
+        } else {
+            Err(SampleError::OutOfRange(rate))
+        }
+    }
+}
+
+impl From<SampleRate> for f64 {
+    fn from(rate: SampleRate) -> Self {
+        rate.0
+    }
+}
+
+impl FromStr for SampleRate {
+    type Err = SampleError;
+
+    fn from_str(rate: &str) -> Result<Self, Self::Err> {
+        rate.trim().parse::<f64>()?.try_into()
+    }
+}
+
+/// How the lines are sampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum SamplingMethod {
+    /// Keep each line independently with probability `rate`, `--sample-rate`
+    Bernoulli { rate: SampleRate },
+    /// Keep `size` lines chosen uniformly from the whole input, `--sample`
+    Reservoir { size: usize },
+}
+
+/// Random sampling of the lines, recorded in the [`super::Stats`] so that the sample can be
+/// reproduced with the same `--seed`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sampling {
+    #[serde(flatten)]
+    pub method: SamplingMethod,
+    pub seed: u64,
+}
+
+impl Sampling {
+    /// Samples the items of `iter`, keeping them in their original order
+    pub fn sample<I: Iterator>(self, iter: I) -> Sample<I> {
+        let rng = Rng::with_seed(self.seed);
+        match self.method {
+            SamplingMethod::Bernoulli { rate } => Sample::Bernoulli {
+                iter,
+                rng,
+                rate: rate.get(),
+            },
+            SamplingMethod::Reservoir { size } => Sample::Reservoir {
+                iter: Some(iter),
+                rng,
+                size,
+                kept: Vec::new().into_iter(),
+            },
+        }
+    }
+}
+
+/// Iterator over the items kept by a [`Sampling`], or all of them when not sampling.
+/// Unlike a boxed iterator it is [`Send`] when `I` is, so it can be sampled before being
+/// bridged to rayon
+pub enum Sample<I: Iterator> {
+    All(I),
+    Bernoulli {
+        iter: I,
+        rng: Rng,
+        rate: f64,
+    },
+    /// Consumes the whole of `iter` on the first call to `next`
+    Reservoir {
+        iter: Option<I>,
+        rng: Rng,
+        size: usize,
+        kept: std::vec::IntoIter<I::Item>,
+    },
+}
+
+impl<I: Iterator> Iterator for Sample<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::All(iter) => iter.next(),
+            Self::Bernoulli { iter, rng, rate } => iter.find(|_| rng.f64() < *rate),
+            Self::Reservoir {
+                iter,
+                rng,
+                size,
+                kept,
+            } => {
+                if let Some(iter) = iter.take() {
+                    *kept = reservoir(iter, *size, rng).into_iter();
+                }
+                kept.next()
+            }
+        }
+    }
+}
+
+/// Chooses `size` items uniformly at random from `iter` (Algorithm R), in their original order.
+/// The reservoir grows with the items kept rather than being sized from `size` up front, as
+/// it may be much larger than the items there are
+fn reservoir<T>(iter: impl Iterator<Item = T>, size: usize, rng: &mut Rng) -> Vec<T> {
+    let mut kept: Vec<(usize, T)> = Vec::new();
+    for (i, item) in iter.enumerate() {
+        if kept.len() < size {
+            kept.push((i, item));
+        } else {
+            let j = rng.usize(..=i);
+            if j < size {
+                kept[j] = (i, item);
+            }
+        }
+    }
+    kept.sort_unstable_by_key(|(i, _)| *i);
+    kept.into_iter().map(|(_, item)| item).collect()
+}
+
+impl fmt::Display for Sampling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.method {
+            SamplingMethod::Bernoulli { rate } => write!(f, "{}% of lines", rate.get() * 100.0)?,
+            SamplingMethod::Reservoir { size } => write!(f, "{size} lines")?,
+        }
+        write!(f, " (seed {})", self.seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_rates() {
+        assert_eq!("0.5".parse::<SampleRate>().unwrap().get(), 0.5);
+        assert_eq!("1".parse::<SampleRate>().unwrap().get(), 1.0);
+        assert!(matches!(
+            "0".parse::<SampleRate>(),
+            Err(SampleError::OutOfRange(_))
+        ));
+        assert!(matches!(
+            "1.5".parse::<SampleRate>(),
+            Err(SampleError::OutOfRange(_))
+        ));
+        assert!(matches!(
+            "NaN".parse::<SampleRate>(),
+            Err(SampleError::OutOfRange(_))
+        ));
+        assert!(matches!(
+            "half".parse::<SampleRate>(),
+            Err(SampleError::InvalidNumber(_))
+        ));
+    }
+
+    #[test]
+    fn bernoulli_sampling() {
+        let sampling = Sampling {
+            method: SamplingMethod::Bernoulli {
+                rate: "0.1".parse().unwrap(),
+            },
+            seed: 7,
+        };
+        let sample: Vec<usize> = sampling.sample(0..10_000).collect();
+        assert!((800..1200).contains(&sample.len()), "{}", sample.len());
+        assert!(sample.is_sorted());
+        assert_eq!(sample, sampling.sample(0..10_000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn reservoir_sampling() {
+        let sampling = Sampling {
+            method: SamplingMethod::Reservoir { size: 10 },
+            seed: 7,
+        };
+        let sample: Vec<usize> = sampling.sample(0..1000).collect();
+        assert_eq!(sample.len(), 10);
+        assert!(sample.is_sorted());
+        assert!(sample.iter().any(|&i| i >= 500), "{sample:?}");
+        assert_eq!(sample, sampling.sample(0..1000).collect::<Vec<_>>());
+        assert_eq!(sampling.sample(0..3).collect::<Vec<_>>(), [0, 1, 2]);
+
+        // A reservoir larger than could be allocated is fine for few items
+        let sampling = Sampling {
+            method: SamplingMethod::Reservoir { size: usize::MAX },
+            seed: 7,
+        };
+        assert_eq!(sampling.sample(0..3).collect::<Vec<_>>(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn sampling_round_trip() {
+        let sampling = Sampling {
+            method: SamplingMethod::Bernoulli {
+                rate: "0.25".parse().unwrap(),
+            },
+            seed: 42,
+        };
+        let json = serde_json::to_string(&sampling).unwrap();
+        assert_eq!(json, r#"{"method":"bernoulli","rate":0.25,"seed":42}"#);
+        assert_eq!(serde_json::from_str::<Sampling>(&json).unwrap(), sampling);
+        assert!(
+            serde_json::from_str::<Sampling>(r#"{"method":"bernoulli","rate":2,"seed":1}"#)
+                .is_err()
+        );
+        assert_eq!(sampling.to_string(), "25% of lines (seed 42)");
+    }
+}
@@ -1,20 +1,62 @@
-use owo_colors::{OwoColorize, Stream};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt;
-use std::io::IsTerminal;
 use std::iter::Sum;
 use std::ops::Add;
 
+use super::duplicates::{CandidateKeys, DuplicateRecords};
+use super::errors::{ParseDiagnostic, ParseErrorKind};
+use super::group::Group;
+use super::location::RecordLocation;
+use super::repair::Repair;
+use super::sample::Sampling;
 use crate::json::IndexMap;
+use crate::json::paths::{MapDetector, PathStyle};
+
+pub mod line_ids;
+pub mod output;
+
+pub use self::line_ids::{DEFAULT_MAX_ERROR_SAMPLES, LineIdLimits, LineIds};
+pub use self::output::{
+    OutputError, OutputFormat, OutputOptions, PathSummary, SortBy, SortOrder, html_report,
+};
 
 /// Container for the data collected about the JSONs along the way
 #[derive(Debug, PartialEq, Eq, Default, Clone, Serialize, Deserialize)]
 pub struct Stats {
     pub keys_count: IndexMap<String, usize>,
     pub line_count: usize,
-    pub bad_lines: Vec<String>,
+    pub bad_lines: LineIds,
     pub keys_types_count: IndexMap<String, usize>,
-    pub empty_lines: Vec<String>,
+    pub empty_lines: LineIds,
+    /// Number of the `bad_lines` with each kind of error, with the first few as examples
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub parse_errors: IndexMap<ParseErrorKind, ParseErrorSummary>,
+    /// Number of lines fixed by each `--repair`, whose records are analysed
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub repairs: IndexMap<Repair, usize>,
+    /// Lines with a key given more than once in an object, by path of the key, from
+    /// `--duplicate-keys`. Of all the lines read, before the `--where` predicates and the
+    /// queries
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub duplicate_keys: IndexMap<String, LineIds>,
+    /// Records the same as an earlier record, from `--duplicates`. Of the records selected
+    /// by the query once filtered by the `--where` predicates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_records: Option<DuplicateRecords>,
+    /// Distinct values of each scalar path and the candidate keys, from `--candidate-keys`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub candidate_keys: Option<CandidateKeys>,
+    /// Number of lines skipped for not matching the `--where` predicates
+    #[serde(default)]
+    pub filtered_out: usize,
+    /// Number of JSONs seen at each nesting depth, scalars being depth 0
+    #[serde(default)]
+    pub depth_histogram: BTreeMap<usize, usize>,
+    /// How the lines were sampled, if they were
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<Sampling>,
     // TODO: Add this: pub json_count: usize,
 }
 
@@ -23,9 +65,100 @@ impl Stats {
         Stats {
             keys_count: IndexMap::new(),
             line_count: 0,
-            bad_lines: Vec::new(),
+            bad_lines: LineIds::default(),
             keys_types_count: IndexMap::new(),
-            empty_lines: Vec::new(),
+            empty_lines: LineIds::default(),
+            parse_errors: IndexMap::new(),
+            repairs: IndexMap::new(),
+            duplicate_keys: IndexMap::new(),
+            duplicate_records: None,
+            candidate_keys: None,
+            filtered_out: 0,
+            depth_histogram: BTreeMap::new(),
+            sampling: None,
+        }
+    }
+
+    /// Empty stats keeping the ids of the bad and empty lines within `limits`
+    pub fn with_line_id_limits(limits: LineIdLimits) -> Stats {
+        Stats {
+            bad_lines: LineIds::new(limits),
+            empty_lines: LineIds::new(limits),
+            ..Stats::new()
+        }
+    }
+
+    /// Records the diagnosis of the bad line `id` towards the counts of its kind of error
+    pub fn add_parse_error(&mut self, id: &RecordLocation, diagnostic: ParseDiagnostic) {
+        let summary = self.parse_errors.entry(diagnostic.kind).or_default();
+        summary.add(id, diagnostic);
+    }
+
+    /// Number of lines read: those analysed along with the bad, empty and filtered out lines
+    pub fn lines_read(&self) -> usize {
+        self.line_count + self.bad_lines.len() + self.empty_lines.len() + self.filtered_out
+    }
+
+    /// Merges the counts of paths recorded before their parent object was detected as a map
+    /// into the collapsed `*` paths
+    pub fn collapse_map_paths(&mut self, maps: &MapDetector) {
+        rekey_counts(&mut self.keys_count, |path| maps.collapse(path));
+        rekey_counts(&mut self.keys_types_count, |path_type| {
+            let (path, type_) = path_type.rsplit_once("::")?;
+            maps.collapse(path).map(|path| format!("{path}::{type_}"))
+        });
+    }
+
+    /// The stats with the paths converted from JSONpaths to `style`, only copied when the
+    /// paths need converting
+    pub fn with_path_style(&self, style: PathStyle) -> Cow<'_, Stats> {
+        if style == PathStyle::Jsonpath {
+            return Cow::Borrowed(self);
+        }
+        let mut stats = self.clone();
+        rekey_counts(&mut stats.keys_count, |path| {
+            Some(style.from_jsonpath(path))
+        });
+        rekey_counts(&mut stats.keys_types_count, |path_type| {
+            let (path, type_) = path_type.rsplit_once("::")?;
+            Some(format!("{}::{type_}", style.from_jsonpath(path)))
+        });
+        stats.duplicate_keys = std::mem::take(&mut stats.duplicate_keys)
+            .into_iter()
+            .map(|(path, ids)| (style.from_jsonpath(&path), ids))
+            .collect();
+        if let Some(keys) = &mut stats.candidate_keys {
+            for candidate in &mut keys.candidates {
+                *candidate = style.from_jsonpath(candidate);
+            }
+            keys.paths = std::mem::take(&mut keys.paths)
+                .into_iter()
+                .map(|(path, uniqueness)| (style.from_jsonpath(&path), uniqueness))
+                .collect();
+        }
+        Cow::Owned(stats)
+    }
+
+    /// Whether the stats keep hashes to merge their distinct counts, see
+    /// [`Stats::forget_hashes`]
+    pub fn has_hashes(&self) -> bool {
+        self.duplicate_records
+            .as_ref()
+            .is_some_and(|duplicates| duplicates.hashes.is_some())
+            || self
+                .candidate_keys
+                .as_ref()
+                .is_some_and(|keys| keys.max_exact_hashes.is_some())
+    }
+
+    /// Drops the hashes kept to merge the distinct counts of `--duplicates` and
+    /// `--candidate-keys`, which are only saved by `--save-stats` rather than reported
+    pub fn forget_hashes(&mut self) {
+        if let Some(duplicates) = &mut self.duplicate_records {
+            duplicates.forget_hashes();
+        }
+        if let Some(keys) = &mut self.candidate_keys {
+            keys.forget_hashes();
         }
     }
 
@@ -44,62 +177,200 @@ impl Stats {
     }
 }
 
+/// Renames the keys of `counts` for which `rename` returns a new name, summing the counts of
+/// keys that end up with the same name
+fn rekey_counts(counts: &mut IndexMap<String, usize>, rename: impl Fn(&str) -> Option<String>) {
+    if !counts.keys().any(|key| rename(key).is_some()) {
+        return;
+    }
+    let mut rekeyed = IndexMap::with_capacity(counts.len());
+    for (key, count) in counts.drain(..) {
+        let key = rename(&key).unwrap_or(key);
+        *rekeyed.entry(key).or_insert(0) += count;
+    }
+    *counts = rekeyed;
+}
+
+/// Examples of each kind of parse error kept in the [`Stats`]
+pub const MAX_PARSE_ERROR_EXAMPLES: usize = 3;
+
+/// Number of bad lines with a kind of parse error, with the first few as examples
+#[derive(Debug, PartialEq, Eq, Default, Clone, Serialize, Deserialize)]
+pub struct ParseErrorSummary {
+    pub count: usize,
+    pub examples: Vec<ParseErrorExample>,
+}
+
+/// A bad line and the diagnosis of why it failed to parse
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ParseErrorExample {
+    pub id: RecordLocation,
+    #[serde(flatten)]
+    pub diagnostic: ParseDiagnostic,
+}
+
+impl ParseErrorSummary {
+    /// Records the diagnosis of the bad line `id`
+    pub fn add(&mut self, id: &RecordLocation, diagnostic: ParseDiagnostic) {
+        self.count += 1;
+        if self.examples.len() < MAX_PARSE_ERROR_EXAMPLES {
+            self.examples.push(ParseErrorExample {
+                id: id.clone(),
+                diagnostic,
+            });
+        }
+    }
+
+    /// Adds the counts and examples of `rhs`, with the example ids located in `file_path`
+    fn merge(&mut self, rhs: Self, file_path: &str) {
+        self.count += rhs.count;
+        let room = MAX_PARSE_ERROR_EXAMPLES.saturating_sub(self.examples.len());
+        self.examples.extend(
+            rhs.examples
+                .into_iter()
+                .take(room)
+                .map(|example| example.with_file_path(file_path)),
+        );
+    }
+}
+
+impl ParseErrorExample {
+    fn with_file_path(self, file_path: &str) -> Self {
+        Self {
+            id: self.id.in_file(file_path),
+            ..self
+        }
+    }
+}
+
+/// Adds the lines with duplicate keys of `rhs` into `duplicate_keys`, with the sampled ids
+/// located in `file_path`
+fn merge_duplicate_keys(
+    duplicate_keys: &mut IndexMap<String, LineIds>,
+    rhs: IndexMap<String, LineIds>,
+    file_path: &str,
+) {
+    for (path, ids) in rhs {
+        duplicate_keys
+            .entry(path)
+            .or_default()
+            .merge(ids, file_path);
+    }
+}
+
+/// Adds the `duplicate_records` and `candidate_keys` of another input into those of `output`,
+/// with the sampled ids located in `file_path`
+fn merge_duplicates(
+    output: &mut Stats,
+    duplicate_records: Option<DuplicateRecords>,
+    candidate_keys: Option<CandidateKeys>,
+    file_path: &str,
+) {
+    match (&mut output.duplicate_records, duplicate_records) {
+        (Some(duplicates), Some(rhs)) => duplicates.merge(rhs, file_path),
+        (None, Some(rhs)) => output.duplicate_records = Some(rhs.with_file_path(file_path)),
+        (_, None) => {}
+    }
+    match (&mut output.candidate_keys, candidate_keys) {
+        (Some(keys), Some(rhs)) => keys.merge(rhs),
+        (None, rhs) => output.candidate_keys = rhs,
+        (_, None) => {}
+    }
+}
+
+/// Adds the parse errors of `rhs` into `parse_errors`, see [`ParseErrorSummary::merge`]
+fn merge_parse_errors(
+    parse_errors: &mut IndexMap<ParseErrorKind, ParseErrorSummary>,
+    rhs: IndexMap<ParseErrorKind, ParseErrorSummary>,
+    file_path: &str,
+) {
+    for (kind, summary) in rhs {
+        parse_errors
+            .entry(kind)
+            .or_default()
+            .merge(summary, file_path);
+    }
+}
+
 impl fmt::Display for Stats {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let stream = Stream::Stdout;
-        writeln!(f, "Keys:\n{:#?}\n", self.keys_count.keys())?;
-        writeln!(f, "Key occurrence counts:\n{:#?}", self.keys_count)?;
-        writeln!(f, "\nKey occurrence rate:")?;
-        for (k, v) in self.key_occurrence() {
-            writeln!(f, "{}: {:.3}%", k, v)?;
-        }
-        writeln!(f, "\nKey type occurrence rate:")?;
-        for (k, v) in self.key_type_occurrence() {
-            writeln!(f, "{}: {:.3}%", k, v)?;
-        }
-        if !self.bad_lines.is_empty() {
-            writeln!(
-                f,
-                "{}\n{:?}",
-                "Corrupted lines:".if_supports_color(stream, |text| text.red()),
-                self.bad_lines.if_supports_color(stream, |text| text.red())
-            )?;
-        }
-        if !self.empty_lines.is_empty() {
-            writeln!(
-                f,
-                "{}\n{:?}",
-                "Empty lines:".if_supports_color(stream, |text| text.red()),
-                self.empty_lines
-                    .if_supports_color(stream, |text| text.red())
-            )?;
-        }
-        Ok(())
+        write!(f, "{}", self.text_report(&OutputOptions::default()))
     }
 }
 
-impl Stats {
-    pub fn print(&self) -> std::result::Result<(), serde_json::Error> {
-        if std::io::stdout().is_terminal() {
-            println!("{}", self);
-            Ok(())
-        } else {
-            let json_out = serde_json::to_string_pretty(self)?;
-            println!("{}", json_out);
-            Ok(())
+/// Identifies one of the sets of stats collected from a single read: those of a `--jsonpath`
+/// query, either overall or for one of the `--group-by` groups
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Section {
+    pub query: String,
+    pub group: Option<Group>,
+}
+
+impl Section {
+    pub fn new(query: impl Into<String>, group: Option<Group>) -> Self {
+        Self {
+            query: query.into(),
+            group,
         }
     }
 }
 
+/// The overall section of the query
+impl From<&str> for Section {
+    fn from(query: &str) -> Self {
+        Self::new(query, None)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Default, Clone, Serialize, Deserialize)]
 pub struct FileStats {
     pub file_path: String,
+    /// Name of the `--jsonpath` query the stats are for, when there were several
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    /// The `--group-by` group the stats are for, `None` for all of the records
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<Group>,
     pub stats: Stats,
 }
 
 impl FileStats {
     pub fn new(file_path: String, stats: Stats) -> Self {
-        Self { file_path, stats }
+        Self {
+            file_path,
+            query: None,
+            group: None,
+            stats,
+        }
+    }
+
+    /// Stats of a file for each of the sections, only naming the `--jsonpath` queries when
+    /// there are several to tell apart
+    pub fn by_section(file_path: String, sections: IndexMap<Section, Stats>) -> Vec<Self> {
+        // Each query has a single overall section
+        let several = sections.keys().filter(|s| s.group.is_none()).count() > 1;
+        sections
+            .into_iter()
+            .map(|(section, stats)| Self {
+                file_path: file_path.to_owned(),
+                query: several.then_some(section.query),
+                group: section.group,
+                stats,
+            })
+            .collect()
+    }
+
+    /// Title of the stats in reports, the file path followed by the query name and group
+    /// if any
+    pub fn title(&self) -> String {
+        let mut title = self.file_path.to_owned();
+        if let Some(query) = &self.query {
+            title.push_str(&format!(" {query}"));
+        }
+        if let Some(group) = &self.group {
+            title.push_str(&format!(" [{group}]"));
+        }
+        title
     }
 }
 
@@ -120,29 +391,54 @@ impl Add for FileStats {
         }
 
         output.line_count += rhs.stats.line_count;
+        output.filtered_out += rhs.stats.filtered_out;
+        output.sampling = output.sampling.or(rhs.stats.sampling);
 
-        output.bad_lines = output
-            .bad_lines
-            .into_iter()
-            .map(|line_id| format!("{}:{line_id}", self.file_path))
-            .collect();
-        output.bad_lines.extend(
-            rhs.stats
-                .bad_lines
+        for (repair, count) in rhs.stats.repairs {
+            *output.repairs.entry(repair).or_insert(0) += count;
+        }
+
+        for (depth, count) in rhs.stats.depth_histogram {
+            *output.depth_histogram.entry(depth).or_insert(0) += count;
+        }
+
+        output.bad_lines = output.bad_lines.with_file_path(&self.file_path);
+        output.bad_lines.merge(rhs.stats.bad_lines, &rhs.file_path);
+
+        output.empty_lines = output.empty_lines.with_file_path(&self.file_path);
+
+        for summary in output.parse_errors.values_mut() {
+            summary.examples = std::mem::take(&mut summary.examples)
                 .into_iter()
-                .map(|line_id| format!("{}:{line_id}", rhs.file_path)),
+                .map(|example| example.with_file_path(&self.file_path))
+                .collect();
+        }
+        merge_parse_errors(
+            &mut output.parse_errors,
+            rhs.stats.parse_errors,
+            &rhs.file_path,
         );
-
-        output.empty_lines = output
+        output
             .empty_lines
+            .merge(rhs.stats.empty_lines, &rhs.file_path);
+
+        output.duplicate_keys = std::mem::take(&mut output.duplicate_keys)
             .into_iter()
-            .map(|line_id| format!("{}:{line_id}", self.file_path))
+            .map(|(path, ids)| (path, ids.with_file_path(&self.file_path)))
             .collect();
-        output.empty_lines.extend(
-            rhs.stats
-                .empty_lines
-                .into_iter()
-                .map(|line_id| format!("{}:{line_id}", rhs.file_path)),
+        merge_duplicate_keys(
+            &mut output.duplicate_keys,
+            rhs.stats.duplicate_keys,
+            &rhs.file_path,
+        );
+        output.duplicate_records = output
+            .duplicate_records
+            .map(|duplicates| duplicates.with_file_path(&self.file_path));
+        merge_duplicates(
+            &mut output,
+            rhs.stats.duplicate_records,
+            rhs.stats.candidate_keys,
+            &rhs.file_path,
         );
 
         output
@@ -180,19 +476,37 @@ impl Add<FileStats> for Stats {
         }
 
         output.line_count += rhs.stats.line_count;
+        output.filtered_out += rhs.stats.filtered_out;
+        output.sampling = output.sampling.or(rhs.stats.sampling);
 
-        output.bad_lines.extend(
-            rhs.stats
-                .bad_lines
-                .into_iter()
-                .map(|line_id| format!("{}:{line_id}", rhs.file_path)),
-        );
+        for (repair, count) in rhs.stats.repairs {
+            *output.repairs.entry(repair).or_insert(0) += count;
+        }
 
-        output.empty_lines.extend(
-            rhs.stats
-                .empty_lines
-                .into_iter()
-                .map(|line_id| format!("{}:{line_id}", rhs.file_path)),
+        for (depth, count) in rhs.stats.depth_histogram {
+            *output.depth_histogram.entry(depth).or_insert(0) += count;
+        }
+
+        output.bad_lines.merge(rhs.stats.bad_lines, &rhs.file_path);
+        output
+            .empty_lines
+            .merge(rhs.stats.empty_lines, &rhs.file_path);
+
+        merge_parse_errors(
+            &mut output.parse_errors,
+            rhs.stats.parse_errors,
+            &rhs.file_path,
+        );
+        merge_duplicate_keys(
+            &mut output.duplicate_keys,
+            rhs.stats.duplicate_keys,
+            &rhs.file_path,
+        );
+        merge_duplicates(
+            &mut output,
+            rhs.stats.duplicate_records,
+            rhs.stats.candidate_keys,
+            &rhs.file_path,
         );
 
         output
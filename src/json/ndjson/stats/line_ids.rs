@@ -0,0 +1,351 @@
+use serde::{Deserialize, Serialize};
+
+use crate::json::ndjson::location::RecordLocation;
+
+/// Default number of ids kept by a [`LineIds`], from `--max-error-samples`
+pub const DEFAULT_MAX_ERROR_SAMPLES: usize = 100;
+
+/// How many ids of the lines a [`LineIds`] keeps, and how
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineIdLimits {
+    /// Most ids kept as samples, all of them when `None`
+    pub max_samples: Option<usize>,
+    /// Whether to compress consecutive lines into ranges, e.g. `1200-1850`
+    pub ranges: bool,
+}
+
+impl Default for LineIdLimits {
+    /// Keeps all of the ids, as they are
+    fn default() -> Self {
+        Self {
+            max_samples: None,
+            ranges: false,
+        }
+    }
+}
+
+impl LineIdLimits {
+    /// The tightest of both limits, so that merged ids honour the cap of either
+    fn merge(self, rhs: Self) -> Self {
+        Self {
+            max_samples: match (self.max_samples, rhs.max_samples) {
+                (Some(max), Some(rhs_max)) => Some(max.min(rhs_max)),
+                (max, rhs_max) => max.or(rhs_max),
+            },
+            ranges: self.ranges || rhs.ranges,
+        }
+    }
+}
+
+/// Number of lines of a kind, e.g. the bad lines, along with a sample of their locations
+/// bounded by its [`LineIdLimits`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "SavedLineIds")]
+pub struct LineIds {
+    count: usize,
+    samples: Vec<RecordLocation>,
+    /// Whether ids were left out of the samples
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
+    #[serde(skip)]
+    limits: LineIdLimits,
+}
+
+impl LineIds {
+    pub fn new(limits: LineIdLimits) -> Self {
+        Self {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    /// Counts the line `id`, keeping it if there is room in the samples
+    pub fn push(&mut self, id: &RecordLocation) {
+        self.count += 1;
+        if !(self.limits.ranges && self.extend_last(id)) {
+            self.keep(id.clone());
+        }
+    }
+
+    /// Counts the line `id` arriving out of order, as from parallel processing, keeping the
+    /// smallest ids as samples. The samples are only compressed into ranges and capped when
+    /// sorted by [`LineIds::sort`], which is done whenever they reach twice the cap, so the
+    /// last range kept may end early
+    pub fn push_unordered(&mut self, id: &RecordLocation) {
+        self.count += 1;
+        self.samples.push(id.clone());
+        if self
+            .limits
+            .max_samples
+            .is_some_and(|max| self.samples.len() > 2 * max)
+        {
+            self.sort();
+        }
+    }
+
+    /// Number of lines counted, sampled or not
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn samples(&self) -> &[RecordLocation] {
+        &self.samples
+    }
+
+    /// Whether some of the ids were left out of the samples
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, RecordLocation> {
+        self.samples.iter()
+    }
+
+    /// Sorts the samples, e.g. after the lines were processed in parallel, compressing them
+    /// into ranges and capping them as [`LineIds::push`] would have
+    pub fn sort(&mut self) {
+        let mut samples = std::mem::take(&mut self.samples);
+        samples.sort();
+        for id in samples {
+            if !(self.limits.ranges && self.extend_last(&id)) {
+                self.keep(id);
+            }
+        }
+    }
+
+    /// The ids with their samples located in `file_path`, to tell apart those of several
+    /// files once merged
+    pub fn with_file_path(mut self, file_path: &str) -> Self {
+        self.samples = self
+            .samples
+            .into_iter()
+            .map(|id| id.in_file(file_path))
+            .collect();
+        self
+    }
+
+    /// Adds the counts and samples of `rhs`, with the sampled ids located in `file_path`,
+    /// keeping to the tightest limits of both
+    pub fn merge(&mut self, rhs: Self, file_path: &str) {
+        self.limits = self.limits.merge(rhs.limits);
+        self.count += rhs.count;
+        self.truncated |= rhs.truncated;
+        if let Some(max) = self.limits.max_samples {
+            if self.samples.len() > max {
+                self.samples.truncate(max);
+                self.truncated = true;
+            }
+        }
+        for id in rhs.samples {
+            self.keep(id.in_file(file_path));
+        }
+    }
+
+    fn keep(&mut self, id: RecordLocation) {
+        if self
+            .limits
+            .max_samples
+            .is_some_and(|max| self.samples.len() >= max)
+        {
+            self.truncated = true;
+        } else {
+            self.samples.push(id);
+        }
+    }
+
+    /// Extends the last sample into a range when `id` is the line right after it
+    fn extend_last(&mut self, id: &RecordLocation) -> bool {
+        let Some(last) = self.samples.last_mut() else {
+            return false;
+        };
+        let consecutive = last.is_whole_lines()
+            && id.is_whole_lines()
+            && last.file == id.file
+            && last.last_line() + 1 == id.line;
+        if consecutive {
+            last.end_line = Some(id.last_line());
+        }
+        consecutive
+    }
+}
+
+/// The limits are how the ids were collected, not part of what was collected
+impl PartialEq for LineIds {
+    fn eq(&self, other: &Self) -> bool {
+        self.count == other.count
+            && self.samples == other.samples
+            && self.truncated == other.truncated
+    }
+}
+
+impl Eq for LineIds {}
+
+/// Compares the samples in their compact form, e.g. `file.json:12`
+impl<const N: usize> PartialEq<[&str; N]> for LineIds {
+    fn eq(&self, other: &[&str; N]) -> bool {
+        self.count == N
+            && self
+                .samples
+                .iter()
+                .zip(other)
+                .all(|(id, other)| id.to_string() == *other)
+    }
+}
+
+/// All of the ids, as they are
+impl From<Vec<RecordLocation>> for LineIds {
+    fn from(samples: Vec<RecordLocation>) -> Self {
+        Self {
+            count: samples.len(),
+            samples,
+            ..Default::default()
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a LineIds {
+    type Item = &'a RecordLocation;
+    type IntoIter = std::slice::Iter<'a, RecordLocation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Saved stats list all of the ids when from before they were sampled
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedLineIds {
+    All(Vec<RecordLocation>),
+    Sampled {
+        count: usize,
+        samples: Vec<RecordLocation>,
+        #[serde(default)]
+        truncated: bool,
+    },
+}
+
+impl From<SavedLineIds> for LineIds {
+    fn from(saved: SavedLineIds) -> Self {
+        match saved {
+            SavedLineIds::All(ids) => ids.into(),
+            SavedLineIds::Sampled {
+                count,
+                samples,
+                truncated,
+            } => Self {
+                count,
+                samples,
+                truncated,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_ids(max_samples: Option<usize>, ranges: bool, ids: &[&str]) -> LineIds {
+        let mut line_ids = LineIds::new(LineIdLimits {
+            max_samples,
+            ranges,
+        });
+        ids.iter()
+            .for_each(|id| line_ids.push(&id.parse().unwrap()));
+        line_ids
+    }
+
+    fn compact(ids: &LineIds) -> Vec<String> {
+        ids.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn capped_samples() {
+        let ids = line_ids(Some(2), false, &["1", "2", "5"]);
+        assert_eq!(ids.len(), 3);
+        assert_eq!(compact(&ids), ["1", "2"]);
+        assert!(ids.is_truncated());
+
+        let ids = line_ids(None, false, &["1", "2", "5"]);
+        assert_eq!(ids, ["1", "2", "5"]);
+        assert!(!ids.is_truncated());
+    }
+
+    #[test]
+    fn line_ranges() {
+        let ids = line_ids(
+            Some(2),
+            true,
+            &["3", "4", "5", "7", "8-9", "10", "12", "13"],
+        );
+        assert_eq!(ids.len(), 8);
+        assert_eq!(compact(&ids), ["3-5", "7-10"]);
+        assert!(ids.is_truncated());
+
+        let ids = line_ids(None, true, &["a:1", "a:2", "b:3", "4#1", "5[0]", "6"]);
+        assert_eq!(compact(&ids), ["a:1-2", "b:3", "4#1", "5[0]", "6"]);
+    }
+
+    #[test]
+    fn unordered_ids() {
+        let unordered = |max_samples, ranges, ids: &[&str]| {
+            let mut line_ids = LineIds::new(LineIdLimits {
+                max_samples,
+                ranges,
+            });
+            ids.iter()
+                .for_each(|id| line_ids.push_unordered(&id.parse().unwrap()));
+            line_ids.sort();
+            line_ids
+        };
+        let ids = ["9", "3", "12", "4", "1", "8", "5", "2"];
+        assert_eq!(compact(&unordered(Some(3), false, &ids)), ["1", "2", "3"]);
+        // 9 was dropped while the samples were `1` and `3-4`, cutting its range short
+        assert_eq!(compact(&unordered(Some(2), true, &ids)), ["1-5", "8"]);
+        let all = unordered(None, true, &ids);
+        assert_eq!(compact(&all), ["1-5", "8-9", "12"]);
+        assert_eq!(all.len(), 8);
+        assert!(!all.is_truncated());
+        assert!(unordered(Some(0), false, &ids).is_truncated());
+    }
+
+    #[test]
+    fn merge_honours_cap() {
+        let mut ids = LineIds::default().with_file_path("unused");
+        ids.merge(line_ids(Some(3), false, &["1", "2"]), "a.json");
+        ids.merge(line_ids(Some(5), false, &["4", "6"]), "b.json");
+        assert_eq!(ids.len(), 4);
+        assert_eq!(compact(&ids), ["a.json:1", "a.json:2", "b.json:4"]);
+        assert!(ids.is_truncated());
+
+        let mut ids = line_ids(None, false, &["1", "2", "3"]).with_file_path("a.json");
+        ids.merge(line_ids(Some(1), false, &["7"]), "b.json");
+        assert_eq!(ids.len(), 4);
+        assert_eq!(compact(&ids), ["a.json:1"]);
+
+        // Ids already located keep their file
+        let mut ids = LineIds::default();
+        ids.merge(line_ids(None, false, &["a.json:1"]), "merged");
+        assert_eq!(ids, ["a.json:1"]);
+    }
+
+    #[test]
+    fn saved_line_ids() {
+        let ids = line_ids(Some(1), false, &["1", "2"]);
+        let saved = serde_json::to_value(&ids).unwrap();
+        assert_eq!(
+            saved,
+            serde_json::json!({"count": 2, "samples": [{"line": 1}], "truncated": true})
+        );
+        assert_eq!(serde_json::from_value::<LineIds>(saved).unwrap(), ids);
+
+        let old: LineIds = serde_json::from_str(r#"["1", "a.json:2"]"#).unwrap();
+        assert_eq!(old, ["1", "a.json:2"]);
+    }
+}
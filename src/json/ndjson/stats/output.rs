@@ -0,0 +1,571 @@
+use std::cmp::Ordering;
+use std::io::{self, IsTerminal, Write};
+
+use clap::ValueEnum;
+use owo_colors::{OwoColorize, Stream};
+use serde::Serialize;
+use thiserror::Error;
+
+use super::{LineIds, MAX_PARSE_ERROR_EXAMPLES, Stats};
+use crate::json::IndexMap;
+use crate::json::paths::PathStyle;
+
+mod html;
+mod table;
+mod tree;
+
+pub use self::html::html_report;
+
+use self::table::Table;
+use self::tree::Tree;
+
+/// Formats [`Stats`] can be written out as
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable report, a table of the paths followed by any corrupted or empty lines
+    #[default]
+    Text,
+    /// Pretty printed JSON
+    Json,
+    /// Compact JSON on a single line
+    Ndjson,
+    /// YAML document
+    Yaml,
+    /// One CSV row per path
+    Csv,
+    /// Markdown table with one row per path
+    Markdown,
+    /// Aligned terminal table with one row per path, without the line summaries
+    Table,
+    /// Self-contained HTML page with sortable tables and a collapsible tree
+    Html,
+    /// Indented tree of the discovered structure, chosen with `--tree`
+    #[value(skip)]
+    Tree,
+}
+
+impl OutputFormat {
+    /// Fallback when no format was chosen: text for a terminal, JSON when piped
+    pub fn detect() -> Self {
+        if io::stdout().is_terminal() {
+            Self::Text
+        } else {
+            Self::Json
+        }
+    }
+
+    /// Whether the format is intended to be read by people rather than other programs,
+    /// in which case extra headings can be mixed into the output
+    pub fn is_human_readable(&self) -> bool {
+        matches!(self, Self::Text | Self::Markdown | Self::Table | Self::Tree)
+    }
+}
+
+/// Column to order the rows of the tabular outputs by
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    /// The path itself
+    Path,
+    /// Number of times the path was seen
+    Count,
+    /// Number of different types seen at the path
+    Types,
+}
+
+impl SortBy {
+    /// Order used when `--sort-order` is not given, most frequent/varied first for counts
+    fn default_order(&self) -> SortOrder {
+        match self {
+            Self::Path => SortOrder::Asc,
+            Self::Count | Self::Types => SortOrder::Desc,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Controls how [`Stats`] are written out
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    /// Order of the rows of the tabular outputs, order of discovery when `None`
+    pub sort_by: Option<SortBy>,
+    /// Defaults to ascending for paths and descending for counts
+    pub sort_order: Option<SortOrder>,
+    /// Width to fit the text tables into, truncating the paths as needed
+    pub max_width: Option<usize>,
+    /// Depth past which the nodes of the tree output are collapsed
+    pub max_depth: Option<usize>,
+    /// Notation the paths are written in
+    pub path_style: PathStyle,
+}
+
+impl OutputOptions {
+    pub fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            ..Default::default()
+        }
+    }
+
+    /// Width of the terminal when writing to one, used as [`OutputOptions::max_width`]
+    pub fn terminal_width() -> Option<usize> {
+        console::Term::stdout()
+            .size_checked()
+            .map(|(_rows, columns)| columns as usize)
+    }
+}
+
+/// Wrapper around the various errors we can encounter while writing out [`Stats`]
+#[derive(Error, Debug)]
+pub enum OutputError {
+    #[error("Failed to write output")]
+    IOError(#[from] io::Error),
+    #[error("Failed to serialise output as JSON")]
+    JSONError(#[from] serde_json::Error),
+    #[error("Failed to serialise output as YAML")]
+    YAMLError(#[from] serde_yaml::Error),
+}
+
+/// Everything collected about a single path, one row of the tabular output formats
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PathSummary {
+    pub path: String,
+    pub count: usize,
+    pub occurrence: f64,
+    pub types: IndexMap<String, usize>,
+}
+
+fn percentage(count: usize, total: usize) -> f64 {
+    100f64 * count as f64 / total as f64
+}
+
+impl Stats {
+    /// Combines the path and path type counts into one [`PathSummary`] per path
+    pub fn path_summaries(&self) -> Vec<PathSummary> {
+        let mut summaries: IndexMap<&str, PathSummary> = self
+            .keys_count
+            .iter()
+            .map(|(path, count)| {
+                let summary = PathSummary {
+                    path: path.to_owned(),
+                    count: *count,
+                    occurrence: percentage(*count, self.line_count),
+                    types: IndexMap::new(),
+                };
+                (path.as_str(), summary)
+            })
+            .collect();
+        for (path_type, count) in &self.keys_types_count {
+            let Some((path, type_)) = path_type.rsplit_once("::") else {
+                continue;
+            };
+            if let Some(summary) = summaries.get_mut(path) {
+                summary.types.insert(type_.to_string(), *count);
+            }
+        }
+        summaries.into_values().collect()
+    }
+
+    /// [`Stats::path_summaries`] in the order requested by `options`
+    pub fn sorted_path_summaries(&self, options: &OutputOptions) -> Vec<PathSummary> {
+        let mut summaries = self.path_summaries();
+        let Some(sort_by) = options.sort_by else {
+            return summaries;
+        };
+        let compare = |a: &PathSummary, b: &PathSummary| -> Ordering {
+            match sort_by {
+                SortBy::Path => a.path.cmp(&b.path),
+                SortBy::Count => a.count.cmp(&b.count),
+                SortBy::Types => a.types.len().cmp(&b.types.len()),
+            }
+        };
+        match options.sort_order.unwrap_or(sort_by.default_order()) {
+            SortOrder::Asc => summaries.sort_by(compare),
+            SortOrder::Desc => summaries.sort_by(|a, b| compare(b, a)),
+        }
+        summaries
+    }
+
+    /// Writes the stats to stdout as described by `options`
+    pub fn print(&self, options: &OutputOptions) -> Result<(), OutputError> {
+        let mut stdout = io::stdout().lock();
+        self.write(&mut stdout, options)
+    }
+
+    /// Writes the stats to `writer` as described by `options`
+    pub fn write(
+        &self,
+        writer: &mut impl Write,
+        options: &OutputOptions,
+    ) -> Result<(), OutputError> {
+        if options.format == OutputFormat::Html {
+            write!(writer, "{}", html_report(&[("Stats", self)], options))?;
+            return Ok(());
+        }
+        let mut stats = self.with_path_style(options.path_style);
+        if stats.has_hashes() {
+            stats.to_mut().forget_hashes();
+        }
+        match options.format {
+            OutputFormat::Text => write!(writer, "{}", stats.text_report(options))?,
+            OutputFormat::Json => {
+                serde_json::to_writer_pretty(&mut *writer, &*stats)?;
+                writeln!(writer)?;
+            }
+            OutputFormat::Ndjson => {
+                serde_json::to_writer(&mut *writer, &*stats)?;
+                writeln!(writer)?;
+            }
+            OutputFormat::Yaml => serde_yaml::to_writer(&mut *writer, &*stats)?,
+            OutputFormat::Csv => write!(writer, "{}", stats.table(options, false).to_csv())?,
+            OutputFormat::Markdown => write!(writer, "{}", stats.markdown_report(options))?,
+            OutputFormat::Table => {
+                write!(writer, "{}", stats.table(options, true).to_text(options))?
+            }
+            OutputFormat::Tree => write!(writer, "{}", stats.tree_report(options))?,
+            OutputFormat::Html => unreachable!("written above"),
+        }
+        Ok(())
+    }
+
+    fn table(&self, options: &OutputOptions, percent_signs: bool) -> Table {
+        Table::from_summaries(
+            &self.sorted_path_summaries(options),
+            self.line_count,
+            percent_signs,
+        )
+    }
+
+    /// Table of the paths followed by the corrupted and empty lines, the `text` format
+    pub(super) fn text_report(&self, options: &OutputOptions) -> String {
+        let mut report = self.report_heading();
+        report.push_str(&self.table(options, true).to_text(options));
+        report.push_str(&self.line_issues());
+        report
+    }
+
+    /// Tree of the paths followed by the corrupted and empty lines
+    fn tree_report(&self, options: &OutputOptions) -> String {
+        let tree = Tree::from_summaries(self.sorted_path_summaries(options), options.path_style);
+        let mut report = self.report_heading();
+        report.push_str(&tree.to_text(options, self.line_count));
+        report.push_str(&self.line_issues());
+        report
+    }
+
+    /// Line count, and the sampling, filtered out count and depth histogram when there are any
+    fn report_heading(&self) -> String {
+        let mut heading = format!("Lines: {}\n", self.line_count);
+        if let Some(sampling) = &self.sampling {
+            heading.push_str(&format!("Sampled: {sampling}\n"));
+        }
+        if self.filtered_out > 0 {
+            heading.push_str(&format!("Filtered out: {}\n", self.filtered_out));
+        }
+        if !self.repairs.is_empty() {
+            heading.push_str(&format!("Repaired: {}\n", self.repairs_text()));
+        }
+        if let Some(duplicates) = self.duplicate_records_text() {
+            heading.push_str(&format!("Duplicate records: {duplicates}\n"));
+        }
+        if let Some(keys) = self.candidate_keys_text() {
+            heading.push_str(&format!("Candidate keys: {keys}\n"));
+        }
+        if !self.depth_histogram.is_empty() {
+            heading.push_str(&format!("Depths: {}\n", self.depth_histogram_text()));
+        }
+        heading.push('\n');
+        heading
+    }
+
+    /// Depth histogram as `depth: count` pairs, e.g. `1: 20, 2: 5`
+    pub(super) fn depth_histogram_text(&self) -> String {
+        self.depth_histogram
+            .iter()
+            .map(|(depth, count)| format!("{depth}: {count}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Lines fixed by each `--repair` as `repair: count` pairs, e.g. `bom: 1, trailing_commas: 2`
+    pub(super) fn repairs_text(&self) -> String {
+        self.repairs
+            .iter()
+            .map(|(repair, count)| format!("{repair}: {count}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Count of each kind of parse error with its first example, e.g.
+    /// ``truncated: 2, e.g. line 3: truncated at column 8: EOF while parsing an object``
+    pub(super) fn parse_error_summaries(&self) -> Vec<String> {
+        self.parse_errors
+            .iter()
+            .map(|(kind, summary)| match summary.examples.first() {
+                Some(example) => format!(
+                    "{kind}: {}, e.g. line {}: {}",
+                    summary.count, example.id, example.diagnostic
+                ),
+                None => format!("{kind}: {}", summary.count),
+            })
+            .collect()
+    }
+
+    /// Number of lines with each duplicate key with their first few ids, e.g.
+    /// `$.id: 2, e.g. 3, 7`
+    pub(super) fn duplicate_key_summaries(&self) -> Vec<String> {
+        self.duplicate_keys
+            .iter()
+            .map(|(path, ids)| format!("{path}: {}{}", ids.len(), examples_text(ids)))
+            .collect()
+    }
+
+    /// Number of duplicate records, `~` when estimated, out of those compared with their
+    /// first few ids, e.g. `2 of 100 records, e.g. 3, 7`
+    pub(super) fn duplicate_records_text(&self) -> Option<String> {
+        let duplicates = self.duplicate_records.as_ref()?;
+        let approx = if duplicates.estimated { "~" } else { "" };
+        let mut text = format!(
+            "{approx}{} of {} records{}",
+            duplicates.duplicates,
+            duplicates.records,
+            examples_text(&duplicates.lines)
+        );
+        if !duplicates.paths.is_empty() {
+            text.push_str(&format!(" (on {})", duplicates.paths.join(", ")));
+        }
+        Some(text)
+    }
+
+    /// Paths with a distinct value in every record, e.g. `$.id, $.uuid`
+    pub(super) fn candidate_keys_text(&self) -> Option<String> {
+        let keys = self.candidate_keys.as_ref()?;
+        if keys.candidates.is_empty() {
+            return Some("none".to_string());
+        }
+        Some(keys.candidates.join(", "))
+    }
+
+    /// Coloured listing of the corrupted and empty lines
+    fn line_issues(&self) -> String {
+        let stream = Stream::Stdout;
+        let mut text = String::new();
+        if !self.bad_lines.is_empty() {
+            text.push_str(&format!(
+                "\n{}\n{:?}{}\n",
+                "Corrupted lines:".if_supports_color(stream, |text| text.red()),
+                compact_ids(&self.bad_lines).if_supports_color(stream, |text| text.red()),
+                truncation_note(&self.bad_lines)
+            ));
+        }
+        if !self.parse_errors.is_empty() {
+            text.push_str(&format!(
+                "\n{}\n",
+                "Parse errors:".if_supports_color(stream, |text| text.red())
+            ));
+            for summary in self.parse_error_summaries() {
+                text.push_str(&format!("  {summary}\n"));
+            }
+        }
+        if !self.empty_lines.is_empty() {
+            text.push_str(&format!(
+                "\n{}\n{:?}{}\n",
+                "Empty lines:".if_supports_color(stream, |text| text.red()),
+                compact_ids(&self.empty_lines).if_supports_color(stream, |text| text.red()),
+                truncation_note(&self.empty_lines)
+            ));
+        }
+        if !self.duplicate_keys.is_empty() {
+            text.push_str(&format!(
+                "\n{}\n",
+                "Duplicate keys (all lines read):".if_supports_color(stream, |text| text.red())
+            ));
+            for summary in self.duplicate_key_summaries() {
+                text.push_str(&format!("  {summary}\n"));
+            }
+        }
+        text
+    }
+
+    fn markdown_report(&self, options: &OutputOptions) -> String {
+        let mut report = self.table(options, true).to_markdown();
+        if !self.bad_lines.is_empty() {
+            report.push_str(&format!(
+                "\n**Corrupted lines:** {}{}\n",
+                compact_ids(&self.bad_lines).join(", "),
+                truncation_note(&self.bad_lines)
+            ));
+        }
+        if !self.parse_errors.is_empty() {
+            report.push_str("\n**Parse errors:**\n\n");
+            for summary in self.parse_error_summaries() {
+                report.push_str(&format!("- {summary}\n"));
+            }
+        }
+        if !self.empty_lines.is_empty() {
+            report.push_str(&format!(
+                "\n**Empty lines:** {}{}\n",
+                compact_ids(&self.empty_lines).join(", "),
+                truncation_note(&self.empty_lines)
+            ));
+        }
+        if !self.duplicate_keys.is_empty() {
+            report.push_str("\n**Duplicate keys (all lines read):**\n\n");
+            for summary in self.duplicate_key_summaries() {
+                report.push_str(&format!("- {summary}\n"));
+            }
+        }
+        if let Some(duplicates) = self.duplicate_records_text() {
+            report.push_str(&format!("\n**Duplicate records:** {duplicates}\n"));
+        }
+        if let Some(keys) = self.candidate_keys_text() {
+            report.push_str(&format!("\n**Candidate keys:** {keys}\n"));
+        }
+        report
+    }
+}
+
+/// The sampled locations of `ids` in their compact form, e.g. `file.json:12`
+fn compact_ids(ids: &LineIds) -> Vec<String> {
+    ids.iter().map(ToString::to_string).collect()
+}
+
+/// The first few sampled `ids`, e.g. `, e.g. 3, 7 …`, nothing when there are none
+fn examples_text(ids: &LineIds) -> String {
+    let mut examples = compact_ids(ids);
+    if examples.is_empty() {
+        return String::new();
+    }
+    let more = examples.len() > MAX_PARSE_ERROR_EXAMPLES || ids.is_truncated();
+    examples.truncate(MAX_PARSE_ERROR_EXAMPLES);
+    let more = if more { " …" } else { "" };
+    format!(", e.g. {}{more}", examples.join(", "))
+}
+
+/// Follows the samples of `ids` with the number of lines in all when some were left out
+fn truncation_note(ids: &LineIds) -> String {
+    if ids.is_truncated() {
+        format!(" … ({} in all)", ids.len())
+    } else {
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::ndjson::RecordLocation;
+
+    fn example_stats() -> Stats {
+        Stats {
+            keys_count: IndexMap::from([("$.key1".to_string(), 2), ("$.a,b".to_string(), 1)]),
+            line_count: 2,
+            keys_types_count: IndexMap::from([
+                ("$.key1::Number".to_string(), 1),
+                ("$.key1::Null".to_string(), 1),
+                ("$.a,b::String".to_string(), 1),
+            ]),
+            bad_lines: vec![RecordLocation::from(3)].into(),
+            ..Default::default()
+        }
+    }
+
+    fn written(stats: &Stats, options: &OutputOptions) -> String {
+        let mut out = Vec::new();
+        stats.write(&mut out, options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn path_summaries_group_types() {
+        let summaries = example_stats().path_summaries();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].path, "$.key1");
+        assert_eq!(summaries[0].occurrence, 100f64);
+        assert_eq!(
+            summaries[0].types,
+            IndexMap::from([("Number".to_string(), 1), ("Null".to_string(), 1)])
+        );
+    }
+
+    #[test]
+    fn sorted_path_summaries() {
+        let stats = example_stats();
+        let paths = |options: &OutputOptions| -> Vec<String> {
+            stats
+                .sorted_path_summaries(options)
+                .into_iter()
+                .map(|summary| summary.path)
+                .collect()
+        };
+        let by_path = OutputOptions {
+            sort_by: Some(SortBy::Path),
+            ..Default::default()
+        };
+        assert_eq!(paths(&by_path), ["$.a,b", "$.key1"]);
+
+        let by_count_asc = OutputOptions {
+            sort_by: Some(SortBy::Count),
+            sort_order: Some(SortOrder::Asc),
+            ..Default::default()
+        };
+        assert_eq!(paths(&by_count_asc), ["$.a,b", "$.key1"]);
+
+        let by_types = OutputOptions {
+            sort_by: Some(SortBy::Types),
+            ..Default::default()
+        };
+        assert_eq!(paths(&by_types), ["$.key1", "$.a,b"]);
+    }
+
+    #[test]
+    fn csv_output() {
+        let expected = "\
+path,count,occurrence_percent,Number,Null,String
+$.key1,2,100.000,50.000,50.000,
+\"$.a,b\",1,50.000,,,50.000
+";
+        let options = OutputOptions::new(OutputFormat::Csv);
+        assert_eq!(written(&example_stats(), &options), expected);
+    }
+
+    #[test]
+    fn markdown_output() {
+        let expected = "\
+| Path | Count | Occurrence % | Number | Null | String |
+| --- | ---: | ---: | ---: | ---: | ---: |
+| $.key1 | 2 | 100.000% | 50.000% | 50.000% |  |
+| $.a,b | 1 | 50.000% |  |  | 50.000% |
+
+**Corrupted lines:** 3
+";
+        let options = OutputOptions::new(OutputFormat::Markdown);
+        assert_eq!(written(&example_stats(), &options), expected);
+    }
+
+    #[test]
+    fn table_output() {
+        let expected = "\
+Path    Count  Occurrence %   Number     Null   String
+$.key1      2      100.000%  50.000%  50.000%
+$.a,b       1       50.000%                    50.000%
+";
+        let options = OutputOptions::new(OutputFormat::Table);
+        assert_eq!(written(&example_stats(), &options), expected);
+    }
+
+    #[test]
+    fn structured_outputs_round_trip() {
+        let stats = example_stats();
+        let ndjson = written(&stats, &OutputOptions::new(OutputFormat::Ndjson));
+        assert_eq!(ndjson.lines().count(), 1);
+        assert_eq!(serde_json::from_str::<Stats>(&ndjson).unwrap(), stats);
+        let yaml = written(&stats, &OutputOptions::new(OutputFormat::Yaml));
+        assert_eq!(serde_yaml::from_str::<Stats>(&yaml).unwrap(), stats);
+    }
+}
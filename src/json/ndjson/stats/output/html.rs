@@ -0,0 +1,250 @@
+use std::fmt::Write;
+
+use super::tree::Tree;
+use super::{OutputOptions, PathSummary, compact_ids, percentage};
+use crate::json::ndjson::Stats;
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+code { font-family: ui-monospace, monospace; }
+nav button { font: inherit; padding: 0.4em 1em; border: 1px solid #bbb; background: #f4f4f4; cursor: pointer; }
+nav button.active { background: #fff; border-bottom-color: #fff; font-weight: bold; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { padding: 0.25em 0.75em; border-bottom: 1px solid #ddd; text-align: right; }
+th:first-child, td:first-child, td.types { text-align: left; }
+th { cursor: pointer; user-select: none; background: #f4f4f4; }
+th.asc::after { content: " ▲"; }
+th.desc::after { content: " ▼"; }
+.bar { display: flex; width: 12em; height: 0.9em; background: #eee; }
+.bar span { display: block; height: 100%; }
+.legend { font-size: 0.85em; color: #555; }
+.type-Object { background: #8e6bbf; }
+.type-Array { background: #d9822b; }
+.type-String { background: #4a90d9; }
+.type-Number { background: #4caf50; }
+.type-Bool { background: #e0c341; }
+.type-Null { background: #9e9e9e; }
+.tree ul { list-style: none; padding-left: 1.5em; margin: 0; }
+.tree summary { cursor: pointer; }
+.rate { color: #2e7d32; }
+.types-label { color: #8a6d00; }
+.issues { color: #c62828; }
+"#;
+
+const SCRIPT: &str = r#"
+document.querySelectorAll("nav button").forEach((button) => {
+  button.addEventListener("click", () => {
+    document.querySelectorAll("nav button").forEach((b) => b.classList.remove("active"));
+    document.querySelectorAll("section.tab").forEach((s) => (s.hidden = s.id !== button.dataset.tab));
+    button.classList.add("active");
+  });
+});
+document.querySelectorAll("table.paths th").forEach((th, column) => {
+  th.addEventListener("click", () => {
+    const tbody = th.closest("table").querySelector("tbody");
+    const asc = !th.classList.contains("asc");
+    th.closest("tr").querySelectorAll("th").forEach((h) => h.classList.remove("asc", "desc"));
+    th.classList.add(asc ? "asc" : "desc");
+    const key = (row) => {
+      const cell = row.children[column];
+      return cell.dataset.value !== undefined ? parseFloat(cell.dataset.value) : cell.textContent;
+    };
+    const rows = Array.from(tbody.rows).sort((a, b) => {
+      const [x, y] = [key(a), key(b)];
+      const order = typeof x === "number" ? x - y : x.localeCompare(y);
+      return asc ? order : -order;
+    });
+    rows.forEach((row) => tbody.appendChild(row));
+  });
+});
+"#;
+
+/// Writes a single self-contained HTML page reporting on each of the titled `sections`,
+/// shown as tabs when there is more than one (e.g. one per file of a glob)
+pub fn html_report(sections: &[(&str, &Stats)], options: &OutputOptions) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>analyse-json report</title>\n");
+    writeln!(html, "<style>{STYLE}</style>\n</head>\n<body>").unwrap();
+    html.push_str("<h1>analyse-json report</h1>\n");
+
+    if sections.len() > 1 {
+        html.push_str("<nav>\n");
+        for (i, (title, _)) in sections.iter().enumerate() {
+            let active = if i == 0 { " class=\"active\"" } else { "" };
+            writeln!(
+                html,
+                "<button data-tab=\"tab-{i}\"{active}>{}</button>",
+                escape(title)
+            )
+            .unwrap();
+        }
+        html.push_str("</nav>\n");
+    }
+    for (i, (title, stats)) in sections.iter().enumerate() {
+        let hidden = if i == 0 { "" } else { " hidden" };
+        writeln!(html, "<section class=\"tab\" id=\"tab-{i}\"{hidden}>").unwrap();
+        writeln!(html, "<h2>{}</h2>", escape(title)).unwrap();
+        html.push_str(&stats_html(
+            &stats.with_path_style(options.path_style),
+            options,
+        ));
+        html.push_str("</section>\n");
+    }
+
+    writeln!(html, "<script>{SCRIPT}</script>\n</body>\n</html>").unwrap();
+    html
+}
+
+fn stats_html(stats: &Stats, options: &OutputOptions) -> String {
+    let summaries = stats.sorted_path_summaries(options);
+    let mut html = String::new();
+    writeln!(
+        html,
+        "<p>Lines: {} &middot; Paths: {} &middot; Corrupted lines: {} &middot; Empty lines: {}</p>",
+        stats.line_count,
+        summaries.len(),
+        stats.bad_lines.len(),
+        stats.empty_lines.len()
+    )
+    .unwrap();
+    if let Some(sampling) = &stats.sampling {
+        writeln!(html, "<p>Sampled: {sampling}</p>").unwrap();
+    }
+    if stats.filtered_out > 0 {
+        writeln!(html, "<p>Filtered out: {}</p>", stats.filtered_out).unwrap();
+    }
+    if !stats.repairs.is_empty() {
+        writeln!(html, "<p>Repaired: {}</p>", stats.repairs_text()).unwrap();
+    }
+    if let Some(duplicates) = stats.duplicate_records_text() {
+        writeln!(html, "<p>Duplicate records: {}</p>", escape(&duplicates)).unwrap();
+    }
+    if let Some(keys) = stats.candidate_keys_text() {
+        writeln!(html, "<p>Candidate keys: {}</p>", escape(&keys)).unwrap();
+    }
+    if !stats.depth_histogram.is_empty() {
+        writeln!(html, "<p>Depths: {}</p>", stats.depth_histogram_text()).unwrap();
+    }
+
+    html.push_str("<h3>Paths</h3>\n<table class=\"paths\">\n<thead><tr>");
+    html.push_str("<th>Path</th><th>Count</th><th>Occurrence %</th><th>Types</th>");
+    html.push_str("</tr></thead>\n<tbody>\n");
+    for summary in &summaries {
+        html.push_str(&path_row(summary, stats.line_count));
+    }
+    html.push_str("</tbody>\n</table>\n");
+
+    html.push_str("<h3>Structure</h3>\n");
+    html.push_str(&Tree::from_summaries(summaries, options.path_style).to_html(stats.line_count));
+
+    for (heading, line_ids) in [
+        ("Corrupted lines", &stats.bad_lines),
+        ("Empty lines", &stats.empty_lines),
+    ] {
+        if line_ids.is_empty() {
+            continue;
+        }
+        writeln!(
+            html,
+            "<h3 class=\"issues\">{heading} ({})</h3>",
+            line_ids.len()
+        )
+        .unwrap();
+        let lines: Vec<String> = compact_ids(line_ids)
+            .iter()
+            .map(|line| escape(line))
+            .collect();
+        let more = if line_ids.is_truncated() { " …" } else { "" };
+        writeln!(html, "<p class=\"issues\">{}{more}</p>", lines.join(", ")).unwrap();
+    }
+    if !stats.parse_errors.is_empty() {
+        html.push_str("<h3 class=\"issues\">Parse errors</h3>\n<ul class=\"issues\">\n");
+        for summary in stats.parse_error_summaries() {
+            writeln!(html, "<li>{}</li>", escape(&summary)).unwrap();
+        }
+        html.push_str("</ul>\n");
+    }
+    if !stats.duplicate_keys.is_empty() {
+        html.push_str(
+            "<h3 class=\"issues\">Duplicate keys (all lines read)</h3>\n<ul class=\"issues\">\n",
+        );
+        for summary in stats.duplicate_key_summaries() {
+            writeln!(html, "<li>{}</li>", escape(&summary)).unwrap();
+        }
+        html.push_str("</ul>\n");
+    }
+    html
+}
+
+/// Table row for a path, with a bar showing the share of each type seen at the path
+fn path_row(summary: &PathSummary, line_count: usize) -> String {
+    let mut bar = String::new();
+    let mut legend = Vec::new();
+    for (type_, count) in &summary.types {
+        let share = percentage(*count, summary.count);
+        let rate = percentage(*count, line_count);
+        write!(
+            bar,
+            "<span class=\"type-{type_}\" style=\"width: {share:.3}%\" title=\"{type_} {rate:.3}%\"></span>"
+        )
+        .unwrap();
+        legend.push(format!("{type_} {rate:.3}%"));
+    }
+    format!(
+        "<tr><td><code>{}</code></td><td data-value=\"{}\">{}</td><td data-value=\"{:.6}\">{:.3}%</td>\
+         <td class=\"types\"><div class=\"bar\">{bar}</div><span class=\"legend\">{}</span></td></tr>\n",
+        escape(&summary.path),
+        summary.count,
+        summary.count,
+        summary.occurrence,
+        summary.occurrence,
+        escape(&legend.join(", "))
+    )
+}
+
+/// Escapes text for use in HTML content and attribute values
+pub(super) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::IndexMap;
+    use crate::json::ndjson::RecordLocation;
+
+    #[test]
+    fn html_report_sections() {
+        let stats = Stats {
+            keys_count: IndexMap::from([("$.<a>".to_string(), 2)]),
+            line_count: 2,
+            keys_types_count: IndexMap::from([("$.<a>::String".to_string(), 2)]),
+            bad_lines: vec![RecordLocation::from(3)].into(),
+            ..Default::default()
+        };
+        let options = OutputOptions::default();
+
+        let single = html_report(&[("Stats", &stats)], &options);
+        assert!(single.starts_with("<!DOCTYPE html>"));
+        assert!(!single.contains("<nav>"));
+        assert!(single.contains("<code>$.&lt;a&gt;</code>"));
+        assert!(single.contains("Corrupted lines (1)"));
+        assert!(!single.contains("src=") && !single.contains("href="));
+
+        let tabs = html_report(&[("file 1", &stats), ("file 2", &stats)], &options);
+        assert!(tabs.contains("<button data-tab=\"tab-1\">file 2</button>"));
+        assert!(tabs.contains("<section class=\"tab\" id=\"tab-1\" hidden>"));
+    }
+}
@@ -0,0 +1,164 @@
+use indexmap::IndexSet;
+use owo_colors::{OwoColorize, Stream};
+
+use super::{OutputOptions, PathSummary, percentage};
+
+/// Narrowest the path column will be truncated to when fitting a table to the terminal
+const MIN_PATH_WIDTH: usize = 12;
+
+/// Consolidated table of [`PathSummary`]s, one row per path and a column per observed type
+pub(super) struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Builds the table cells, with or without `%` suffixes on the rates
+    pub(super) fn from_summaries(
+        summaries: &[PathSummary],
+        line_count: usize,
+        percent_signs: bool,
+    ) -> Self {
+        let types: IndexSet<&str> = summaries
+            .iter()
+            .flat_map(|summary| summary.types.keys().map(String::as_str))
+            .collect();
+        let suffix = if percent_signs { "%" } else { "" };
+
+        let mut headers = vec![
+            "Path".to_string(),
+            "Count".to_string(),
+            "Occurrence %".to_string(),
+        ];
+        headers.extend(types.iter().map(|type_| type_.to_string()));
+
+        let rows = summaries
+            .iter()
+            .map(|summary| {
+                let mut row = vec![
+                    summary.path.to_owned(),
+                    summary.count.to_string(),
+                    format!("{:.3}{suffix}", summary.occurrence),
+                ];
+                row.extend(types.iter().map(|type_| match summary.types.get(*type_) {
+                    Some(count) => {
+                        format!("{:.3}{suffix}", percentage(*count, line_count))
+                    }
+                    None => String::new(),
+                }));
+                row
+            })
+            .collect();
+
+        Self { headers, rows }
+    }
+
+    /// Aligned columns, truncating the start of long paths to fit `options.max_width`
+    pub(super) fn to_text(&self, options: &OutputOptions) -> String {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        if let Some(max_width) = options.max_width {
+            let total = widths.iter().sum::<usize>() + 2 * (widths.len() - 1);
+            if total > max_width {
+                let excess = total - max_width;
+                widths[0] = widths[0].saturating_sub(excess).max(MIN_PATH_WIDTH);
+            }
+        }
+
+        let format_row = |row: &[String]| -> String {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(i, (cell, width))| {
+                    if i == 0 {
+                        format!("{:<width$}", truncate_start(cell, *width))
+                    } else {
+                        format!("{cell:>width$}")
+                    }
+                })
+                .collect();
+            cells.join("  ").trim_end().to_string()
+        };
+
+        let mut text = format!(
+            "{}\n",
+            format_row(&self.headers).if_supports_color(Stream::Stdout, |text| text.bold())
+        );
+        for row in &self.rows {
+            text.push_str(&format_row(row));
+            text.push('\n');
+        }
+        text
+    }
+
+    pub(super) fn to_markdown(&self) -> String {
+        let escape = |cell: &String| cell.replace('|', "\\|").replace('\n', " ");
+        let mut text = format!(
+            "| {} |\n",
+            self.headers
+                .iter()
+                .map(escape)
+                .collect::<Vec<_>>()
+                .join(" | ")
+        );
+        let alignments: Vec<&str> = (0..self.headers.len())
+            .map(|i| if i == 0 { "---" } else { "---:" })
+            .collect();
+        text.push_str(&format!("| {} |\n", alignments.join(" | ")));
+        for row in &self.rows {
+            text.push_str(&format!(
+                "| {} |\n",
+                row.iter().map(escape).collect::<Vec<_>>().join(" | ")
+            ));
+        }
+        text
+    }
+
+    pub(super) fn to_csv(&self) -> String {
+        let escape = |cell: &String| {
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.to_owned()
+            }
+        };
+        let mut headers = self.headers.clone();
+        headers[0] = "path".to_string();
+        headers[1] = "count".to_string();
+        headers[2] = "occurrence_percent".to_string();
+
+        let mut text = String::new();
+        for row in std::iter::once(&headers).chain(&self.rows) {
+            text.push_str(&row.iter().map(escape).collect::<Vec<_>>().join(","));
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Shortens `text` to `width` characters by replacing its start with `…`, keeping the
+/// most specific end of a path visible
+fn truncate_start(text: &str, width: usize) -> String {
+    let length = text.chars().count();
+    if length <= width || width == 0 {
+        return text.to_string();
+    }
+    let kept: String = text.chars().skip(length - width + 1).collect();
+    format!("…{kept}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_long_paths() {
+        assert_eq!(truncate_start("$.a.b", 10), "$.a.b");
+        assert_eq!(truncate_start("$.a.bbbbbb.c", 6), "…bbb.c");
+    }
+}
@@ -0,0 +1,190 @@
+use owo_colors::{OwoColorize, Stream};
+
+use super::html::escape;
+use super::{OutputOptions, PathSummary, percentage};
+use crate::json::IndexMap;
+use crate::json::paths::PathStyle;
+
+/// Hierarchy of the discovered paths, rebuilt from the flat [`PathSummary`]s
+#[derive(Debug, Default)]
+pub(super) struct Tree {
+    /// Only set for paths that were seen as values, rather than just as parents of other paths
+    summary: Option<PathSummary>,
+    children: IndexMap<String, Tree>,
+}
+
+impl Tree {
+    /// Nests the summaries by the segments of their paths, written in `style`
+    pub(super) fn from_summaries(summaries: Vec<PathSummary>, style: PathStyle) -> Self {
+        let mut root = Self::default();
+        for summary in summaries {
+            let mut node = &mut root;
+            for name in style.split_names(&summary.path) {
+                node = node.children.entry(name).or_default();
+            }
+            node.summary = Some(summary);
+        }
+        root
+    }
+
+    /// Renders the tree like the `tree` command, collapsing nodes deeper than
+    /// `options.max_depth`
+    pub(super) fn to_text(&self, options: &OutputOptions, line_count: usize) -> String {
+        let mut text = self.label("$", line_count);
+        text.push('\n');
+        self.render_children(&mut text, "", 1, options.max_depth, line_count);
+        text
+    }
+
+    fn render_children(
+        &self,
+        text: &mut String,
+        prefix: &str,
+        depth: usize,
+        max_depth: Option<usize>,
+        line_count: usize,
+    ) {
+        let last = self.children.len().saturating_sub(1);
+        for (i, (name, child)) in self.children.iter().enumerate() {
+            let (connector, indent) = if i == last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            text.push_str(prefix);
+            text.push_str(connector);
+            text.push_str(&child.label(name, line_count));
+
+            let collapsed = max_depth.is_some_and(|max_depth| depth >= max_depth);
+            if collapsed && !child.children.is_empty() {
+                let hidden = format!("(+{} paths)", child.descendant_paths());
+                text.push_str(&format!(
+                    "  {}",
+                    hidden.if_supports_color(Stream::Stdout, |text| text.dimmed())
+                ));
+            }
+            text.push('\n');
+            if !collapsed {
+                let prefix = format!("{prefix}{indent}");
+                child.render_children(text, &prefix, depth + 1, max_depth, line_count);
+            }
+        }
+    }
+
+    /// Node name followed by its occurrence rate and types when it was seen as a value
+    fn label(&self, name: &str, line_count: usize) -> String {
+        let stream = Stream::Stdout;
+        let Some(summary) = &self.summary else {
+            return name.to_string();
+        };
+        let types = type_rates(summary, line_count);
+        let occurrence = format!("{:.3}%", summary.occurrence);
+        format!(
+            "{name}  {}  {}",
+            occurrence.if_supports_color(stream, |text| text.green()),
+            types.if_supports_color(stream, |text| text.yellow())
+        )
+    }
+
+    /// Renders the tree as nested collapsible HTML lists
+    pub(super) fn to_html(&self, line_count: usize) -> String {
+        let mut html = String::from("<ul class=\"tree\">\n");
+        self.render_html(&mut html, "$", line_count);
+        html.push_str("</ul>\n");
+        html
+    }
+
+    fn render_html(&self, html: &mut String, name: &str, line_count: usize) {
+        let mut label = format!("<code>{}</code>", escape(name));
+        if let Some(summary) = &self.summary {
+            let types = type_rates(summary, line_count);
+            label.push_str(&format!(
+                " <span class=\"rate\">{:.3}%</span> <span class=\"types-label\">{}</span>",
+                summary.occurrence,
+                escape(&types)
+            ));
+        }
+        if self.children.is_empty() {
+            html.push_str(&format!("<li>{label}</li>\n"));
+            return;
+        }
+        html.push_str(&format!(
+            "<li><details open><summary>{label}</summary>\n<ul>\n"
+        ));
+        for (name, child) in &self.children {
+            child.render_html(html, name, line_count);
+        }
+        html.push_str("</ul>\n</details></li>\n");
+    }
+
+    /// Number of paths seen as values below this node
+    fn descendant_paths(&self) -> usize {
+        self.children
+            .values()
+            .map(|child| usize::from(child.summary.is_some()) + child.descendant_paths())
+            .sum()
+    }
+}
+
+/// Types seen at the path with their occurrence rates, e.g. `String 95.000%, Null 5.000%`
+fn type_rates(summary: &PathSummary, line_count: usize) -> String {
+    summary
+        .types
+        .iter()
+        .map(|(type_, count)| format!("{type_} {:.3}%", percentage(*count, line_count)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::ndjson::Stats;
+
+    fn example_stats() -> Stats {
+        Stats {
+            keys_count: IndexMap::from([
+                ("$.a".to_string(), 2),
+                ("$.b.c".to_string(), 1),
+                ("$.b.d[*].e".to_string(), 1),
+            ]),
+            line_count: 2,
+            keys_types_count: IndexMap::from([
+                ("$.a::String".to_string(), 2),
+                ("$.b.c::Null".to_string(), 1),
+                ("$.b.d[*].e::Number".to_string(), 1),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tree_text() {
+        let expected = "\
+$
+├── a  100.000%  String 100.000%
+└── b
+    ├── c  50.000%  Null 50.000%
+    └── d
+        └── [*]
+            └── e  50.000%  Number 50.000%
+";
+        let tree = Tree::from_summaries(example_stats().path_summaries(), PathStyle::Jsonpath);
+        assert_eq!(tree.to_text(&OutputOptions::default(), 2), expected);
+    }
+
+    #[test]
+    fn tree_text_max_depth() {
+        let expected = "\
+$
+├── a  100.000%  String 100.000%
+└── b  (+2 paths)
+";
+        let options = OutputOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        let tree = Tree::from_summaries(example_stats().path_summaries(), PathStyle::Jsonpath);
+        assert_eq!(tree.to_text(&options, 2), expected);
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::{VecDeque, vec_deque};
+use std::iter::{Skip, Take};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+/// Wrapper around the errors we can encounter while parsing a [`LineRange`]
+#[derive(Error, Debug)]
+pub enum LineRangeError {
+    #[error(
+        "Expected a range of line numbers like `1000..2000`, `1000..=1999`, `1000..` or `..2000`"
+    )]
+    MissingDots,
+    #[error("Invalid line number in range")]
+    InvalidNumber(#[from] ParseIntError),
+    #[error("Line number too large in range")]
+    TooLarge,
+    #[error("Line numbers start at 1")]
+    LineZero,
+    #[error("Range of lines is empty")]
+    Empty,
+}
+
+/// Line numbers to inspect given with `--range`, counting from 1 like the line ids in the
+/// stats, e.g. `1000..2000`, `1000..=1999`, `1000..` or `..2000`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    /// Exclusive
+    pub end: Option<usize>,
+}
+
+impl LineRange {
+    /// Number of lines in the range, if bounded
+    pub fn line_count(self) -> Option<usize> {
+        self.end.map(|end| end - self.start)
+    }
+}
+
+impl FromStr for LineRange {
+    type Err = LineRangeError;
+
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        let (start, end) = range
+            .trim()
+            .split_once("..")
+            .ok_or(LineRangeError::MissingDots)?;
+        let start = match start {
+            "" => 1,
+            start => start.parse()?,
+        };
+        let end = match end.strip_prefix('=') {
+            Some(end) => Some(
+                end.parse::<usize>()?
+                    .checked_add(1)
+                    .ok_or(LineRangeError::TooLarge)?,
+            ),
+            None if end.is_empty() => None,
+            None => Some(end.parse()?),
+        };
+        if start == 0 || end == Some(0) {
+            return Err(LineRangeError::LineZero);
+        }
+        if end.is_some_and(|end| end <= start) {
+            return Err(LineRangeError::Empty);
+        }
+        Ok(Self { start, end })
+    }
+}
+
+/// Window of the lines to inspect, from `--skip`, `--range`, `--lines` and `--tail`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineWindow {
+    /// Lines to skip from the start
+    pub skip: usize,
+    /// Lines to take after those skipped, all when `None`
+    pub take: Option<usize>,
+    /// Only keep this many lines from the end of those taken
+    pub tail: Option<usize>,
+}
+
+impl LineWindow {
+    /// Selects the items of `iter` within the window. The items keep their own ids, as the
+    /// window is applied after they are indexed
+    pub fn apply<I: Iterator>(self, iter: I) -> Window<I> {
+        Window {
+            iter: iter.skip(self.skip).take(self.take.unwrap_or(usize::MAX)),
+            tail: self.tail,
+            buffered: None,
+        }
+    }
+}
+
+/// Iterator over the items within a [`LineWindow`].
+/// With a tail, the whole of the underlying iterator is read on the first call to `next`,
+/// buffering only the tail. The buffer grows with the items read rather than being sized
+/// from the tail up front, as it may be much larger than the items there are
+pub struct Window<I: Iterator> {
+    iter: Take<Skip<I>>,
+    tail: Option<usize>,
+    buffered: Option<vec_deque::IntoIter<I::Item>>,
+}
+
+impl<I: Iterator> Iterator for Window<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Some(tail) = self.tail else {
+            return self.iter.next();
+        };
+        let buffered = self.buffered.get_or_insert_with(|| {
+            let mut buffer = VecDeque::new();
+            for item in self.iter.by_ref() {
+                if buffer.len() == tail {
+                    buffer.pop_front();
+                }
+                if tail > 0 {
+                    buffer.push_back(item);
+                }
+            }
+            buffer.into_iter()
+        });
+        buffered.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_line_ranges() {
+        let range = |range: &str| range.parse::<LineRange>().map(|r| (r.start, r.end));
+        assert_eq!(range("1000..2000").unwrap(), (1000, Some(2000)));
+        assert_eq!(range("1000..=1999").unwrap(), (1000, Some(2000)));
+        assert_eq!(range("5..").unwrap(), (5, None));
+        assert_eq!(range("..5").unwrap(), (1, Some(5)));
+        assert_eq!("3..5".parse::<LineRange>().unwrap().line_count(), Some(2));
+        assert!(matches!(range("5"), Err(LineRangeError::MissingDots)));
+        assert!(matches!(
+            range("a..5"),
+            Err(LineRangeError::InvalidNumber(_))
+        ));
+        assert!(matches!(range("0..5"), Err(LineRangeError::LineZero)));
+        assert!(matches!(range("5..5"), Err(LineRangeError::Empty)));
+        assert!(matches!(
+            range(&format!("1..={}", usize::MAX)),
+            Err(LineRangeError::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn line_windows() {
+        let window = |skip, take, tail| -> Vec<usize> {
+            LineWindow { skip, take, tail }.apply(1..=10).collect()
+        };
+        assert_eq!(window(0, None, None), (1..=10).collect::<Vec<_>>());
+        assert_eq!(window(7, None, None), [8, 9, 10]);
+        assert_eq!(window(2, Some(3), None), [3, 4, 5]);
+        assert_eq!(window(0, None, Some(2)), [9, 10]);
+        assert_eq!(window(2, Some(3), Some(2)), [4, 5]);
+        assert_eq!(window(0, None, Some(20)).len(), 10);
+        assert_eq!(window(0, None, Some(usize::MAX)).len(), 10);
+        assert!(window(0, None, Some(0)).is_empty());
+    }
+}
@@ -1,11 +1,44 @@
 use super::serde_json::value::Index;
 use super::{IndexMap, Value, ValueType};
 
+pub mod filter;
+pub mod maps;
+pub mod segment;
+
+pub use self::filter::{PathFilter, PathPattern};
+pub use self::maps::MapDetector;
+pub use self::segment::{PathSegment, PathStyle, split_jsonpath};
+
+/// Settings for how [`ValuePaths::value_paths_with`] walks a JSON value
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    /// Treat arrays like a map of their enumerated elements, `$.array[0]`, `$.array[1]`, ...
+    pub explode_arrays: bool,
+    /// Group the elements of arrays together under `$.array[*]`
+    pub inspect_arrays: bool,
+    /// Which paths are walked and reported
+    pub filter: PathFilter,
+    /// Stop walking at this depth, reporting any deeper objects or arrays as a single path
+    pub max_depth: Option<usize>,
+    /// Which objects are map-like, having their keys collapsed to `*`
+    pub maps: MapDetector,
+}
+
+impl WalkOptions {
+    pub fn new(explode_arrays: bool, inspect_arrays: bool) -> Self {
+        Self {
+            explode_arrays,
+            inspect_arrays,
+            ..Default::default()
+        }
+    }
+}
+
 /// Wrapper around [`Value`] keeping track of its location within the root parent JSON
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ValuePath<'a> {
     pub value: &'a Value,
-    pub path: Vec<String>, // TODO: Switch to Vec<Index> ?
+    pub path: Vec<PathSegment>,
 }
 
 impl<'a> ValuePath<'a> {
@@ -14,7 +47,8 @@ impl<'a> ValuePath<'a> {
     ///
     /// # Arguments
     /// * `value` - Serde JSON Value
-    /// * `path` - Vec of Strings, components of the JSON path to self.value from the root ($)
+    /// * `path` - Vec of [`PathSegment`]s, components of the JSON path to self.value from the
+    ///   root ($)
     ///
     /// # Examples
     ///
@@ -24,23 +58,15 @@ impl<'a> ValuePath<'a> {
     /// let value = serde_json::json!({"key": "value"});
     /// let vp = ValuePath::new(&value, None);
     /// ```
-    pub fn new(value: &'a Value, path: Option<Vec<String>>) -> ValuePath<'a> {
+    pub fn new(value: &'a Value, path: Option<Vec<PathSegment>>) -> ValuePath<'a> {
         let path = path.unwrap_or_default();
         ValuePath { value, path }
     }
 
-    /// JSONpath of `value`s location within the root parent JSON
+    /// Normalized JSONpath of `value`s location within the root parent JSON, which can be
+    /// used as a `--jsonpath` query for the location
     pub fn jsonpath(&self) -> String {
-        let mut jsonpath = String::from("$");
-        for part in &self.path {
-            if part.starts_with('[') {
-                jsonpath.push_str(part);
-            } else {
-                jsonpath.push('.');
-                jsonpath.push_str(part);
-            }
-        }
-        jsonpath
+        PathStyle::Jsonpath.format(&self.path)
     }
 
     // Work around until indexing via a trait is supported
@@ -48,7 +74,7 @@ impl<'a> ValuePath<'a> {
     /// Index into the inner value, tracks the jsonpath location
     pub fn index(&self, index: impl JSONPathIndex) -> ValuePath<'a> {
         let mut child_path = self.path.to_vec();
-        child_path.push(index.jsonpath());
+        child_path.push(index.path_segment());
         ValuePath {
             value: &self.value[index],
             path: child_path,
@@ -56,13 +82,9 @@ impl<'a> ValuePath<'a> {
     }
 
     /// Index into the inner value, enables custom override to the tracked jsonpath location
-    pub fn index_custom(
-        &self,
-        index: impl Index,
-        index_custom: impl JSONPathIndex,
-    ) -> ValuePath<'a> {
+    pub fn index_custom(&self, index: impl Index, segment: PathSegment) -> ValuePath<'a> {
         let mut child_path = self.path.to_vec();
-        child_path.push(index_custom.jsonpath());
+        child_path.push(segment);
         ValuePath {
             value: &self.value[index],
             path: child_path,
@@ -70,60 +92,93 @@ impl<'a> ValuePath<'a> {
     }
 
     /// Lists all of the `ValuePath`s children by walking the inner value.
-    /// Includes flags for how to walk arrays
-    fn value_paths(self, explode_array: bool, inspect_arrays: bool) -> Vec<ValuePath<'a>> {
+    /// `included` is whether a parent already matched one of the filter's include patterns
+    fn value_paths(self, options: &WalkOptions, mut included: bool) -> Vec<ValuePath<'a>> {
         let mut paths = Vec::new();
 
+        let filter = &options.filter;
+        if !filter.is_empty() {
+            let jsonpath = self.jsonpath();
+            if filter.excludes(&jsonpath) {
+                return paths;
+            }
+            included = included || filter.includes(&jsonpath);
+        }
+
+        let at_max_depth = options
+            .max_depth
+            .is_some_and(|max_depth| self.path.len() >= max_depth);
+        if at_max_depth {
+            if included {
+                paths.push(self)
+            }
+            return paths;
+        }
+
         match self.value {
             Value::Object(map) => {
+                let is_map = options.maps.is_active() && options.maps.is_map(&self.jsonpath(), map);
                 for (k, _) in map {
-                    let vp = self.index(k);
-                    let inner_paths = vp.value_paths(explode_array, inspect_arrays);
+                    let vp = if is_map {
+                        self.index_custom(k, PathSegment::AnyKey)
+                    } else {
+                        self.index(k)
+                    };
+                    let inner_paths = vp.value_paths(options, included);
                     paths.extend(inner_paths)
                 }
             }
             Value::Array(array) => {
-                if inspect_arrays {
+                if options.inspect_arrays {
                     for (i, _array_value) in array.iter().enumerate() {
-                        let vp = self.index_custom(i, "[*]");
-                        let inner_paths = vp.value_paths(explode_array, inspect_arrays);
+                        let vp = self.index_custom(i, PathSegment::AnyIndex);
+                        let inner_paths = vp.value_paths(options, included);
                         paths.extend(inner_paths)
                     }
-                } else if explode_array {
+                } else if options.explode_arrays {
                     for (i, _array_value) in array.iter().enumerate() {
                         let vp = self.index(i);
-                        let inner_paths = vp.value_paths(explode_array, inspect_arrays);
+                        let inner_paths = vp.value_paths(options, included);
                         paths.extend(inner_paths)
                     }
-                } else {
+                } else if included {
+                    paths.push(self)
+                }
+            }
+            Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {
+                if included {
                     paths.push(self)
                 }
             }
-            Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => paths.push(self),
         }
         paths
     }
 }
 
 pub trait JSONPathIndex: Index {
-    fn jsonpath(&self) -> String;
+    fn path_segment(&self) -> PathSegment;
+
+    /// JSONpath notation of the index, e.g. `.key`, `['awkward key']` or `[0]`
+    fn jsonpath(&self) -> String {
+        self.path_segment().to_string()
+    }
 }
 
 impl JSONPathIndex for usize {
-    fn jsonpath(&self) -> String {
-        format!("[{}]", self)
+    fn path_segment(&self) -> PathSegment {
+        PathSegment::Index(*self)
     }
 }
 
 impl JSONPathIndex for str {
-    fn jsonpath(&self) -> String {
-        self.to_string()
+    fn path_segment(&self) -> PathSegment {
+        PathSegment::Key(self.to_string())
     }
 }
 
 impl JSONPathIndex for String {
-    fn jsonpath(&self) -> String {
-        self.to_string()
+    fn path_segment(&self) -> PathSegment {
+        PathSegment::Key(self.to_string())
     }
 }
 
@@ -131,13 +186,33 @@ impl<T> JSONPathIndex for &T
 where
     T: ?Sized + JSONPathIndex,
 {
-    fn jsonpath(&self) -> String {
-        (**self).jsonpath()
+    fn path_segment(&self) -> PathSegment {
+        (**self).path_segment()
+    }
+}
+
+/// Depth of nesting within a JSON value
+pub trait JSONDepth {
+    /// Number of levels of objects/arrays down to the deepest value, `0` for scalars and
+    /// empty objects/arrays
+    fn depth(&self) -> usize;
+}
+
+impl JSONDepth for Value {
+    fn depth(&self) -> usize {
+        let children_depth = match self {
+            Value::Object(map) => map.values().map(JSONDepth::depth).max(),
+            Value::Array(array) => array.iter().map(JSONDepth::depth).max(),
+            Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => None,
+        };
+        children_depth.map_or(0, |depth| depth + 1)
     }
 }
 
 pub trait ValuePaths {
     fn value_paths(&self, explode_array: bool, inspect_arrays: bool) -> Vec<ValuePath<'_>>;
+
+    fn value_paths_with(&self, options: &WalkOptions) -> Vec<ValuePath<'_>>;
 }
 
 impl ValuePaths for Value {
@@ -148,8 +223,15 @@ impl ValuePaths for Value {
     ///
     /// See also [`Value::json_paths`] from [`JSONPaths`]
     fn value_paths(&self, explode_array: bool, inspect_arrays: bool) -> Vec<ValuePath<'_>> {
+        self.value_paths_with(&WalkOptions::new(explode_array, inspect_arrays))
+    }
+
+    /// Lists the `ValuePath` children by walking `Value` as configured by `options`.
+    ///
+    /// Subtrees excluded by [`WalkOptions::filter`] are not walked at all
+    fn value_paths_with(&self, options: &WalkOptions) -> Vec<ValuePath<'_>> {
         let base_valuepath = ValuePath::new(self, None);
-        base_valuepath.value_paths(explode_array, inspect_arrays)
+        base_valuepath.value_paths(options, options.filter.include.is_empty())
     }
 }
 
@@ -159,7 +241,7 @@ impl ValuePaths for Value {
 //     type Output = ValuePath<'a>;
 //     fn index(&self, index: I) -> &Self::Output {
 //         let mut child_path = self.path.clone();
-//         child_path.push(index.jsonpath());
+//         child_path.push(index.path_segment());
 //         &ValuePath { value: &self.value[index], path: child_path }
 //     }
 // }
@@ -176,14 +258,18 @@ pub trait JSONPaths {
         explode_array: bool,
         inspect_arrays: bool,
     ) -> IndexMap<String, String>;
+
+    /// Lists the json_paths by walking a JSON `self` as configured by `options`
+    fn json_paths_with(&self, options: &WalkOptions) -> Vec<String>;
+
+    /// Maps the json_paths to the type they contain by walking a JSON `self` as configured
+    /// by `options`
+    fn json_paths_types_with(&self, options: &WalkOptions) -> IndexMap<String, String>;
 }
 
 impl JSONPaths for Value {
     fn json_paths(&self, explode_array: bool, inspect_arrays: bool) -> Vec<String> {
-        self.value_paths(explode_array, inspect_arrays)
-            .into_iter()
-            .map(|value_path| value_path.jsonpath())
-            .collect()
+        self.json_paths_with(&WalkOptions::new(explode_array, inspect_arrays))
     }
 
     fn json_paths_types(
@@ -191,7 +277,18 @@ impl JSONPaths for Value {
         explode_array: bool,
         inspect_arrays: bool,
     ) -> IndexMap<String, String> {
-        self.value_paths(explode_array, inspect_arrays)
+        self.json_paths_types_with(&WalkOptions::new(explode_array, inspect_arrays))
+    }
+
+    fn json_paths_with(&self, options: &WalkOptions) -> Vec<String> {
+        self.value_paths_with(options)
+            .into_iter()
+            .map(|value_path| value_path.jsonpath())
+            .collect()
+    }
+
+    fn json_paths_types_with(&self, options: &WalkOptions) -> IndexMap<String, String> {
+        self.value_paths_with(options)
             .into_iter()
             .map(|value_path| (value_path.jsonpath(), value_path.value.value_type()))
             .collect()
@@ -213,7 +310,7 @@ mod tests {
         let v_1 = &v["key2"];
         let vp_1 = vp_0.index("key2");
         assert_eq!(vp_1.value, v_1);
-        assert_eq!(vp_1.path, vec!["key2".to_string()]);
+        assert_eq!(vp_1.path, vec![PathSegment::from("key2")]);
         assert_eq!(vp_1.jsonpath(), "$.key2".to_string());
     }
 
@@ -227,7 +324,10 @@ mod tests {
         let vp_2 = vp_1.index(0);
 
         assert_eq!(vp_2.value, v_2);
-        assert_eq!(vp_2.path, vec!["key2".to_string(), "[0]".to_string()]);
+        assert_eq!(
+            vp_2.path,
+            vec![PathSegment::from("key2"), PathSegment::Index(0)]
+        );
         assert_eq!(vp_1.jsonpath(), "$.key2".to_string());
         assert_eq!(vp_2.jsonpath(), "$.key2[0]".to_string())
     }
@@ -238,8 +338,8 @@ mod tests {
         let vps = v.value_paths(false, false);
 
         let vp_0 = ValuePath::new(&v, None);
-        let vp_1 = ValuePath::new(&v["key1"], Some(vec!["key1".to_string()]));
-        let vp_2 = ValuePath::new(&v["key2"], Some(vec!["key2".to_string()]));
+        let vp_1 = ValuePath::new(&v["key1"], Some(vec!["key1".into()]));
+        let vp_2 = ValuePath::new(&v["key2"], Some(vec!["key2".into()]));
         let vp_1_alt = vp_0.index("key1");
         let vp_2_alt = vp_0.index("key2");
 
@@ -258,14 +358,14 @@ mod tests {
         let vps = v.value_paths(true, false);
 
         let vp_0 = ValuePath::new(&v, None);
-        let vp_1 = ValuePath::new(&v["key1"], Some(vec!["key1".to_string()]));
+        let vp_1 = ValuePath::new(&v["key1"], Some(vec!["key1".into()]));
         let vp_2_1 = ValuePath::new(
             &v["key2"][0],
-            Some(vec!["key2".to_string(), "[0]".to_string()]),
+            Some(vec!["key2".into(), PathSegment::Index(0)]),
         );
         let vp_2_2 = ValuePath::new(
             &v["key2"][1],
-            Some(vec!["key2".to_string(), "[1]".to_string()]),
+            Some(vec!["key2".into(), PathSegment::Index(1)]),
         );
         let vp_1_alt = vp_0.index("key1");
         let vp_2_1_alt = vp_0.index("key2").index(0);
@@ -280,19 +380,115 @@ mod tests {
         let v = json!({"key1": "value1", "key2": ["a", "b"]});
         let vps = v.value_paths(false, true);
 
-        let vp_1 = ValuePath::new(&v["key1"], Some(vec!["key1".to_string()]));
+        let vp_1 = ValuePath::new(&v["key1"], Some(vec!["key1".into()]));
         let vp_2_1 = ValuePath::new(
             &v["key2"][0],
-            Some(vec!["key2".to_string(), "[*]".to_string()]),
+            Some(vec!["key2".into(), PathSegment::AnyIndex]),
         );
         let vp_2_2 = ValuePath::new(
             &v["key2"][1],
-            Some(vec!["key2".to_string(), "[*]".to_string()]),
+            Some(vec!["key2".into(), PathSegment::AnyIndex]),
         );
 
         assert_eq!(vps, vec![vp_1, vp_2_1, vp_2_2]);
     }
 
+    #[test]
+    fn parse_valuepaths_filtered() {
+        let v = json!({"id": 1, "context": {"a": 1, "b": {"c": 2}}, "data": {"x": [1], "y": 2}});
+        let jsonpaths = |include: &[&str], exclude: &[&str]| -> Vec<String> {
+            let patterns =
+                |patterns: &[&str]| patterns.iter().map(|p| p.parse().unwrap()).collect();
+            let options = WalkOptions {
+                filter: PathFilter::new(patterns(include), patterns(exclude)),
+                ..Default::default()
+            };
+            v.value_paths_with(&options)
+                .iter()
+                .map(|vp| vp.jsonpath())
+                .collect()
+        };
+
+        assert_eq!(
+            jsonpaths(&[], &["$.context"]),
+            ["$.data.x", "$.data.y", "$.id"]
+        );
+        assert_eq!(jsonpaths(&["$.data"], &[]), ["$.data.x", "$.data.y"]);
+        assert_eq!(jsonpaths(&["$.data"], &["*.y"]), ["$.data.x"]);
+        assert_eq!(
+            jsonpaths(&[r"regex:\.(a|c)$"], &[]),
+            ["$.context.a", "$.context.b.c"]
+        );
+    }
+
+    #[test]
+    fn parse_valuepaths_max_depth() {
+        let v = json!({"a": {"b": {"c": 1}, "d": [1, 2]}, "e": 1});
+        let options = |max_depth| WalkOptions {
+            inspect_arrays: true,
+            max_depth: Some(max_depth),
+            ..Default::default()
+        };
+        assert_eq!(
+            v.json_paths_types_with(&options(1)),
+            IndexMap::from([
+                ("$.a".to_string(), "Object".to_string()),
+                ("$.e".to_string(), "Number".to_string()),
+            ])
+        );
+        assert_eq!(
+            v.json_paths_types_with(&options(2)),
+            IndexMap::from([
+                ("$.a.b".to_string(), "Object".to_string()),
+                ("$.a.d".to_string(), "Array".to_string()),
+                ("$.e".to_string(), "Number".to_string()),
+            ])
+        );
+        assert_eq!(v.json_paths_with(&options(0)), vec!["$".to_string()]);
+    }
+
+    #[test]
+    fn json_depth() {
+        assert_eq!(json!(1).depth(), 0);
+        assert_eq!(json!({}).depth(), 0);
+        assert_eq!(json!({"a": 1}).depth(), 1);
+        assert_eq!(json!({"a": [1], "b": {"c": {}}}).depth(), 2);
+        assert_eq!(json!([[[null]]]).depth(), 3);
+    }
+
+    #[test]
+    fn split_jsonpath_round_trip() {
+        let v = json!({"key1": "value1", "key2": {"subkey1": [{"a": 1}, [2]]}});
+        for vp in v.value_paths(true, false) {
+            assert_eq!(split_jsonpath(&vp.jsonpath()), vp.path);
+        }
+        assert!(split_jsonpath("$").is_empty());
+    }
+
+    #[test]
+    fn awkward_keys_round_trip_as_queries() {
+        let v = json!({
+            "a.b": 1,
+            "[0]": 2,
+            "with space": {"it's": 3, "back\\slash": [4]},
+            "*": 5,
+            "1st": 6,
+            "": 7,
+            "new\nline": 8,
+            "ünï": 9,
+        });
+        for vp in v.value_paths(true, false) {
+            let jsonpath = vp.jsonpath();
+            let query = serde_json_path::JsonPath::parse(&jsonpath).unwrap();
+            assert_eq!(
+                query.query(&v).exactly_one().unwrap(),
+                vp.value,
+                "{jsonpath}"
+            );
+            assert_eq!(split_jsonpath(&jsonpath), vp.path);
+        }
+    }
+
     #[test]
     fn typical_parse_json_paths() {
         let v = json!({"key1": "value1", "key2": {"subkey1": "value1"}});
@@ -0,0 +1,117 @@
+use std::str::FromStr;
+
+use regex::Regex;
+
+/// Pattern matched against the JSONpaths produced by [`super::ValuePath::jsonpath`].
+///
+/// Either a glob, where `*` matches any run of characters and `?` any single character,
+/// or a regular expression when prefixed with `regex:`
+#[derive(Debug, Clone)]
+pub enum PathPattern {
+    Glob(String),
+    Regex(Regex),
+}
+
+impl PathPattern {
+    pub fn matches(&self, jsonpath: &str) -> bool {
+        match self {
+            Self::Glob(glob) => glob_matches(glob, jsonpath),
+            Self::Regex(regex) => regex.is_match(jsonpath),
+        }
+    }
+}
+
+impl FromStr for PathPattern {
+    type Err = regex::Error;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        match pattern.strip_prefix("regex:") {
+            Some(regex) => Ok(Self::Regex(Regex::new(regex)?)),
+            None => Ok(Self::Glob(pattern.to_string())),
+        }
+    }
+}
+
+/// Matches the whole of `text` against a glob supporting `*` and `?` wildcards
+fn glob_matches(glob: &str, text: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut g, mut t) = (0, 0);
+    // Position of the last `*` and the text position it was tried against, to backtrack to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match glob.get(g) {
+            Some('*') => {
+                backtrack = Some((g, t));
+                g += 1;
+            }
+            Some('?') => {
+                g += 1;
+                t += 1;
+            }
+            Some(c) if *c == text[t] => {
+                g += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_g, star_t)) => {
+                    g = star_g + 1;
+                    t = star_t + 1;
+                    backtrack = Some((star_g, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|c| *c == '*')
+}
+
+/// Include/exclude rules deciding which paths are walked and reported
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    /// When not empty, only paths matching (or below a path matching) one of these are reported
+    pub include: Vec<PathPattern>,
+    /// Paths matching any of these are skipped along with everything below them
+    pub exclude: Vec<PathPattern>,
+}
+
+impl PathFilter {
+    pub fn new(include: Vec<PathPattern>, exclude: Vec<PathPattern>) -> Self {
+        Self { include, exclude }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn includes(&self, jsonpath: &str) -> bool {
+        self.include.iter().any(|pattern| pattern.matches(jsonpath))
+    }
+
+    pub fn excludes(&self, jsonpath: &str) -> bool {
+        self.exclude.iter().any(|pattern| pattern.matches(jsonpath))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_matches("$.context.*", "$.context.a.b"));
+        assert!(glob_matches("$.a[*]", "$.a[0]"));
+        assert!(glob_matches("$.a?c", "$.abc"));
+        assert!(glob_matches("*.id", "$.user.id"));
+        assert!(!glob_matches("$.context", "$.context.a"));
+        assert!(!glob_matches("*.id", "$.user.identity"));
+    }
+
+    #[test]
+    fn regex_patterns() {
+        let pattern: PathPattern = r"regex:^\$\.context\.".parse().unwrap();
+        assert!(pattern.matches("$.context.a"));
+        assert!(!pattern.matches("$.a.context.b"));
+        assert!("regex:(".parse::<PathPattern>().is_err());
+    }
+}
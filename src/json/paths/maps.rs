@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use dashmap::DashMap;
+use regex::Regex;
+use serde_json::Map;
+
+use super::{PathPattern, PathSegment, Value, split_jsonpath};
+
+/// Distinct keys an object path may have across records before being treated as a map
+/// when detecting maps, unless set otherwise
+pub const DEFAULT_MAP_THRESHOLD: usize = 1000;
+
+/// Keys that look like data rather than field names: numbers, UUIDs, dates, hashes and
+/// identifiers containing a run of digits, e.g. `u123` or `SKU-00042`
+static DYNAMIC_KEY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?x)^(?:
+            -?\d+
+            | [[:xdigit:]]{8}-[[:xdigit:]]{4}-[[:xdigit:]]{4}-[[:xdigit:]]{4}-[[:xdigit:]]{12}
+            | \d{4}-\d{2}-\d{2}.*
+            | [[:xdigit:]]{16,}
+            | .*\d{3,}.*
+        )$",
+    )
+    .unwrap()
+});
+
+/// Whether an object key looks like data (an ID, date, ...) rather than a field name
+pub fn is_dynamic_key(key: &str) -> bool {
+    DYNAMIC_KEY.is_match(key)
+}
+
+/// Distinct keys seen at an object path, until there are too many and it is known to be a map
+#[derive(Debug, Clone)]
+enum DistinctKeys {
+    Keys(HashSet<String>),
+    Map,
+}
+
+/// Decides which objects are map-like, so that their keys are collapsed to [`PathSegment::AnyKey`]
+/// rather than each producing their own paths.
+///
+/// Objects are maps when their path matches one of the `patterns`, or when detecting, if all
+/// of their keys look like data (see [`is_dynamic_key`]) or their path has had more than
+/// `threshold` distinct keys across the records walked so far.
+/// Safe to share between threads
+#[derive(Debug, Clone, Default)]
+pub struct MapDetector {
+    patterns: Vec<PathPattern>,
+    threshold: Option<usize>,
+    distinct_keys: DashMap<String, DistinctKeys>,
+}
+
+impl MapDetector {
+    pub fn new(patterns: Vec<PathPattern>, threshold: Option<usize>) -> Self {
+        Self {
+            patterns,
+            threshold,
+            distinct_keys: DashMap::new(),
+        }
+    }
+
+    /// Whether any objects can be treated as maps, i.e. there is anything to check
+    pub fn is_active(&self) -> bool {
+        !self.patterns.is_empty() || self.threshold.is_some()
+    }
+
+    /// Whether the object `map` found at `jsonpath` should have its keys collapsed,
+    /// recording its keys towards the distinct key threshold
+    pub fn is_map(&self, jsonpath: &str, map: &Map<String, Value>) -> bool {
+        if self
+            .patterns
+            .iter()
+            .any(|pattern| pattern.matches(jsonpath))
+        {
+            return true;
+        }
+        let Some(threshold) = self.threshold else {
+            return false;
+        };
+        if !map.is_empty() && map.keys().all(|key| is_dynamic_key(key)) {
+            return true;
+        }
+
+        let mut distinct_keys = self
+            .distinct_keys
+            .entry(jsonpath.to_string())
+            .or_insert_with(|| DistinctKeys::Keys(HashSet::new()));
+        match distinct_keys.value_mut() {
+            DistinctKeys::Map => true,
+            DistinctKeys::Keys(keys) => {
+                for key in map.keys() {
+                    if !keys.contains(key) {
+                        keys.insert(key.to_owned());
+                    }
+                }
+                if keys.len() > threshold {
+                    *distinct_keys = DistinctKeys::Map;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    /// Whether the object path was found to be a map by passing the distinct key threshold
+    fn passed_threshold(&self, jsonpath: &str) -> bool {
+        self.distinct_keys
+            .get(jsonpath)
+            .is_some_and(|keys| matches!(keys.value(), DistinctKeys::Map))
+    }
+
+    /// Rewrites a path reported before its parent was found to be a map by the distinct key
+    /// threshold, e.g. `$.scores.u1` to `$.scores.*`.
+    /// Returns `None` when there is nothing to collapse
+    pub fn collapse(&self, jsonpath: &str) -> Option<String> {
+        // Only objects passing the threshold can have been reported uncollapsed
+        self.threshold?;
+        let mut collapsed = String::from("$");
+        let mut changed = false;
+        for segment in split_jsonpath(jsonpath) {
+            let segment = match segment {
+                PathSegment::Key(_) if self.passed_threshold(&collapsed) => {
+                    changed = true;
+                    PathSegment::AnyKey
+                }
+                segment => segment,
+            };
+            collapsed.push_str(&segment.to_string());
+        }
+        changed.then_some(collapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn object(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => panic!("not an object"),
+        }
+    }
+
+    #[test]
+    fn dynamic_keys() {
+        for key in [
+            "123",
+            "u123",
+            "SKU-00042",
+            "2024-01-31",
+            "2024-01-31T12:00:00Z",
+            "9b2f6d0e-3c1a-4f7e-8d5b-1a2b3c4d5e6f",
+            "5f1d7c2e9a4b3c8d",
+        ] {
+            assert!(is_dynamic_key(key), "{key}");
+        }
+        for key in ["name", "address2", "utf8", "user_id", "deadbeef"] {
+            assert!(!is_dynamic_key(key), "{key}");
+        }
+    }
+
+    #[test]
+    fn manual_map_paths() {
+        let detector = MapDetector::new(vec!["$.scores".parse().unwrap()], None);
+        assert!(detector.is_active());
+        let scores = object(json!({"alice": 1, "bob": 2}));
+        assert!(detector.is_map("$.scores", &scores));
+        assert!(!detector.is_map("$.other", &scores));
+        assert_eq!(detector.collapse("$.scores.alice"), None);
+    }
+
+    #[test]
+    fn distinct_key_threshold() {
+        let detector = MapDetector::new(vec![], Some(2));
+        assert!(detector.is_map("$.ids", &object(json!({"u123": 1}))));
+        assert!(!detector.is_map("$.names", &object(json!({"alice": 1, "bob": 2}))));
+        assert_eq!(detector.collapse("$.names.alice"), None);
+        assert!(detector.is_map("$.names", &object(json!({"carol": 1}))));
+        assert!(detector.is_map("$.names", &object(json!({"alice": 1}))));
+        assert_eq!(
+            detector.collapse("$.names.alice.age"),
+            Some("$.names.*.age".to_string())
+        );
+        assert_eq!(detector.collapse("$.names.*.age"), None);
+        assert_eq!(detector.collapse("$.ids.u123"), None);
+    }
+}
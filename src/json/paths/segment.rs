@@ -0,0 +1,313 @@
+use std::fmt::{self, Write};
+
+use clap::ValueEnum;
+
+/// Component of the location of a value within its root parent JSON
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    /// Object member, `.key` or `['awkward key']`
+    Key(String),
+    /// Array element, `[0]`
+    Index(usize),
+    /// Any array element, `[*]`, as walked with `inspect_arrays`
+    AnyIndex,
+    /// Any member of a map-like object, `.*`
+    AnyKey,
+}
+
+impl PathSegment {
+    /// Segment as shown on its own, without the leading `.` of a member name,
+    /// e.g. `key`, `['awkward key']`, `[0]`
+    pub fn name(&self) -> String {
+        let segment = self.to_string();
+        match segment.strip_prefix('.') {
+            Some(name) => name.to_string(),
+            None => segment,
+        }
+    }
+}
+
+impl From<&str> for PathSegment {
+    fn from(key: &str) -> Self {
+        Self::Key(key.to_string())
+    }
+}
+
+impl From<usize> for PathSegment {
+    fn from(index: usize) -> Self {
+        Self::Index(index)
+    }
+}
+
+/// Formats the segment as normalized JSONPath (RFC 9535), using the `.key` shorthand where
+/// the key allows and bracket notation otherwise, so that paths parse back unambiguously
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(key) if is_shorthand_name(key) => write!(f, ".{key}"),
+            Self::Key(key) => {
+                f.write_str("['")?;
+                for c in key.chars() {
+                    match c {
+                        '\'' => f.write_str("\\'")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\u{8}' => f.write_str("\\b")?,
+                        '\u{c}' => f.write_str("\\f")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\r' => f.write_str("\\r")?,
+                        '\t' => f.write_str("\\t")?,
+                        c if c < ' ' => write!(f, "\\u{:04x}", c as u32)?,
+                        c => f.write_char(c)?,
+                    }
+                }
+                f.write_str("']")
+            }
+            Self::Index(index) => write!(f, "[{index}]"),
+            Self::AnyIndex => f.write_str("[*]"),
+            Self::AnyKey => f.write_str(".*"),
+        }
+    }
+}
+
+/// Whether `key` can be written as `.key` in JSONPath (RFC 9535 `member-name-shorthand`)
+fn is_shorthand_name(key: &str) -> bool {
+    let is_name_first = |c: char| c.is_ascii_alphabetic() || c == '_' || !c.is_ascii();
+    let mut chars = key.chars();
+    chars.next().is_some_and(is_name_first) && chars.all(|c| is_name_first(c) || c.is_ascii_digit())
+}
+
+/// Notations paths can be reported in
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PathStyle {
+    /// Normalized JSONPath, `$.a.b[0]`, usable as a `--jsonpath` query
+    #[default]
+    Jsonpath,
+    /// JSON Pointer (RFC 6901), `/a/b/0`, with `*` for wildcards
+    Pointer,
+    /// Plain dotted names, `a.b.0`, ambiguous for keys containing dots
+    Dotted,
+}
+
+impl PathStyle {
+    /// Writes out the path made up of `segments` in this notation
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use analyse_json::json::paths::{PathSegment, PathStyle};
+    ///
+    /// let segments = [PathSegment::from("a/b"), PathSegment::Index(0)];
+    /// assert_eq!(PathStyle::Jsonpath.format(&segments), "$['a/b'][0]");
+    /// assert_eq!(PathStyle::Pointer.format(&segments), "/a~1b/0");
+    /// assert_eq!(PathStyle::Dotted.format(&segments), "a/b.0");
+    /// ```
+    pub fn format(self, segments: &[PathSegment]) -> String {
+        let mut path = String::new();
+        match self {
+            Self::Jsonpath => {
+                path.push('$');
+                for segment in segments {
+                    write!(path, "{segment}").unwrap();
+                }
+            }
+            Self::Pointer => {
+                for segment in segments {
+                    path.push('/');
+                    path.push_str(&self.segment_name(segment));
+                }
+            }
+            Self::Dotted => {
+                let names: Vec<String> = segments.iter().map(|s| self.segment_name(s)).collect();
+                path = names.join(".");
+            }
+        }
+        path
+    }
+
+    /// Converts a JSONpath produced by [`super::ValuePath::jsonpath`] to this notation
+    pub fn from_jsonpath(self, jsonpath: &str) -> String {
+        match self {
+            Self::Jsonpath => jsonpath.to_string(),
+            style => style.format(&split_jsonpath(jsonpath)),
+        }
+    }
+
+    /// Name of each segment of a path written in this notation, as shown in a tree
+    pub fn split_names(self, path: &str) -> Vec<String> {
+        match self {
+            Self::Jsonpath => split_jsonpath(path).iter().map(PathSegment::name).collect(),
+            Self::Pointer => path.split('/').skip(1).map(str::to_string).collect(),
+            Self::Dotted if path.is_empty() => Vec::new(),
+            Self::Dotted => path.split('.').map(str::to_string).collect(),
+        }
+    }
+
+    /// Segment as a single component of a pointer or dotted path
+    fn segment_name(self, segment: &PathSegment) -> String {
+        match segment {
+            PathSegment::Key(key) if self == Self::Pointer => {
+                key.replace('~', "~0").replace('/', "~1")
+            }
+            PathSegment::Key(key) => key.to_owned(),
+            PathSegment::Index(index) => index.to_string(),
+            PathSegment::AnyIndex | PathSegment::AnyKey => "*".to_string(),
+        }
+    }
+}
+
+/// Splits a JSONpath produced by [`super::ValuePath::jsonpath`] back into its segments,
+/// the inverse of [`super::ValuePath::jsonpath`]
+///
+/// # Examples
+///
+/// ```
+/// use analyse_json::json::paths::{PathSegment, split_jsonpath};
+///
+/// assert_eq!(
+///     split_jsonpath("$.key[*]['sub.key']"),
+///     vec![
+///         PathSegment::from("key"),
+///         PathSegment::AnyIndex,
+///         PathSegment::from("sub.key"),
+///     ]
+/// );
+/// ```
+pub fn split_jsonpath(jsonpath: &str) -> Vec<PathSegment> {
+    let jsonpath = jsonpath.strip_prefix('$').unwrap_or(jsonpath);
+    let mut segments = Vec::new();
+    let mut chars = jsonpath.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '[' {
+            let segment = match chars.peek() {
+                Some(&quote @ ('\'' | '"')) => {
+                    chars.next();
+                    PathSegment::Key(unescape_name(&mut chars, quote))
+                }
+                _ => {
+                    let mut inner = String::new();
+                    for c in chars.by_ref() {
+                        if c == ']' {
+                            break;
+                        }
+                        inner.push(c);
+                    }
+                    match inner.parse() {
+                        Ok(index) => PathSegment::Index(index),
+                        Err(_) => PathSegment::AnyIndex,
+                    }
+                }
+            };
+            segments.push(segment);
+            continue;
+        }
+        let mut key = String::new();
+        if c != '.' {
+            key.push(c);
+        }
+        while let Some(&c) = chars.peek() {
+            if c == '.' || c == '[' {
+                break;
+            }
+            key.push(c);
+            chars.next();
+        }
+        segments.push(if key == "*" {
+            PathSegment::AnyKey
+        } else {
+            PathSegment::Key(key)
+        });
+    }
+    segments
+}
+
+/// Reads a quoted bracket notation name up to the closing `quote` and `]`, resolving escapes
+fn unescape_name(chars: &mut impl Iterator<Item = char>, quote: char) -> String {
+    let mut name = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('b') => name.push('\u{8}'),
+                Some('f') => name.push('\u{c}'),
+                Some('n') => name.push('\n'),
+                Some('r') => name.push('\r'),
+                Some('t') => name.push('\t'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    if let Some(c) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        name.push(c);
+                    }
+                }
+                Some(c) => name.push(c),
+                None => break,
+            },
+            c if c == quote => {
+                // Closing `]`
+                chars.next();
+                break;
+            }
+            c => name.push(c),
+        }
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_notation() {
+        let jsonpath = |segment: PathSegment| segment.to_string();
+        assert_eq!(jsonpath("key_1".into()), ".key_1");
+        assert_eq!(jsonpath("clé".into()), ".clé");
+        assert_eq!(jsonpath("a.b".into()), "['a.b']");
+        assert_eq!(jsonpath("[0]".into()), "['[0]']");
+        assert_eq!(jsonpath("1st".into()), "['1st']");
+        assert_eq!(jsonpath("".into()), "['']");
+        assert_eq!(jsonpath("*".into()), "['*']");
+        assert_eq!(jsonpath("it's \\ \n".into()), r"['it\'s \\ \n']");
+        assert_eq!(jsonpath(PathSegment::Index(3)), "[3]");
+        assert_eq!(jsonpath(PathSegment::AnyIndex), "[*]");
+        assert_eq!(jsonpath(PathSegment::AnyKey), ".*");
+        assert_eq!(PathSegment::from("a b").name(), "['a b']");
+        assert_eq!(PathSegment::from("ab").name(), "ab");
+    }
+
+    #[test]
+    fn path_styles() {
+        let jsonpath = "$['a/b~c'].d[*][2].*";
+        assert_eq!(
+            PathStyle::Pointer.from_jsonpath(jsonpath),
+            "/a~1b~0c/d/*/2/*"
+        );
+        assert_eq!(PathStyle::Dotted.from_jsonpath(jsonpath), "a/b~c.d.*.2.*");
+        assert_eq!(PathStyle::Jsonpath.from_jsonpath(jsonpath), jsonpath);
+        assert_eq!(PathStyle::Pointer.from_jsonpath("$"), "");
+        assert_eq!(
+            PathStyle::Pointer.split_names("/a~1b~0c/d/*"),
+            ["a~1b~0c", "d", "*"]
+        );
+        assert_eq!(PathStyle::Dotted.split_names("a.b"), ["a", "b"]);
+        assert!(PathStyle::Dotted.split_names("").is_empty());
+    }
+
+    #[test]
+    fn split_awkward_jsonpaths() {
+        let segments = vec![
+            PathSegment::from("a.b"),
+            PathSegment::Index(0),
+            PathSegment::from("[0]"),
+            PathSegment::AnyKey,
+            PathSegment::from("it's \\ \n \u{1}"),
+            PathSegment::AnyIndex,
+            PathSegment::from("*"),
+            PathSegment::from("c"),
+        ];
+        let jsonpath: String = segments.iter().map(ToString::to_string).collect();
+        assert_eq!(split_jsonpath(&format!("${jsonpath}")), segments);
+        assert_eq!(
+            split_jsonpath(r#"$["double \"quoted\""]"#),
+            vec![PathSegment::from(r#"double "quoted""#)]
+        );
+    }
+}
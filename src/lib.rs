@@ -1,18 +1,35 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use clap::CommandFactory;
-use clap::Parser;
 use clap::builder::Styles;
 use clap::builder::styling::AnsiColor;
+use clap::{Parser, Subcommand};
 use clap_complete::Shell;
 use glob::glob;
 use grep_cli::is_readable_stdin;
 use humantime::format_duration;
-use json::ndjson::JSONStats;
+use indexmap::IndexSet;
+use json::IndexMap;
+use json::ndjson::duplicates::DEFAULT_MAX_EXACT_HASHES;
+use json::ndjson::errors::NDJSONError;
+use json::ndjson::sample::{SampleRate, SamplingMethod};
+use json::ndjson::stats::{
+    DEFAULT_MAX_ERROR_SAMPLES, LineIdLimits, OutputFormat, OutputOptions, SortBy, SortOrder,
+};
+use json::ndjson::{
+    Budget, BudgetExceeded, CleanCopy, GroupBy, JSONStats, LineRange, LineWindow, NamedQuery,
+    Predicate, Quarantine, Sampling, Threshold,
+};
+use json::paths::maps::DEFAULT_MAP_THRESHOLD;
+use json::paths::{MapDetector, PathFilter, PathPattern, PathStyle, WalkOptions};
 use serde_json_path::JsonPath;
-use std::io;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Instant;
 
+use crate::io_helpers::buf_reader::get_bufreader;
 use crate::json::ndjson;
 
 mod io_helpers;
@@ -26,8 +43,60 @@ fn styles() -> Styles {
         .placeholder(AnsiColor::Green.on_default())
 }
 
+const EXIT_CODES_HELP: &str = "Exit codes: 0 success, 1 other failure, 3 input unreadable, \
+    4 --max-bad-lines exceeded (lines not JSON), \
+    5 --max-empty-lines exceeded (JSON records without the queried values)";
+
+/// Failures the process exits with distinct codes for, so CI and ingestion gates can tell
+/// them apart.
+///
+/// The two error budgets exit differently on purpose: bad lines are input that is not JSON,
+/// a broken export or transfer, while empty lines are valid JSON records failing the
+/// validation of their content by `--jsonpath`, which is usually fixed upstream of the data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    Other = 1,
+    /// An input file, stdin or saved stats could not be read
+    InputUnreadable = 3,
+    /// More lines failed to parse than `--max-bad-lines` allows, including the lines with
+    /// duplicate keys under `--duplicate-keys-bad`
+    BudgetExceeded = 4,
+    /// More parsed records returned empty for a query than `--max-empty-lines` allows
+    ValidationFailed = 5,
+}
+
+impl Failure {
+    /// The kind of failure of an `error` returned by [`run`]
+    pub fn of(error: &anyhow::Error) -> Self {
+        if let Some(exceeded) = error.downcast_ref::<BudgetExceeded>() {
+            return match exceeded {
+                BudgetExceeded::BadLines { .. } => Self::BudgetExceeded,
+                BudgetExceeded::EmptyLines { .. } => Self::ValidationFailed,
+            };
+        }
+        if error.downcast_ref::<InputUnreadable>().is_some() {
+            return Self::InputUnreadable;
+        }
+        Self::Other
+    }
+
+    pub fn exit_code(self) -> i32 {
+        self as i32
+    }
+}
+
+/// Context of the errors reading the input, for [`Failure::InputUnreadable`]
+#[derive(Debug)]
+struct InputUnreadable(String);
+
+impl fmt::Display for InputUnreadable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
 #[derive(Parser, Default, PartialEq, Eq)]
-#[clap(author, version, about, long_about = None, styles = styles())]
+#[clap(author, version, about, long_about = None, styles = styles(), after_help = EXIT_CODES_HELP)]
 pub struct Cli {
     /// File to process, expected to contain a single JSON object or Newline Delimited (ND) JSON objects
     #[clap(value_parser)]
@@ -37,13 +106,51 @@ pub struct Cli {
     #[clap(short, long)]
     glob: Option<String>,
 
-    /// Limit inspection to the first n lines
+    /// Limit inspection to the first n lines, after any skipped
     #[clap(short = 'n', long)]
     lines: Option<usize>,
 
-    /// JSONpath query to filter/limit the inspection to e.g. `'$.a_key.an_array[0]'`
+    /// Skip the first n lines
+    #[clap(long, value_name = "N")]
+    skip: Option<usize>,
+
+    /// Only inspect these lines, counting from 1, e.g. `1000..2000`, `1000..=1999` or `1000..`
+    #[clap(long, value_name = "A..B", conflicts_with = "skip")]
+    range: Option<LineRange>,
+
+    /// Only inspect the last n lines (of those selected by the options above)
+    #[clap(long, value_name = "N")]
+    tail: Option<usize>,
+
+    /// Inspect a random sample of the lines, keeping each with this probability, e.g. `0.1`
+    #[clap(long, value_name = "P", conflicts_with = "sample")]
+    sample_rate: Option<SampleRate>,
+
+    /// Inspect a random sample of n lines, chosen uniformly from the whole input
+    #[clap(long, value_name = "N")]
+    sample: Option<usize>,
+
+    /// Seed for `--sample-rate` and `--sample`, to reproduce a sample. Random by default, the
+    /// seed used is reported with the stats
     #[clap(long)]
-    jsonpath: Option<String>,
+    seed: Option<u64>,
+
+    /// JSONpath query to filter/limit the inspection to e.g. `'$.a_key.an_array[0]'`.
+    /// Can be repeated, optionally naming each query with `name=query`, to report on each
+    /// query separately from a single read
+    #[clap(long, value_name = "[NAME=]QUERY")]
+    jsonpath: Vec<String>,
+
+    /// Only analyse the records matching this predicate, e.g. `'$.type == "purchase"'`.
+    /// Supports `==`, `!=`, `<`, `<=`, `>`, `>=`, `=~` (regex), a path alone for existence and
+    /// `!path` for absence. Can be repeated, records must match all of them
+    #[clap(long = "where", value_name = "PREDICATE")]
+    where_: Vec<String>,
+
+    /// Also report stats separately for each value found at this JSONpath, e.g.
+    /// `'$.event_type'`, alongside those of all the records
+    #[clap(long, value_name = "PATH")]
+    group_by: Option<String>,
 
     /// Walk the elements of arrays grouping elements paths together under `$.path.to.array[*]`?
     /// See also `--explode-arrays`
@@ -56,6 +163,32 @@ pub struct Cli {
     #[clap(long, conflicts_with = "inspect_arrays")]
     explode_arrays: bool,
 
+    /// Only report paths matching this glob (`*` and `?` wildcards), or regular expression
+    /// when prefixed with `regex:`, along with everything below them. Can be repeated
+    #[clap(long, value_name = "PATTERN")]
+    include_paths: Vec<String>,
+
+    /// Skip walking paths matching this glob (`*` and `?` wildcards), or regular expression
+    /// when prefixed with `regex:`, along with everything below them. Can be repeated
+    #[clap(long, value_name = "PATTERN")]
+    exclude_paths: Vec<String>,
+
+    /// Treat objects at paths matching this glob (`*` and `?` wildcards), or regular
+    /// expression when prefixed with `regex:`, as maps, collapsing their keys to `*` like
+    /// `--inspect-arrays` does for array indices. Can be repeated
+    #[clap(long, value_name = "PATTERN")]
+    map_path: Vec<String>,
+
+    /// Detect map-like objects, whose keys all look like IDs, dates or hashes, or that have
+    /// more than `--map-threshold` distinct keys across records, collapsing their keys to `*`
+    #[clap(long)]
+    detect_maps: bool,
+
+    /// Distinct keys an object may have across records before `--detect-maps` treats it as a
+    /// map [default: 1000]
+    #[clap(long, requires = "detect_maps")]
+    map_threshold: Option<usize>,
+
     /// Include combined results for all files when using glob
     #[clap(long)]
     merge: bool,
@@ -64,102 +197,632 @@ pub struct Cli {
     #[clap(long)]
     parallel: bool,
 
+    /// Format of the output, defaults to `text` when writing to a terminal and `json` otherwise
+    #[clap(value_enum, short, long)]
+    output: Option<OutputFormat>,
+
+    /// Show the discovered structure as an indented tree
+    #[clap(long, conflicts_with = "output")]
+    tree: bool,
+
+    /// Stop walking below this depth, reporting deeper objects and arrays as a single path.
+    /// Also collapses the `--tree` below this depth
+    #[clap(long)]
+    max_depth: Option<usize>,
+
+    /// Notation to report the paths in [default: jsonpath]
+    #[clap(value_enum, long)]
+    path_style: Option<PathStyle>,
+
+    /// Order the rows of the tabular outputs by this column rather than by first appearance
+    #[clap(value_enum, long)]
+    sort_by: Option<SortBy>,
+
+    /// Direction of `--sort-by`, defaults to `asc` for paths and `desc` for counts
+    #[clap(value_enum, long, requires = "sort_by")]
+    sort_order: Option<SortOrder>,
+
     /// Silence progress and timing output
     #[clap(short, long)]
     quiet: bool,
 
+    /// Attempt to repair the lines that fail to parse: byte order marks, single quotes,
+    /// trailing commas, NaN and Infinity, records glued on one line and records split across
+    /// two lines. Repaired records are analysed and counted by repair in the stats
+    #[clap(long)]
+    repair: bool,
+
+    /// Check the objects for keys given more than once, of which only the last value is
+    /// otherwise kept, reporting the lines with duplicate keys by path of the key. All the lines
+    /// read are checked, whatever the `--where` predicates and queries
+    #[clap(long)]
+    duplicate_keys: bool,
+
+    /// Count the lines with duplicate keys as bad lines rather than analysing them
+    #[clap(long, requires = "duplicate_keys")]
+    duplicate_keys_bad: bool,
+
+    /// Count the records the same as an earlier record, comparing whole records or only their
+    /// values at the `--duplicates-on` paths, with a sample of the ids of the duplicates. The
+    /// records of each query are compared once filtered by the `--where` predicates
+    #[clap(long)]
+    duplicates: bool,
+
+    /// Compare the records on their values at this JSONpath for `--duplicates`, e.g.
+    /// `'$.event_id'`. Can be repeated
+    #[clap(long, value_name = "PATH", requires = "duplicates")]
+    duplicates_on: Vec<String>,
+
+    /// Count the distinct values of each scalar path outside of arrays and maps, suggesting
+    /// those with a distinct value in every record as candidate primary keys. The paths are
+    /// walked as set by `--include-paths`, `--exclude-paths`, `--max-depth` and the map options
+    #[clap(long)]
+    candidate_keys: bool,
+
+    /// Count the distinct records for `--duplicates`, and distinct values of the paths for
+    /// `--candidate-keys`, exactly up to this many hashes between all the paths, estimating
+    /// them beyond in bounded memory [default: 1000000]
+    #[clap(long, value_name = "N")]
+    max_exact_hashes: Option<usize>,
+
+    /// Fail when more lines than this fail to parse, as a number of lines or a percentage of
+    /// the lines read, e.g. `100` or `2.5%`
+    #[clap(long, value_name = "N|%")]
+    max_bad_lines: Option<Threshold>,
+
+    /// Fail when more records than this return empty for a `--jsonpath` query, as a number of
+    /// records or a percentage of the lines read
+    #[clap(long, value_name = "N|%")]
+    max_empty_lines: Option<Threshold>,
+
+    /// Stop reading at the first line over a `--max-bad-lines` or `--max-empty-lines` number,
+    /// or at the first bad line when neither is given. Percentages are checked once each input
+    /// is read
+    #[clap(long)]
+    fail_fast: bool,
+
+    /// Keep the ids of at most this many bad lines, and of as many empty lines, in the stats.
+    /// The rest are only counted [default: 100]
+    #[clap(long, value_name = "N")]
+    max_error_samples: Option<usize>,
+
+    /// Compress the ids of consecutive bad or empty lines into ranges, e.g. `1200-1850`
+    #[clap(long)]
+    line_ranges: bool,
+
+    /// Write the lines that fail to parse to this file as they are found, as NDJSON of each
+    /// line with its id and error
+    #[clap(long, value_name = "PATH")]
+    bad_lines_out: Option<PathBuf>,
+
+    /// Write the records returning empty for a `--jsonpath` query to this file as they are
+    /// found, as NDJSON of each record with its id and query
+    #[clap(long, value_name = "PATH")]
+    empty_lines_out: Option<PathBuf>,
+
+    /// Copy the lines that parse as JSON to this file while collecting the stats, gzip
+    /// compressed when it ends with `.gz`
+    #[clap(long, value_name = "PATH")]
+    clean_out: Option<PathBuf>,
+
+    /// Re-serialise the lines copied by `--clean-out` as compact JSON with sorted keys
+    #[clap(long, requires = "clean_out")]
+    canonical: bool,
+
+    /// Save the raw stats to this file (one JSON line per input file) so runs can be
+    /// combined later with the `merge` subcommand
+    #[clap(long)]
+    save_stats: Option<PathBuf>,
+
     /// Output shell completions for the chosen shell to stdout
     #[clap(value_enum, long, id = "SHELL")]
     generate_completions: Option<Shell>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, PartialEq, Eq)]
+pub enum Command {
+    /// Combine stats saved by previous runs with `--save-stats` into overall stats,
+    /// as `--merge` would for a single run
+    Merge {
+        /// Files written by `--save-stats`
+        #[clap(required = true)]
+        stats_files: Vec<PathBuf>,
+    },
 }
 
 impl Cli {
-    fn jsonpath_selector(&self) -> Result<Option<JsonPath>> {
-        let jsonpath_selector = if let Some(jsonpath) = &self.jsonpath {
-            let path = JsonPath::parse(jsonpath)
+    fn queries(&self) -> Result<Vec<NamedQuery>> {
+        let mut queries: Vec<NamedQuery> = Vec::new();
+        for jsonpath in &self.jsonpath {
+            let query: NamedQuery = jsonpath
+                .parse()
                 .with_context(|| format!("Failed to parse jsonpath query string: {jsonpath}"))?;
-            Some(path)
-        } else {
-            None
+            if queries.iter().any(|other| other.name == query.name) {
+                bail!("Duplicate jsonpath query name: {}", query.name);
+            }
+            queries.push(query);
+        }
+        Ok(queries)
+    }
+
+    fn predicates(&self) -> Result<Vec<Predicate>> {
+        self.where_
+            .iter()
+            .map(|predicate| {
+                predicate
+                    .parse()
+                    .with_context(|| format!("Failed to parse --where predicate: {predicate}"))
+            })
+            .collect()
+    }
+
+    fn group_by(&self) -> Result<Option<GroupBy>> {
+        self.group_by
+            .as_deref()
+            .map(|path| {
+                path.parse()
+                    .with_context(|| format!("Failed to parse --group-by path: {path}"))
+            })
+            .transpose()
+    }
+
+    fn duplicates_on(&self) -> Result<Vec<JsonPath>> {
+        self.duplicates_on
+            .iter()
+            .map(|path| {
+                JsonPath::parse(path)
+                    .with_context(|| format!("Failed to parse --duplicates-on path: {path}"))
+            })
+            .collect()
+    }
+
+    fn max_exact_hashes(&self) -> usize {
+        self.max_exact_hashes.unwrap_or(DEFAULT_MAX_EXACT_HASHES)
+    }
+
+    /// The error budget, of no bad lines at all when failing fast without one
+    fn budget(&self) -> Budget {
+        let unbudgeted = self.max_bad_lines.is_none() && self.max_empty_lines.is_none();
+        Budget {
+            max_bad_lines: self
+                .max_bad_lines
+                .or((self.fail_fast && unbudgeted).then_some(Threshold::Count(0))),
+            max_empty_lines: self.max_empty_lines,
+            fail_fast: self.fail_fast,
+        }
+    }
+
+    /// How many ids of the bad and empty lines to keep in the stats
+    fn line_id_limits(&self) -> LineIdLimits {
+        LineIdLimits {
+            max_samples: Some(self.max_error_samples.unwrap_or(DEFAULT_MAX_ERROR_SAMPLES)),
+            ranges: self.line_ranges,
+        }
+    }
+
+    /// The lines to inspect, before any sampling
+    fn line_window(&self) -> LineWindow {
+        let (skip, take) = match self.range {
+            Some(range) => (range.start - 1, range.line_count()),
+            None => (self.skip.unwrap_or(0), None),
         };
-        Ok(jsonpath_selector)
+        LineWindow {
+            skip,
+            take: take.into_iter().chain(self.lines).min(),
+            tail: self.tail,
+        }
+    }
+
+    /// The random sampling of the lines requested, if any
+    fn sampling(&self) -> Option<Sampling> {
+        let method = match (self.sample_rate, self.sample) {
+            (Some(rate), _) => SamplingMethod::Bernoulli { rate },
+            (None, Some(size)) => SamplingMethod::Reservoir { size },
+            (None, None) => return None,
+        };
+        Some(Sampling {
+            method,
+            seed: self.seed.unwrap_or_default(),
+        })
+    }
+
+    fn walk_options(&self) -> Result<WalkOptions> {
+        let parse_patterns = |patterns: &[String]| -> Result<Vec<PathPattern>> {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    pattern
+                        .parse()
+                        .with_context(|| format!("Failed to parse path pattern: {pattern}"))
+                })
+                .collect()
+        };
+        Ok(WalkOptions {
+            explode_arrays: self.explode_arrays,
+            inspect_arrays: self.inspect_arrays,
+            filter: PathFilter::new(
+                parse_patterns(&self.include_paths)?,
+                parse_patterns(&self.exclude_paths)?,
+            ),
+            max_depth: self.max_depth,
+            maps: MapDetector::new(
+                parse_patterns(&self.map_path)?,
+                self.detect_maps
+                    .then(|| self.map_threshold.unwrap_or(DEFAULT_MAP_THRESHOLD)),
+            ),
+        })
     }
 }
 
 /// Wrapper around [`Cli`] to hold derived attributes
 pub struct Settings {
     args: Cli,
-    jsonpath_selector: Option<JsonPath>,
+    queries: Vec<NamedQuery>,
+    predicates: Vec<Predicate>,
+    group_by: Option<GroupBy>,
+    duplicates_on: Vec<JsonPath>,
+    walk_options: WalkOptions,
+    output: OutputOptions,
+    quarantine: Arc<Quarantine>,
+    clean_copy: Option<Arc<CleanCopy>>,
+    budget: Budget,
+    line_id_limits: LineIdLimits,
 }
 
 impl Settings {
-    pub fn init(args: Cli) -> Result<Self> {
-        let jsonpath_selector = args.jsonpath_selector()?;
+    pub fn init(mut args: Cli) -> Result<Self> {
+        if (args.sample_rate.is_some() || args.sample.is_some()) && args.seed.is_none() {
+            // Fixed up front so every file is sampled alike and the seed can be reported
+            args.seed = Some(fastrand::u64(..));
+        }
+        let queries = args.queries()?;
+        let predicates = args.predicates()?;
+        let group_by = args.group_by()?;
+        let duplicates_on = args.duplicates_on()?;
+        let walk_options = args.walk_options()?;
+        let format = if args.tree {
+            OutputFormat::Tree
+        } else {
+            args.output.unwrap_or_else(OutputFormat::detect)
+        };
+        let output = OutputOptions {
+            format,
+            sort_by: args.sort_by,
+            sort_order: args.sort_order,
+            max_width: OutputOptions::terminal_width(),
+            max_depth: args.max_depth,
+            path_style: args.path_style.unwrap_or_default(),
+        };
+        let quarantine = Quarantine::create(
+            args.bad_lines_out.as_deref(),
+            args.empty_lines_out.as_deref(),
+        )
+        .context("Failed to create --bad-lines-out or --empty-lines-out file")?;
+        let clean_copy = args
+            .clean_out
+            .as_deref()
+            .map(|path| CleanCopy::create(path, args.canonical))
+            .transpose()
+            .context("Failed to create --clean-out file")?
+            .map(Arc::new);
+        let budget = args.budget();
+        let line_id_limits = args.line_id_limits();
         Ok(Self {
             args,
-            jsonpath_selector,
+            queries,
+            predicates,
+            group_by,
+            duplicates_on,
+            walk_options,
+            output,
+            quarantine: Arc::new(quarantine),
+            clean_copy,
+            budget,
+            line_id_limits,
         })
     }
+
+    /// Where `--bad-lines-out` and `--empty-lines-out` lines are written, for recording them
+    /// with [`ndjson::errors::collection::Errors::with_quarantine`]
+    pub fn quarantine(&self) -> Arc<Quarantine> {
+        Arc::clone(&self.quarantine)
+    }
+
+    /// Names of the `--jsonpath` queries stats are collected for, `$` for the whole JSON when
+    /// there are none
+    fn query_names(&self) -> impl Iterator<Item = &str> {
+        let whole = self.queries.is_empty().then_some("$");
+        whole
+            .into_iter()
+            .chain(self.queries.iter().map(|query| query.name.as_str()))
+    }
+
+    /// Whether the record `json` matches all of the `--where` predicates
+    fn matches_predicates(&self, json: &json::Value) -> bool {
+        self.predicates
+            .iter()
+            .all(|predicate| predicate.matches(json))
+    }
+
+    /// The `--group-by` group of the record `json`, if grouping
+    fn group_of(&self, json: &json::Value) -> Option<ndjson::Group> {
+        self.group_by.as_ref().map(|group_by| group_by.group(json))
+    }
+
+    /// The error for the record `json` returning empty for a query, keeping the record if
+    /// it is to be written by `--empty-lines-out`
+    fn empty_query(&self, json: &json::Value) -> NDJSONError {
+        let line = self
+            .quarantine
+            .keeps_empty_lines()
+            .then(|| json.to_string());
+        NDJSONError::EmptyQuery { line }
+    }
+
+    /// Name of the `--jsonpath` query of index `query_index`, if any
+    fn query_name(&self, query_index: Option<usize>) -> Option<&str> {
+        query_index
+            .and_then(|i| self.queries.get(i))
+            .map(|query| query.name.as_str())
+    }
+
+    /// Prints a heading between sections of output, skipped for machine readable formats
+    fn print_heading(&self, heading: &str) {
+        if self.output.format.is_human_readable() {
+            println!("{heading}");
+        }
+    }
 }
 
-fn process_ndjson_file_path(settings: &Settings, file_path: &PathBuf) -> Result<ndjson::Stats> {
-    let stats = file_path.json_stats(settings).with_context(|| {
-        format!(
+fn process_ndjson_file_path(
+    settings: &Settings,
+    file_path: &PathBuf,
+) -> Result<Vec<ndjson::FileStats>> {
+    let stats = file_path.json_stats_by_section(settings).with_context(|| {
+        InputUnreadable(format!(
             "Failed to collect stats for JSON file: {}",
             file_path.display()
-        )
+        ))
     })?;
 
-    Ok(stats)
+    Ok(ndjson::FileStats::by_section(
+        file_path.to_string_lossy().into_owned(),
+        stats,
+    ))
+}
+
+/// Prints a single HTML page with a tab for each of the stats
+fn print_html_report(settings: &Settings, file_stats_list: &[ndjson::FileStats]) {
+    let titles: Vec<String> = file_stats_list
+        .iter()
+        .map(ndjson::FileStats::title)
+        .collect();
+    let sections: Vec<(&str, &ndjson::Stats)> = titles
+        .iter()
+        .map(String::as_str)
+        .zip(file_stats_list.iter().map(|file_stats| &file_stats.stats))
+        .collect();
+    print!(
+        "{}",
+        ndjson::stats::html_report(&sections, &settings.output)
+    );
+}
+
+/// Prints the stats of each `--jsonpath` query and `--group-by` group, under a heading when
+/// there are several
+fn print_query_stats(settings: &Settings, file_stats_list: &[ndjson::FileStats]) -> Result<()> {
+    if settings.output.format == OutputFormat::Html && file_stats_list.len() > 1 {
+        print_html_report(settings, file_stats_list);
+        return Ok(());
+    }
+    let grouped = file_stats_list
+        .iter()
+        .any(|file_stats| file_stats.group.is_some());
+    let mut previous_query = None;
+    for file_stats in file_stats_list {
+        if let Some(query) = &file_stats.query {
+            if previous_query != Some(query) {
+                settings.print_heading(&format!("Query '{query}':"));
+            }
+            previous_query = Some(query);
+        }
+        match &file_stats.group {
+            Some(group) => settings.print_heading(&format!("Group {group}:")),
+            None if grouped => settings.print_heading("All Groups:"),
+            None => {}
+        }
+        file_stats.stats.print(&settings.output)?;
+    }
+    Ok(())
+}
+
+/// Sums the stats of all the files, separately for each `--jsonpath` query and
+/// `--group-by` group. Each query's groups are ordered by name, followed by its overall stats
+fn merge_query_stats(
+    settings: &Settings,
+    file_stats_list: &[ndjson::FileStats],
+    title: &str,
+) -> Vec<ndjson::FileStats> {
+    let mut queries: IndexSet<Option<String>> = IndexSet::new();
+    let mut by_section: IndexMap<_, Vec<&ndjson::FileStats>> = IndexMap::new();
+    for file_stats in file_stats_list {
+        queries.insert(file_stats.query.to_owned());
+        by_section
+            .entry((file_stats.query.to_owned(), file_stats.group.to_owned()))
+            .or_default()
+            .push(file_stats);
+    }
+    by_section.sort_by_cached_key(|(query, group), _| {
+        (
+            queries.get_index_of(query),
+            group.is_none(),
+            group.to_owned(),
+        )
+    });
+    by_section
+        .into_iter()
+        .map(|((query, group), file_stats_list)| {
+            let blank = ndjson::Stats::with_line_id_limits(settings.line_id_limits);
+            let mut stats = file_stats_list.into_iter().fold(blank, |acc, x| acc + x);
+            // Maps detected in later files may not have been collapsed in earlier ones
+            stats.collapse_map_paths(&settings.walk_options.maps);
+            ndjson::FileStats {
+                file_path: title.to_string(),
+                query,
+                group,
+                stats,
+            }
+        })
+        .collect()
+}
+
+/// Opens the `--save-stats` file for writing, if requested
+fn saved_stats_writer(settings: &Settings) -> Result<Option<BufWriter<File>>> {
+    let Some(save_path) = &settings.args.save_stats else {
+        return Ok(None);
+    };
+    let file = File::create(save_path)
+        .with_context(|| format!("Failed to create stats save file: {}", save_path.display()))?;
+    Ok(Some(BufWriter::new(file)))
+}
+
+/// Writes `file_stats` as a single line of JSON, the format read back by [`load_saved_stats`]
+fn save_stats(writer: &mut Option<BufWriter<File>>, file_stats: &ndjson::FileStats) -> Result<()> {
+    if let Some(writer) = writer {
+        serde_json::to_writer(&mut *writer, file_stats).context("Failed to serialise stats")?;
+        writeln!(writer).context("Failed to write stats")?;
+        writer.flush().context("Failed to write stats")?;
+    }
+    Ok(())
+}
+
+/// Reads back all of the [`ndjson::FileStats`] written to a `--save-stats` file
+fn load_saved_stats(settings: &Settings, file_path: &PathBuf) -> Result<Vec<ndjson::FileStats>> {
+    let reader = get_bufreader(&settings.args, file_path)?;
+    let mut file_stats_list = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let file_stats = serde_json::from_str(&line)
+            .with_context(|| format!("Failed to parse saved stats on line {}", i + 1))?;
+        file_stats_list.push(file_stats);
+    }
+    Ok(file_stats_list)
+}
+
+fn run_merge(settings: Settings, stats_files: &[PathBuf]) -> Result<()> {
+    let mut file_stats_list = Vec::new();
+    for file_path in stats_files {
+        let saved = load_saved_stats(&settings, file_path).with_context(|| {
+            InputUnreadable(format!(
+                "Failed to load saved stats file: {}",
+                file_path.display()
+            ))
+        })?;
+        file_stats_list.extend(saved);
+    }
+    let overall_file_stats = merge_query_stats(&settings, &file_stats_list, "Overall Stats");
+    print_query_stats(&settings, &overall_file_stats).context("Failed to print combined stats")?;
+    check_budget(&settings, &overall_file_stats).context("Combined stats over the error budget")?;
+    Ok(())
 }
 
 fn run_stdin(settings: Settings) -> Result<()> {
+    let mut saved_stats = saved_stats_writer(&settings)?;
     let stats = io::stdin()
-        .json_stats(&settings)
-        .context("Failed to collect stats for JSON stdin")?;
+        .json_stats_by_section(&settings)
+        .context(InputUnreadable(
+            "Failed to collect stats for JSON stdin".to_string(),
+        ))?;
+    let file_stats_list = ndjson::FileStats::by_section("<stdin>".to_string(), stats);
 
-    stats.print()?;
+    print_query_stats(&settings, &file_stats_list)?;
+    for file_stats in &file_stats_list {
+        save_stats(&mut saved_stats, file_stats)?;
+    }
+    check_budget(&settings, &file_stats_list).context("Stdin over the error budget")?;
     Ok(())
 }
 
 fn run_no_stdin(settings: Settings) -> Result<()> {
+    let mut saved_stats = saved_stats_writer(&settings)?;
+
     if let Some(file_path) = &settings.args.file_path {
-        let file_stats = process_ndjson_file_path(&settings, file_path)?;
+        let file_stats_list = process_ndjson_file_path(&settings, file_path)?;
 
-        file_stats.print()?;
+        print_query_stats(&settings, &file_stats_list)?;
+        for file_stats in &file_stats_list {
+            save_stats(&mut saved_stats, file_stats)?;
+        }
+        check_budget(&settings, &file_stats_list)
+            .with_context(|| format!("File over the error budget: {}", file_path.display()))?;
         return Ok(());
     }
 
     if let Some(pattern) = &settings.args.glob {
-        let mut file_stats_list = Vec::new();
+        let mut all_file_stats = Vec::new();
+        // Files over the error budget fail the run once all of them are read, unless failing fast
+        let mut over_budget = Ok(());
+        // The HTML report is a single page with a tab per file, so is written once at the end
+        let html = settings.output.format == OutputFormat::Html;
 
-        println!("Glob '{}':", pattern);
+        settings.print_heading(&format!("Glob '{}':", pattern));
         let file_paths = glob(pattern).context(
             "Failed to parse glob pattern, try quoting '<pattern>' to avoid shell parsing",
         )?;
         for entry in file_paths {
-            let file_path = entry?;
-            println!("File '{}':", file_path.display());
-            let file_stats = ndjson::FileStats::new(
-                file_path.to_string_lossy().into_owned(),
-                process_ndjson_file_path(&settings, &file_path)?,
-            );
-
-            file_stats.stats.print().with_context(|| {
-                format!("Failed to print stats for file: {}", file_path.display())
-            })?;
-            if settings.args.merge {
-                file_stats_list.push(file_stats)
+            let file_path = entry.context(InputUnreadable(
+                "Failed to read path matching glob pattern".to_string(),
+            ))?;
+            settings.print_heading(&format!("File '{}':", file_path.display()));
+            let file_stats_list = process_ndjson_file_path(&settings, &file_path)?;
+
+            if !html {
+                print_query_stats(&settings, &file_stats_list).with_context(|| {
+                    format!("Failed to print stats for file: {}", file_path.display())
+                })?;
+            }
+            for file_stats in &file_stats_list {
+                save_stats(&mut saved_stats, file_stats)?;
+            }
+            let checked = check_budget(&settings, &file_stats_list)
+                .with_context(|| format!("File over the error budget: {}", file_path.display()));
+            if settings.args.merge || html {
+                all_file_stats.extend(file_stats_list)
+            }
+            if checked.is_err() && settings.args.fail_fast {
+                return checked;
             }
+            over_budget = over_budget.and(checked);
         }
-        if settings.args.merge {
-            println!("Overall Stats");
-            let overall_file_stats: ndjson::Stats = file_stats_list.iter().sum();
-            overall_file_stats
-                .print()
+        let overall_file_stats = if settings.args.merge {
+            merge_query_stats(&settings, &all_file_stats, "Overall Stats")
+        } else {
+            Vec::new()
+        };
+        if html {
+            all_file_stats.extend(overall_file_stats);
+            print_html_report(&settings, &all_file_stats);
+        } else if !overall_file_stats.is_empty() {
+            settings.print_heading("Overall Stats");
+            print_query_stats(&settings, &overall_file_stats)
                 .context("Failed to print combined stats")?;
         }
-        return Ok(());
+        return over_budget;
+    }
+    Ok(())
+}
+
+/// Checks the overall stats of each `--jsonpath` query against the `--max-bad-lines` and
+/// `--max-empty-lines` budgets
+fn check_budget(settings: &Settings, file_stats_list: &[ndjson::FileStats]) -> Result<()> {
+    for file_stats in file_stats_list.iter().filter(|fs| fs.group.is_none()) {
+        settings.budget.check(&file_stats.stats)?;
     }
     Ok(())
 }
@@ -175,27 +838,195 @@ fn print_completions(args: Cli) {
 
 pub fn run(args: Cli) -> Result<()> {
     let now = Instant::now();
-    let settings = Settings::init(args).context("Failed to initialise settings from CLI args")?;
-    let quiet = settings.args.quiet;
-    if settings.args.generate_completions.is_some() {
-        print_completions(settings.args);
+    // Handled before the settings, which create the side files such as `--bad-lines-out`
+    if args.generate_completions.is_some() {
+        print_completions(args);
         return Ok(());
-    } else if is_readable_stdin() {
-        run_stdin(settings).context("Failed to process stdin")?;
-    } else if settings.args == Cli::default() {
+    }
+    let readable_stdin = is_readable_stdin();
+    if args.command.is_none() && !readable_stdin && args == Cli::default() {
         let mut cmd = Cli::command();
         cmd.print_help().context("Failed to print CLI help")?;
         return Ok(());
-    } else {
-        run_no_stdin(settings).context("Failed to process file(s)")?;
     }
+    let settings = Settings::init(args).context("Failed to initialise settings from CLI args")?;
+    let quiet = settings.args.quiet;
+    let quarantine = settings.quarantine();
+    let clean_copy = settings.clean_copy.clone();
+    let processed = if let Some(Command::Merge { stats_files }) = &settings.args.command {
+        let stats_files = stats_files.to_owned();
+        run_merge(settings, &stats_files).context("Failed to merge saved stats")
+    } else if readable_stdin {
+        run_stdin(settings).context("Failed to process stdin")
+    } else {
+        run_no_stdin(settings).context("Failed to process file(s)")
+    };
+    // The side files are completed even when the stats are over the error budget
+    let finished = quarantine
+        .finish()
+        .context("Failed to write --bad-lines-out or --empty-lines-out file")
+        .and(clean_copy.map_or(Ok(()), |clean_copy| {
+            clean_copy
+                .finish()
+                .context("Failed to write --clean-out file")
+        }));
     if !quiet {
         eprintln!("Completed in {}", format_duration(now.elapsed()));
     }
-    Ok(())
+    finish_run(processed, finished)
+}
+
+/// The result of a run `processed` into side files `finished`. The error processing, such as
+/// being over the error budget, decides the exit code, with that finishing only reported
+fn finish_run(processed: Result<()>, finished: Result<()>) -> Result<()> {
+    match (processed, finished) {
+        (Err(error), Err(finish_error)) => {
+            eprintln!("{finish_error:?}");
+            Err(error)
+        }
+        (processed, finished) => processed.and(finished),
+    }
 }
 
 #[test]
 fn verify_cli() {
     Cli::command().debug_assert()
 }
+
+#[test]
+fn completions_leave_side_files_alone() {
+    let mut bad_lines = tempfile::NamedTempFile::new().unwrap();
+    write!(bad_lines, "kept").unwrap();
+    run(Cli {
+        generate_completions: Some(Shell::Bash),
+        bad_lines_out: Some(bad_lines.path().to_path_buf()),
+        clean_out: Some(bad_lines.path().to_path_buf()),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(std::fs::read_to_string(bad_lines.path()).unwrap(), "kept");
+}
+
+#[test]
+fn budget_failure_outranks_side_files() {
+    let exceeded = || {
+        Err(anyhow::Error::new(BudgetExceeded::BadLines {
+            count: 1,
+            total: 2,
+            max: Threshold::Count(0),
+        }))
+    };
+    let unwritten = || Err(anyhow::anyhow!("Failed to write --clean-out file"));
+    let failure = |result: Result<()>| Failure::of(&result.unwrap_err());
+    assert_eq!(
+        failure(finish_run(exceeded(), unwritten())),
+        Failure::BudgetExceeded
+    );
+    assert_eq!(
+        failure(finish_run(exceeded(), Ok(()))),
+        Failure::BudgetExceeded
+    );
+    assert_eq!(failure(finish_run(Ok(()), unwritten())), Failure::Other);
+    assert!(finish_run(Ok(()), Ok(())).is_ok());
+}
+
+#[test]
+fn failure_exit_codes() {
+    let exceeded = |exceeded| {
+        Err::<(), _>(exceeded)
+            .context("File over the error budget: in.json")
+            .context("Failed to process file(s)")
+            .unwrap_err()
+    };
+    let max = Threshold::Count(0);
+    assert_eq!(
+        Failure::of(&exceeded(BudgetExceeded::BadLines {
+            count: 1,
+            total: 2,
+            max
+        })),
+        Failure::BudgetExceeded
+    );
+    assert_eq!(
+        Failure::of(&exceeded(BudgetExceeded::EmptyLines {
+            count: 1,
+            total: 2,
+            max
+        })),
+        Failure::ValidationFailed
+    );
+
+    let settings = Settings::init(Cli::default()).unwrap();
+    let missing = PathBuf::from("missing.json");
+    let error = process_ndjson_file_path(&settings, &missing)
+        .context("Failed to process file(s)")
+        .unwrap_err();
+    assert_eq!(Failure::of(&error), Failure::InputUnreadable);
+    assert_eq!(Failure::of(&anyhow::anyhow!("other")).exit_code(), 1);
+}
+
+#[test]
+fn saved_stats_round_trip() {
+    let stats = |key: &str, line_count| ndjson::Stats {
+        keys_count: json::IndexMap::from([(key.to_string(), line_count)]),
+        line_count,
+        bad_lines: vec![json::ndjson::RecordLocation::from(2)].into(),
+        ..Default::default()
+    };
+    let file_stats_list = vec![
+        ndjson::FileStats::new("file/1.json".to_string(), stats("$.key1", 3)),
+        ndjson::FileStats::new("file/2.json".to_string(), stats("$.key2", 4)),
+    ];
+
+    let save_path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+    let settings = Settings::init(Cli {
+        save_stats: Some(save_path.to_path_buf()),
+        ..Default::default()
+    })
+    .unwrap();
+    let mut writer = saved_stats_writer(&settings).unwrap();
+    for file_stats in &file_stats_list {
+        save_stats(&mut writer, file_stats).unwrap();
+    }
+    drop(writer);
+
+    let loaded = load_saved_stats(&settings, &save_path.to_path_buf()).unwrap();
+    assert_eq!(loaded, file_stats_list);
+    assert_eq!(
+        loaded.iter().sum::<ndjson::Stats>(),
+        file_stats_list.iter().sum::<ndjson::Stats>()
+    );
+}
+
+#[test]
+fn merge_grouped_stats() {
+    let file_stats = |file_path: &str, group: Option<&str>, line_count| ndjson::FileStats {
+        group: group.map(|group| ndjson::Group::Value(group.to_string())),
+        ..ndjson::FileStats::new(
+            file_path.to_string(),
+            ndjson::Stats {
+                line_count,
+                ..Default::default()
+            },
+        )
+    };
+    let file_stats_list = vec![
+        file_stats("file/1.json", Some("b"), 1),
+        file_stats("file/1.json", None, 1),
+        file_stats("file/2.json", Some("a"), 2),
+        file_stats("file/2.json", Some("b"), 3),
+        file_stats("file/2.json", None, 5),
+    ];
+    let settings = Settings::init(Cli::default()).unwrap();
+
+    let merged = merge_query_stats(&settings, &file_stats_list, "Overall Stats");
+    let merged: Vec<_> = merged
+        .iter()
+        .map(|file_stats| {
+            let group = file_stats.group.as_ref().map(ToString::to_string);
+            (group, file_stats.stats.line_count)
+        })
+        .collect();
+    let group = |group: &str| Some(group.to_string());
+    assert_eq!(merged, [(group("a"), 2), (group("b"), 4), (None, 6)]);
+}
@@ -1,4 +1,4 @@
-use analyse_json::Cli;
+use analyse_json::{Cli, Failure};
 use clap::Parser;
 use std::process;
 
@@ -10,6 +10,6 @@ fn main() {
     if let Err(e) = analyse_json::run(args) {
         eprintln!("Application error:\n{:?}", e);
 
-        process::exit(1);
+        process::exit(Failure::of(&e).exit_code());
     }
 }